@@ -1,21 +1,244 @@
 // module schema
-use clap::Parser;
+use crate::handlers::output::IssueFormat;
+use crate::handlers::render::ReportFormat;
+use clap::builder::RangedU64ValueParser;
+use clap::{ArgGroup, Args, Parser, Subcommand};
 
 /// cli struct
 #[derive(Parser, Debug)]
 #[command(name = env!("CARGO_PKG_NAME"))]
 #[command(author = env!("CARGO_PKG_AUTHORS"))]
 #[command(version = env!("CARGO_PKG_VERSION"))]
-#[command(about = "Generic AI Agent", long_about = None)]
+#[command(
+    about = "Jira status reports, issue lookups and report jobs from the command line",
+    long_about = None
+)]
 #[command(
     help_template = "{author-with-newline} {about-section}Version: {version} \n {usage-heading} {usage} \n {all-args} {tab}"
 )]
+#[command(subcommand_required = true, arg_required_else_help = true)]
 pub struct Cli {
-    /// config file to use
-    #[arg(short, long, value_name = "config")]
-    pub config: String,
+    #[command(subcommand)]
+    pub command: Command,
+
+    /// config file to use (json, toml or yaml), JIRA_SERVICE_* variables override its settings
+    #[arg(short, long, value_name = "config", global = true)]
+    pub config: Option<String>,
+
+    /// overrides a config setting, nested keys are dotted (--set auth.method=basic), repeatable
+    #[arg(long, value_name = "key=value", global = true)]
+    pub set: Vec<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// write the status report of a list of epics or of the epics a jql query selects
+    Report(ReportArgs),
+
+    /// print a single issue
+    Get {
+        /// issue key (WRIG-1)
+        key: String,
+
+        /// output format
+        #[arg(short, long, value_name = "format", default_value = "text")]
+        format: IssueFormat,
+
+        /// bypass the issue cache
+        #[arg(short, long)]
+        refresh: bool,
+    },
+
+    /// list the issues matching a jql query as a table
+    Search {
+        /// jql query (project = WRIG AND status = "In Progress")
+        jql: String,
+
+        /// stop after this many issues
+        #[arg(
+            short,
+            long,
+            value_name = "limit",
+            default_value_t = 50,
+            value_parser = RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        limit: usize,
+    },
+
+    /// inspect or clear the issue cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheCommand,
+    },
+
+    /// manage the report jobs (formdata) processed by the worker
+    Queue {
+        #[command(subcommand)]
+        action: QueueCommand,
+    },
+
+    /// run as an http server on server_address
+    Serve {
+        /// listen address, overrides server_address (127.0.0.1:8090)
+        #[arg(short, long, value_name = "address")]
+        address: Option<String>,
+    },
+
+    /// process report jobs from the formdata queue until interrupted
+    Worker,
+
+    /// add a comment (jira wiki markup) to an issue
+    Comment {
+        /// issue key
+        key: String,
+
+        /// comment text
+        #[arg(short, long, value_name = "message")]
+        message: String,
+
+        /// print the request instead of sending it
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// list the transitions available for an issue
+    Transitions {
+        /// issue key
+        key: String,
+    },
+
+    /// move an issue through a transition
+    Transition {
+        /// issue key
+        key: String,
+
+        /// transition name, id or target status
+        #[arg(long, value_name = "to")]
+        to: String,
+
+        /// comment (jira wiki markup) added with the transition
+        #[arg(short, long, value_name = "message")]
+        message: Option<String>,
+
+        /// print the request instead of sending it
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// create the stories of a yaml or markdown spec under its epic (existing summaries are
+    /// skipped)
+    Create {
+        /// spec file
+        spec: String,
+
+        /// print the requests instead of sending them
+        #[arg(long)]
+        dry_run: bool,
+    },
 
-    /// comma delimted list of issues (WRIG-A,WRIG-B)
+    /// inspect the layered config
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+}
+
+#[derive(Args, Debug)]
+#[command(group(ArgGroup::new("query").required(true).args(["issues", "jql"])))]
+pub struct ReportArgs {
+    /// comma delimited list of epics (WRIG-A,WRIG-B)
     #[arg(short, long, value_name = "issues")]
-    pub issues: String,
+    pub issues: Option<String>,
+
+    /// jql query used to select the epics (project = WRIG AND issuetype = Epic)
+    #[arg(short, long, value_name = "jql")]
+    pub jql: Option<String>,
+
+    /// comma delimited output formats (markdown,html,json,csv,asciidoc), overrides the config
+    #[arg(short, long, value_name = "format", value_delimiter = ',')]
+    pub format: Vec<ReportFormat>,
+
+    /// start of the reporting window (YYYY-MM-DD), defaults to 14 days before --until
+    #[arg(long, value_name = "since")]
+    pub since: Option<String>,
+
+    /// end of the reporting window (YYYY-MM-DD), defaults to now
+    #[arg(long, value_name = "until")]
+    pub until: Option<String>,
+
+    /// bypass the issue cache and fetch every issue from jira
+    #[arg(short, long)]
+    pub refresh: bool,
+
+    /// post the status summary of each epic as a comment on the epic after the report is written
+    #[arg(long)]
+    pub comment_epics: bool,
+
+    /// print the epic comments instead of sending them
+    #[arg(long, requires = "comment_epics")]
+    pub dry_run: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CacheCommand {
+    /// list the cached issues, or print the cached json of one issue
+    Show {
+        /// issue key
+        key: Option<String>,
+    },
+
+    /// remove one cached issue, or every cached issue with --all
+    #[command(group(ArgGroup::new("target").required(true).args(["key", "all"])))]
+    Clear {
+        /// issue key
+        key: Option<String>,
+
+        /// remove every cached issue
+        #[arg(long)]
+        all: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum QueueCommand {
+    /// list the queued report jobs
+    List,
+
+    /// queue a report job (an existing job with the same key is replaced)
+    Add {
+        /// job key
+        key: String,
+
+        /// issue keys (WRIG-1,WRIG-4) or a jql query
+        #[arg(short, long, value_name = "prompt")]
+        prompt: String,
+
+        /// key of the generated document in the documents store (report.html)
+        #[arg(long, value_name = "file")]
+        file: String,
+
+        /// report title, defaults to report_title
+        #[arg(short, long, value_name = "title", default_value = "")]
+        title: String,
+
+        /// output format, defaults to the extension of --file
+        #[arg(short, long, value_name = "format")]
+        format: Option<ReportFormat>,
+
+        /// keep the job queued and regenerate the document on every poll
+        #[arg(long)]
+        repeat: bool,
+    },
+
+    /// remove a queued report job
+    Remove {
+        /// job key
+        key: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// check the merged config and report every problem found
+    Validate,
 }
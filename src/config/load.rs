@@ -1,29 +1,540 @@
+use crate::error::jira::JiraServiceError;
+use crate::handlers::render::ReportFormat;
+use serde::de::{self, Deserialize, Deserializer, IntoDeserializer};
 use serde_derive::{Deserialize, Serialize};
-use std::fs::File;
+use serde_json::{Map, Value};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Parameters {
+    #[serde(default = "default_name")]
     pub name: String,
+    #[serde(default)]
     pub description: String,
+    /// info, debug or trace (defaults to info)
+    #[serde(default = "default_log_level")]
     pub log_level: String,
+    /// issue endpoint of the rest api (https://jira.example.com/rest/api/2/issue/), required
+    /// unless test is set
+    #[serde(default)]
     pub base_url: String,
+    /// token file of the default bearer authentication, not needed when auth selects another
+    /// source
+    #[serde(default)]
     pub api_key_path: String,
+    /// staging, cache and queue directories live here (defaults to the current directory)
+    #[serde(default = "default_working_dir")]
     pub working_dir: String,
+    /// report file written to {working_dir}/staging (defaults to biweekly-report.md)
+    #[serde(default = "default_document_name")]
     pub document_name: String,
+    /// serve issues from the fixtures instead of jira
+    #[serde(default, deserialize_with = "from_text")]
     pub test: bool,
+    /// directory holding the json fixtures served in test mode
+    #[serde(default)]
+    pub fixtures_dir: Option<String>,
+    /// maximum number of concurrent jira requests (defaults to 4)
+    #[serde(default, deserialize_with = "option_from_text")]
+    pub max_concurrency: Option<usize>,
+    /// jira rest api version, 2 (server and data center) or 3 (cloud, descriptions and comments
+    /// are atlassian documents), defaults to the version in base_url
+    #[serde(default, deserialize_with = "option_from_text")]
+    pub api_version: Option<u32>,
+    /// web ui root used for issue links (https://jira.example.com/browse/), defaults to the
+    /// host and context path of base_url
+    #[serde(default)]
+    pub browse_url: Option<String>,
+    /// how requests authenticate, a bearer token read from api_key_path when not set
+    #[serde(default)]
+    pub auth: AuthOptions,
+    /// attempts per jira request, transient failures (429, 5xx, timeouts) are retried
+    /// (defaults to 4)
+    #[serde(default, deserialize_with = "option_from_text")]
+    pub retry_max_attempts: Option<u32>,
+    /// seconds a jira request may spend retrying (defaults to 60)
+    #[serde(default, deserialize_with = "option_from_text")]
+    pub retry_deadline_secs: Option<u64>,
+    /// link types that select the stories of an epic, matched against the link type name
+    /// (Relates) or its description from the epic (is parent of), all outward links count
+    /// when empty
+    #[serde(default, deserialize_with = "list_from_text")]
+    pub story_link_types: Vec<String>,
+    /// seconds a cached issue is served before it is revalidated (defaults to 3600)
+    #[serde(default, deserialize_with = "option_from_text")]
+    pub cache_ttl_secs: Option<u64>,
+    /// markdown report template file, the built in templates/report.md is used when not set
+    #[serde(default)]
+    pub template_path: Option<String>,
+    /// title passed to the report template
+    #[serde(default)]
+    pub report_title: Option<String>,
+    /// default output formats (markdown, html, json, csv, asciidoc)
+    #[serde(default, deserialize_with = "option_list_from_text")]
+    pub formats: Option<Vec<ReportFormat>>,
+    /// address the http server listens on (defaults to 127.0.0.1:8090)
+    #[serde(default)]
+    pub server_address: Option<String>,
+    /// directory of the formdata queue, archive and documents stores (defaults to
+    /// {working_dir}/formdata)
+    #[serde(default)]
+    pub queue_db_path: Option<String>,
+    /// seconds between queue polls in worker mode (defaults to 30)
+    #[serde(default, deserialize_with = "option_from_text")]
+    pub poll_interval_secs: Option<u64>,
+    /// filtering and ordering of story comments
+    #[serde(default)]
+    pub comments: CommentOptions,
+    /// discovery of epic children and subtasks
+    #[serde(default)]
+    pub hierarchy: HierarchyOptions,
+    /// custom field holding story points (customfield_10016), counted in the summary table
+    #[serde(default)]
+    pub story_points_field: Option<String>,
+    /// working time used to show effort durations
+    #[serde(default)]
+    pub time_tracking: TimeTrackingOptions,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CommentOptions {
+    /// only keep comments created or updated inside the reporting window
+    #[serde(default = "default_true", deserialize_with = "from_text")]
+    pub in_window: bool,
+    /// keep only the last N comments
+    #[serde(default, deserialize_with = "option_from_text")]
+    pub limit: Option<usize>,
+    /// list the newest comment first
+    #[serde(default, deserialize_with = "from_text")]
+    pub newest_first: bool,
+    /// authors (name, key, display name or account id) whose comments are dropped, e.g.
+    /// automation users
+    #[serde(default, deserialize_with = "list_from_text")]
+    pub exclude_authors: Vec<String>,
+}
+
+impl Default for CommentOptions {
+    fn default() -> Self {
+        CommentOptions {
+            in_window: true,
+            limit: None,
+            newest_first: false,
+            exclude_authors: vec![],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthMethod {
+    /// personal access token (jira server and data center)
+    #[default]
+    Bearer,
+    /// user (email on jira cloud) and api token
+    Basic,
+    /// login and password of the base_url host in a .netrc file, sent as basic auth
+    Netrc,
+    /// token from an environment variable, basic auth when user is set, else bearer
+    Env,
+}
+
+/// only paths and variable names are configured, the secrets themselves are read at startup
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct AuthOptions {
+    #[serde(default)]
+    pub method: AuthMethod,
+    /// user name or email for basic and env
+    #[serde(default)]
+    pub user: Option<String>,
+    /// token file for bearer and basic (defaults to api_key_path)
+    #[serde(default)]
+    pub token_path: Option<String>,
+    /// variable holding the token for env (defaults to JIRA_API_TOKEN)
+    #[serde(default)]
+    pub token_env: Option<String>,
+    /// netrc file (defaults to ~/.netrc)
+    #[serde(default)]
+    pub netrc_path: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct HierarchyOptions {
+    /// jql finding the children of an epic, {key} is replaced by the epic key and an empty
+    /// query disables the search (defaults to "Epic Link" = {key} OR parent = {key})
+    #[serde(default)]
+    pub child_jql: Option<String>,
+    /// levels of subtasks walked below each story, 0 disables (defaults to 2)
+    #[serde(default, deserialize_with = "option_from_text")]
+    pub subtask_depth: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TimeTrackingOptions {
+    /// hours in a working day, as configured in jira (defaults to 8)
+    #[serde(default, deserialize_with = "option_from_text")]
+    pub hours_per_day: Option<u32>,
+    /// days in a working week, as configured in jira (defaults to 5)
+    #[serde(default, deserialize_with = "option_from_text")]
+    pub days_per_week: Option<u32>,
+}
+
+/// a setting given as text (environment variables, --set), numbers and flags are parsed from
+/// it
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Lenient<T> {
+    Value(T),
+    Text(String),
+}
+
+fn from_text<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr,
+    T::Err: fmt::Display,
+{
+    match Lenient::<T>::deserialize(deserializer)? {
+        Lenient::Value(value) => Ok(value),
+        Lenient::Text(text) => text
+            .trim()
+            .parse()
+            .map_err(|e| de::Error::custom(format!("{} ({})", text, e))),
+    }
+}
+
+fn option_from_text<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr,
+    T::Err: fmt::Display,
+{
+    match Option::<Lenient<T>>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Lenient::Value(value)) => Ok(Some(value)),
+        Some(Lenient::Text(text)) => text
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|e| de::Error::custom(format!("{} ({})", text, e))),
+    }
+}
+
+/// a list setting given as text is split on commas (JIRA_SERVICE_FORMATS=html,csv)
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LenientList<T> {
+    Values(Vec<T>),
+    Text(String),
+}
+
+fn split_text<T, E>(text: &str) -> Result<Vec<T>, E>
+where
+    T: de::DeserializeOwned,
+    E: de::Error,
+{
+    text.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            T::deserialize(item.to_string().into_deserializer())
+                .map_err(|e: E| de::Error::custom(format!("{} ({})", text, e)))
+        })
+        .collect()
+}
+
+fn list_from_text<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: de::DeserializeOwned,
+{
+    match LenientList::<T>::deserialize(deserializer)? {
+        LenientList::Values(values) => Ok(values),
+        LenientList::Text(text) => split_text(&text),
+    }
+}
+
+fn option_list_from_text<'de, D, T>(deserializer: D) -> Result<Option<Vec<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: de::DeserializeOwned,
+{
+    match Option::<LenientList<T>>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(LenientList::Values(values)) => Ok(Some(values)),
+        Some(LenientList::Text(text)) => split_text(&text).map(Some),
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_name() -> String {
+    env!("CARGO_PKG_NAME").to_string()
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+fn default_working_dir() -> String {
+    ".".to_string()
+}
+
+fn default_document_name() -> String {
+    "biweekly-report.md".to_string()
+}
+
+impl Parameters {
+    /// expands a leading ~ in the configured paths
+    fn expand_paths(&mut self) {
+        for path in [&mut self.api_key_path, &mut self.working_dir] {
+            *path = expand_home(path);
+        }
+        for path in [
+            &mut self.fixtures_dir,
+            &mut self.template_path,
+            &mut self.queue_db_path,
+            &mut self.auth.token_path,
+            &mut self.auth.netrc_path,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *path = expand_home(path);
+        }
+    }
+}
+
+/// ~ and ~/path are resolved against HOME, other paths are returned as they are
+pub fn expand_home(path: &str) -> String {
+    let home = || std::env::var("HOME").unwrap_or_default();
+    match path.strip_prefix('~') {
+        Some("") => home(),
+        Some(rest) if rest.starts_with('/') => format!("{}{}", home(), rest),
+        _ => path.to_string(),
+    }
+}
+
+/// prefix of the environment variables overriding config keys, __ separates nested keys
+/// (JIRA_SERVICE_AUTH__METHOD=basic sets auth.method)
+const ENV_PREFIX: &str = "JIRA_SERVICE_";
+
 pub trait ConfigInterface {
-    fn read(&self, dir: String) -> Result<Parameters, Box<dyn std::error::Error>>;
+    fn read(
+        &self,
+        name: Option<String>,
+        overrides: &[String],
+    ) -> Result<Parameters, Box<dyn std::error::Error>>;
 }
 
 #[derive(Debug, Clone)]
 pub struct ImplConfigInterface {}
 
 impl ConfigInterface for ImplConfigInterface {
-    fn read(&self, name: String) -> Result<Parameters, Box<dyn std::error::Error>> {
-        let json_data = File::open(&name)?;
-        let params = serde_json::from_reader(json_data)?;
+    /// layers, later ones win : the config file (json, toml or yaml), JIRA_SERVICE_* variables
+    /// and the cli overrides (key=value with dotted keys), field defaults fill in the rest
+    fn read(
+        &self,
+        name: Option<String>,
+        overrides: &[String],
+    ) -> Result<Parameters, Box<dyn std::error::Error>> {
+        let source = name.clone().unwrap_or("defaults".to_string());
+        let mut config = match &name {
+            Some(name) => read_file(name)?,
+            None => Value::Object(Map::new()),
+        };
+        apply_overrides(&mut config, std::env::vars(), overrides)?;
+        let mut params: Parameters =
+            serde_json::from_value(config).map_err(|e| JiraServiceError::config(&source, e))?;
+        params.expand_paths();
+        // issue urls are built by appending the key
+        if !params.base_url.is_empty() && !params.base_url.ends_with('/') {
+            params.base_url.push('/');
+        }
         Ok(params)
     }
 }
+
+/// sets the JIRA_SERVICE_* variables and then the cli overrides over the file settings
+fn apply_overrides(
+    config: &mut Value,
+    vars: impl IntoIterator<Item = (String, String)>,
+    overrides: &[String],
+) -> Result<(), JiraServiceError> {
+    for (key, value) in vars {
+        if let Some(key) = key.strip_prefix(ENV_PREFIX) {
+            let path = key.to_lowercase();
+            set_path(config, &path.split("__").collect::<Vec<&str>>(), &value);
+        }
+    }
+    for item in overrides {
+        let (key, value) = item
+            .split_once('=')
+            .ok_or_else(|| JiraServiceError::Config {
+                path: "--set".to_string(),
+                message: format!("{} is not key=value", item),
+            })?;
+        set_path(config, &key.trim().split('.').collect::<Vec<&str>>(), value);
+    }
+    Ok(())
+}
+
+/// the format follows the extension (.toml, .yaml, .yml), anything else is read as json
+fn read_file(name: &str) -> Result<Value, JiraServiceError> {
+    let data = fs::read_to_string(name).map_err(|e| JiraServiceError::config(name, e))?;
+    let extension = Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let value: Value = match extension.as_str() {
+        "toml" => toml::from_str(&data).map_err(|e| JiraServiceError::config(name, e))?,
+        "yaml" | "yml" => {
+            serde_yaml::from_str(&data).map_err(|e| JiraServiceError::config(name, e))?
+        }
+        _ => serde_json::from_str(&data).map_err(|e| JiraServiceError::config(name, e))?,
+    };
+    match value {
+        Value::Object(_) => Ok(value),
+        // an empty yaml file
+        Value::Null => Ok(Value::Object(Map::new())),
+        _ => Err(JiraServiceError::Config {
+            path: name.to_string(),
+            message: "expected a map of settings".to_string(),
+        }),
+    }
+}
+
+/// sets a nested key, values that look like json (a quoted string, a list or a map) are parsed
+/// and everything else is kept as a string, numeric, boolean and list settings parse their
+/// text so report_title=2024 stays a string and formats=html,csv becomes a list
+fn set_path(config: &mut Value, path: &[&str], value: &str) {
+    let value = match value.trim_start().starts_with(['"', '[', '{']) {
+        true => serde_json::from_str(value).unwrap_or(Value::String(value.to_string())),
+        false => Value::String(value.to_string()),
+    };
+    let mut current = config;
+    for (index, key) in path.iter().enumerate() {
+        if !current.is_object() {
+            *current = Value::Object(Map::new());
+        }
+        let Value::Object(map) = current else {
+            return;
+        };
+        if index == path.len() - 1 {
+            map.insert(key.to_string(), value);
+            return;
+        }
+        current = map
+            .entry(key.to_string())
+            .or_insert(Value::Object(Map::new()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_overrides(overrides: &[(&str, &str)]) -> Result<Parameters, serde_json::Error> {
+        let mut config = Value::Object(Map::new());
+        for (key, value) in overrides {
+            set_path(&mut config, &key.split('.').collect::<Vec<&str>>(), value);
+        }
+        serde_json::from_value(config)
+    }
+
+    #[test]
+    fn text_overrides_follow_the_field_type() {
+        let params = with_overrides(&[
+            ("report_title", "2024"),
+            ("auth.user", "12345"),
+            ("test", "true"),
+            ("max_concurrency", "8"),
+            ("comments.in_window", "false"),
+            ("hierarchy.subtask_depth", " 1 "),
+            ("story_link_types", "[\"Relates\", \"Blocks\"]"),
+            ("browse_url", "\"https://jira.example.com/browse/\""),
+        ])
+        .unwrap();
+        assert_eq!(params.report_title.as_deref(), Some("2024"));
+        assert_eq!(params.auth.user.as_deref(), Some("12345"));
+        assert!(params.test);
+        assert_eq!(params.max_concurrency, Some(8));
+        assert!(!params.comments.in_window);
+        assert_eq!(params.hierarchy.subtask_depth, Some(1));
+        assert_eq!(params.story_link_types, vec!["Relates", "Blocks"]);
+        assert_eq!(
+            params.browse_url.as_deref(),
+            Some("https://jira.example.com/browse/")
+        );
+        assert!(with_overrides(&[("max_concurrency", "many")]).is_err());
+    }
+
+    fn load(vars: &[(&str, &str)], overrides: &[&str]) -> Parameters {
+        let mut config = Value::Object(Map::new());
+        let vars = vars
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()));
+        let overrides = overrides
+            .iter()
+            .map(|item| item.to_string())
+            .collect::<Vec<String>>();
+        apply_overrides(&mut config, vars, &overrides).unwrap();
+        serde_json::from_value(config).unwrap()
+    }
+
+    #[test]
+    fn env_list_overrides() {
+        let params = load(
+            &[
+                ("JIRA_SERVICE_FORMATS", "html"),
+                ("JIRA_SERVICE_STORY_LINK_TYPES", "Relates, is parent of"),
+                ("JIRA_SERVICE_COMMENTS__EXCLUDE_AUTHORS", "bot"),
+                ("OTHER_FORMATS", "csv"),
+            ],
+            &[],
+        );
+        assert_eq!(params.formats, Some(vec![ReportFormat::Html]));
+        assert_eq!(params.story_link_types, vec!["Relates", "is parent of"]);
+        assert_eq!(params.comments.exclude_authors, vec!["bot"]);
+    }
+
+    #[test]
+    fn set_list_overrides() {
+        let params = load(
+            &[("JIRA_SERVICE_FORMATS", "html")],
+            &[
+                "formats=md,csv,",
+                "story_link_types=Relates",
+                "comments.exclude_authors=[\"bot\", \"jenkins\"]",
+            ],
+        );
+        assert_eq!(
+            params.formats,
+            Some(vec![ReportFormat::Markdown, ReportFormat::Csv])
+        );
+        assert_eq!(params.story_link_types, vec!["Relates"]);
+        assert_eq!(params.comments.exclude_authors, vec!["bot", "jenkins"]);
+        assert_eq!(load(&[], &["formats="]).formats, Some(vec![]));
+        assert!(with_overrides(&[("formats", "html,pdf")]).is_err());
+    }
+
+    #[test]
+    fn file_values_keep_their_type() {
+        let params: Parameters = serde_json::from_value(serde_json::json!({
+            "test": true,
+            "max_concurrency": 2,
+            "comments": { "limit": 3, "newest_first": true },
+        }))
+        .unwrap();
+        assert!(params.test);
+        assert_eq!(params.max_concurrency, Some(2));
+        assert_eq!(params.comments.limit, Some(3));
+        assert!(params.comments.newest_first);
+        assert!(params.comments.in_window);
+    }
+}
@@ -0,0 +1,139 @@
+// module validate
+//
+// checks a loaded config before anything is sent to jira, every problem is collected so a
+// single run reports all of them
+//
+// base_url     http(s) url with a host pointing at the rest api (skipped in test mode)
+// api_version  2 or 3
+// credentials  the configured auth source resolves (api_key_path exists for bearer)
+// working_dir  {working_dir}/staging can be created and written to (nothing is created)
+// paths        template_path, fixtures_dir (test mode) exist
+
+use crate::config::load::Parameters;
+use crate::error::jira::JiraServiceError;
+use crate::handlers::auth::Credentials;
+use crate::handlers::service::resolve_api_version;
+use std::fs;
+use std::path::Path;
+
+/// Ok with a summary when the config is usable, otherwise a config error listing every problem
+pub fn validate(source: &str, params: &Parameters) -> Result<String, JiraServiceError> {
+    let mut problems: Vec<String> = vec![];
+    if !["info", "debug", "trace"].contains(&params.log_level.as_str()) {
+        problems.push(format!(
+            "log_level : {} is not info, debug or trace",
+            params.log_level
+        ));
+    }
+    if !params.test {
+        if let Err(problem) = check_base_url(&params.base_url) {
+            problems.push(format!("base_url : {}", problem));
+        }
+        match Credentials::resolve(params) {
+            Err(JiraServiceError::Config { path, message }) => {
+                problems.push(format!("{} : {}", path, message))
+            }
+            Err(err) => problems.push(err.to_string()),
+            Ok(_) => {}
+        }
+    }
+    let api_version = resolve_api_version(params);
+    if !(2..=3).contains(&api_version) {
+        problems.push(format!(
+            "api_version : unsupported version {} (expected 2 or 3)",
+            api_version
+        ));
+    }
+    let staging = format!("{}/staging", params.working_dir);
+    if let Err(err) = check_writable(&staging) {
+        problems.push(format!(
+            "working_dir : {} is not writable ({})",
+            staging, err
+        ));
+    }
+    if let Some(path) = &params.template_path
+        && !Path::new(path).is_file()
+    {
+        problems.push(format!("template_path : {} does not exist", path));
+    }
+    if params.test {
+        let fixtures_dir = params.fixtures_dir.as_deref().unwrap_or("docs/fixtures");
+        if !Path::new(fixtures_dir).is_dir() {
+            problems.push(format!("fixtures_dir : {} does not exist", fixtures_dir));
+        }
+    }
+    match problems.len() {
+        0 => Ok(format!("config ok ({})", source)),
+        count => Err(JiraServiceError::Config {
+            path: source.to_string(),
+            message: format!(
+                "{} problem{}\n  - {}",
+                count,
+                if count == 1 { "" } else { "s" },
+                problems.join("\n  - ")
+            ),
+        }),
+    }
+}
+
+/// shape only, nothing is sent to the server
+fn check_base_url(base_url: &str) -> Result<(), String> {
+    if base_url.is_empty() {
+        return Err("not set (required unless test is set)".to_string());
+    }
+    let url = reqwest::Url::parse(base_url).map_err(|e| format!("{} ({})", base_url, e))?;
+    if !["http", "https"].contains(&url.scheme()) {
+        return Err(format!("{} is not an http(s) url", base_url));
+    }
+    if url.host_str().is_none_or(str::is_empty) {
+        return Err(format!("{} has no host", base_url));
+    }
+    if !url.path().contains("/rest/api/") {
+        return Err(format!(
+            "{} does not point at the rest api (.../rest/api/2/issue/)",
+            base_url
+        ));
+    }
+    Ok(())
+}
+
+/// writes a probe file into the directory, or into its nearest existing ancestor when it is
+/// not created yet (validating creates nothing)
+fn check_writable(dir: &str) -> std::io::Result<()> {
+    let mut existing = Path::new(dir);
+    while !existing.exists() {
+        existing = match existing.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ if existing != Path::new(".") => Path::new("."),
+            _ => return Err(std::io::ErrorKind::NotFound.into()),
+        };
+    }
+    if !existing.is_dir() {
+        return Err(std::io::Error::other(format!(
+            "{} is not a directory",
+            existing.display()
+        )));
+    }
+    let probe = existing.join(format!(".write-check-{}", std::process::id()));
+    fs::write(&probe, b"")?;
+    fs::remove_file(&probe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_writable_creates_nothing() {
+        let root = std::env::temp_dir().join(format!("validate-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let staging = root.join("work/staging");
+        assert!(check_writable(staging.to_str().unwrap()).is_ok());
+        assert!(!root.join("work").exists());
+
+        let file = root.join("file");
+        fs::write(&file, b"").unwrap();
+        assert!(check_writable(file.join("staging").to_str().unwrap()).is_err());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
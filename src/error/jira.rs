@@ -0,0 +1,349 @@
+use serde_json::Value;
+use std::error::Error;
+use std::fmt;
+
+/// errors surfaced to the user, each variant exits with its own process exit code
+#[derive(Debug, Clone)]
+pub enum JiraServiceError {
+    /// missing or invalid config (also the api key file)
+    Config {
+        path: String,
+        message: String,
+    },
+    /// 401 and 403
+    Auth {
+        status: u16,
+        key: Option<String>,
+        url: String,
+    },
+    /// 404
+    NotFound {
+        key: Option<String>,
+        url: String,
+    },
+    /// 429, retry_after in seconds when jira sent a Retry-After header
+    RateLimited {
+        key: Option<String>,
+        url: String,
+        retry_after: Option<u64>,
+    },
+    /// 5xx, retry_after in seconds when jira sent a Retry-After header (503)
+    Server {
+        status: u16,
+        key: Option<String>,
+        url: String,
+        message: String,
+        retry_after: Option<u64>,
+    },
+    /// any other rejected request (400, 405, 409 ...)
+    Request {
+        status: u16,
+        key: Option<String>,
+        url: String,
+        message: String,
+    },
+    /// connection refused, dns or tls failures and timeouts
+    Network {
+        key: Option<String>,
+        url: String,
+        message: String,
+    },
+    /// unexpected json from jira or a fixture
+    Parse {
+        key: Option<String>,
+        url: String,
+        message: String,
+    },
+    Io {
+        path: String,
+        message: String,
+    },
+    Other(String),
+}
+
+impl JiraServiceError {
+    /// maps a non success http status, the body is reduced to the jira error messages
+    pub fn from_status(
+        status: u16,
+        key: Option<&str>,
+        url: &str,
+        body: &[u8],
+        retry_after: Option<u64>,
+    ) -> Self {
+        let key = key.map(|key| key.to_string());
+        let url = url.to_string();
+        match status {
+            401 | 403 => JiraServiceError::Auth { status, key, url },
+            404 => JiraServiceError::NotFound { key, url },
+            429 => JiraServiceError::RateLimited {
+                key,
+                url,
+                retry_after,
+            },
+            500..=599 => JiraServiceError::Server {
+                status,
+                key,
+                url,
+                message: error_messages(body),
+                retry_after,
+            },
+            _ => JiraServiceError::Request {
+                status,
+                key,
+                url,
+                message: error_messages(body),
+            },
+        }
+    }
+
+    pub fn network(key: Option<&str>, url: &str, err: impl fmt::Display) -> Self {
+        JiraServiceError::Network {
+            key: key.map(|key| key.to_string()),
+            url: url.to_string(),
+            message: err.to_string(),
+        }
+    }
+
+    pub fn parse(key: Option<&str>, url: &str, err: impl fmt::Display) -> Self {
+        JiraServiceError::Parse {
+            key: key.map(|key| key.to_string()),
+            url: url.to_string(),
+            message: err.to_string(),
+        }
+    }
+
+    pub fn config(path: &str, err: impl fmt::Display) -> Self {
+        JiraServiceError::Config {
+            path: path.to_string(),
+            message: err.to_string(),
+        }
+    }
+
+    pub fn io(path: &str, err: impl fmt::Display) -> Self {
+        JiraServiceError::Io {
+            path: path.to_string(),
+            message: err.to_string(),
+        }
+    }
+
+    /// keeps the variant of boxed errors so they can cross task boundaries (Send)
+    pub fn from_boxed(err: Box<dyn Error>) -> Self {
+        match err.downcast::<JiraServiceError>() {
+            Ok(err) => *err,
+            Err(err) => JiraServiceError::Other(err.to_string()),
+        }
+    }
+
+    /// process exit code of the variant
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            JiraServiceError::Other(_) => 1,
+            JiraServiceError::Config { .. } => 3,
+            JiraServiceError::Auth { .. } => 4,
+            JiraServiceError::NotFound { .. } => 5,
+            JiraServiceError::RateLimited { .. } => 6,
+            JiraServiceError::Server { .. } => 7,
+            JiraServiceError::Request { .. } => 8,
+            JiraServiceError::Network { .. } => 9,
+            JiraServiceError::Parse { .. } => 10,
+            JiraServiceError::Io { .. } => 11,
+        }
+    }
+}
+
+/// exit code of any boxed error (1 for errors that are not a JiraServiceError, clap exits
+/// with 2 on usage errors)
+pub fn exit_code(err: &(dyn Error + 'static)) -> i32 {
+    match err.downcast_ref::<JiraServiceError>() {
+        Some(err) => err.exit_code(),
+        None => 1,
+    }
+}
+
+/// "WRIG-1 (url)" or just the url when no issue is involved
+fn target(key: &Option<String>, url: &str) -> String {
+    match key {
+        Some(key) => format!("{} ({})", key, url),
+        None => url.to_string(),
+    }
+}
+
+/// errorMessages and errors of a jira error body, else the (shortened) body
+fn error_messages(body: &[u8]) -> String {
+    if let Ok(value) = serde_json::from_slice::<Value>(body) {
+        let mut messages = vec![];
+        if let Some(list) = value["errorMessages"].as_array() {
+            messages.extend(list.iter().filter_map(|m| m.as_str()).map(String::from));
+        }
+        if let Some(errors) = value["errors"].as_object() {
+            for (field, message) in errors {
+                messages.push(format!(
+                    "{} : {}",
+                    field,
+                    message.as_str().unwrap_or_default()
+                ));
+            }
+        }
+        if !messages.is_empty() {
+            return messages.join(", ");
+        }
+    }
+    String::from_utf8_lossy(body).chars().take(200).collect()
+}
+
+impl fmt::Display for JiraServiceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JiraServiceError::Config { path, message } => {
+                write!(f, "config error in {} : {}", path, message)
+            }
+            JiraServiceError::Auth { status, key, url } => write!(
+                f,
+                "not authorized ({}) for {}, check the auth settings and token",
+                status,
+                target(key, url)
+            ),
+            JiraServiceError::NotFound { key, url } => {
+                write!(f, "not found : {}", target(key, url))
+            }
+            JiraServiceError::RateLimited {
+                key,
+                url,
+                retry_after,
+            } => match retry_after {
+                Some(secs) => write!(
+                    f,
+                    "rate limited by jira for {}, retry after {}s",
+                    target(key, url),
+                    secs
+                ),
+                None => write!(f, "rate limited by jira for {}", target(key, url)),
+            },
+            JiraServiceError::Server {
+                status,
+                key,
+                url,
+                message,
+                ..
+            } => write!(
+                f,
+                "jira server error ({}) for {} : {}",
+                status,
+                target(key, url),
+                message
+            ),
+            JiraServiceError::Request {
+                status,
+                key,
+                url,
+                message,
+            } => write!(
+                f,
+                "request rejected ({}) for {} : {}",
+                status,
+                target(key, url),
+                message
+            ),
+            JiraServiceError::Network { key, url, message } => {
+                write!(f, "unable to reach {} : {}", target(key, url), message)
+            }
+            JiraServiceError::Parse { key, url, message } => write!(
+                f,
+                "unable to parse the response for {} : {}",
+                target(key, url),
+                message
+            ),
+            JiraServiceError::Io { path, message } => write!(f, "{} : {}", path, message),
+            JiraServiceError::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for JiraServiceError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URL: &str = "https://jira.example.com/rest/api/2/issue/WRIG-1";
+
+    fn status(status: u16) -> JiraServiceError {
+        JiraServiceError::from_status(status, Some("WRIG-1"), URL, b"", Some(5))
+    }
+
+    #[test]
+    fn exit_codes() {
+        for (err, code) in [
+            (JiraServiceError::Other("failed".to_string()), 1),
+            (JiraServiceError::config("config.json", "missing"), 3),
+            (status(401), 4),
+            (status(404), 5),
+            (status(429), 6),
+            (status(503), 7),
+            (status(400), 8),
+            (
+                JiraServiceError::network(None, URL, "connection refused"),
+                9,
+            ),
+            (JiraServiceError::parse(None, URL, "expected value"), 10),
+            (JiraServiceError::io("staging", "permission denied"), 11),
+        ] {
+            assert_eq!(err.exit_code(), code, "{}", err);
+            assert_eq!(exit_code(&err), code, "{}", err);
+        }
+    }
+
+    #[test]
+    fn statuses() {
+        for code in [401, 403] {
+            assert!(
+                matches!(status(code), JiraServiceError::Auth { status, .. } if status == code)
+            );
+        }
+        assert!(matches!(status(404), JiraServiceError::NotFound { .. }));
+        assert!(matches!(
+            status(429),
+            JiraServiceError::RateLimited {
+                retry_after: Some(5),
+                ..
+            }
+        ));
+        for code in [500, 502, 503, 599] {
+            assert!(
+                matches!(status(code), JiraServiceError::Server { status, .. } if status == code)
+            );
+        }
+        for code in [400, 405, 409, 422] {
+            assert!(
+                matches!(status(code), JiraServiceError::Request { status, .. } if status == code)
+            );
+        }
+    }
+
+    #[test]
+    fn error_bodies() {
+        let body =
+            br#"{"errorMessages": ["Issue does not exist"], "errors": {"summary": "required"}}"#;
+        let err = JiraServiceError::from_status(400, None, URL, body, None);
+        assert!(
+            err.to_string()
+                .ends_with("Issue does not exist, summary : required")
+        );
+        let err = JiraServiceError::from_status(502, None, URL, "x".repeat(500).as_bytes(), None);
+        assert!(err.to_string().ends_with(&"x".repeat(200)));
+    }
+
+    #[test]
+    fn boxed_errors_keep_their_variant() {
+        let boxed: Box<dyn Error> = Box::new(status(503));
+        assert_eq!(exit_code(boxed.as_ref()), 7);
+        let err = JiraServiceError::from_boxed(boxed);
+        assert!(matches!(err, JiraServiceError::Server { status: 503, .. }));
+
+        let boxed: Box<dyn Error> = Box::from("plain failure");
+        assert_eq!(exit_code(boxed.as_ref()), 1);
+        let err = JiraServiceError::from_boxed(boxed);
+        assert!(matches!(&err, JiraServiceError::Other(message) if message == "plain failure"));
+        assert_eq!(err.exit_code(), 1);
+    }
+}
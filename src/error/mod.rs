@@ -1 +1 @@
-pub mod generic;
+pub mod jira;
@@ -0,0 +1,567 @@
+// module adf
+//
+// converts atlassian document format (descriptions and comment bodies on the v3 api) to the
+// same markdown the wiki markup conversion produces, and converts wiki markup into a document
+// for the v3 write endpoints
+//
+// block nodes are converted to lines (every line carries the prefix of the enclosing quote or
+// list item), inline nodes to a single string with their marks applied
+
+use crate::handlers::markdown::{Block, ListItem, Span, parse_blocks};
+use crate::handlers::markup;
+use chrono::DateTime;
+use serde_json::{Value, json};
+
+/// converts an adf document to markdown
+pub fn to_markdown(doc: &Value) -> String {
+    let mut lines: Vec<String> = vec![];
+    blocks(content(doc), "", &mut lines);
+    // every block ends with an empty line, collapse the runs this leaves behind
+    let mut result: Vec<String> = vec![];
+    for line in lines {
+        if line.is_empty() && result.last().is_some_and(|last| last.is_empty()) {
+            continue;
+        }
+        result.push(line);
+    }
+    result.join("\n").trim_matches('\n').to_string()
+}
+
+/// jira wiki markup as an adf document, the markup is converted to markdown first and its
+/// blocks are mapped to adf nodes (headings, paragraphs, lists, code, quotes, tables, rules)
+pub fn from_wiki(text: &str) -> Value {
+    let blocks = parse_blocks(&markup::to_markdown(text));
+    json!({ "type": "doc", "version": 1, "content": block_nodes(&blocks) })
+}
+
+fn block_nodes(blocks: &[Block]) -> Vec<Value> {
+    blocks.iter().map(block_node).collect()
+}
+
+fn block_node(block: &Block) -> Value {
+    match block {
+        Block::Heading(level, spans) => json!({
+            "type": "heading",
+            "attrs": { "level": level.clamp(&1, &6) },
+            "content": inline_nodes(spans),
+        }),
+        Block::Paragraph(lines) => paragraph(lines),
+        Block::List(items) => list_node(items),
+        Block::Code { language, body } => {
+            let mut node = json!({ "type": "codeBlock", "content": [] });
+            if !language.is_empty() {
+                node["attrs"] = json!({ "language": language });
+            }
+            // empty text nodes are rejected
+            if !body.is_empty() {
+                node["content"] = json!([{ "type": "text", "text": body }]);
+            }
+            node
+        }
+        Block::Quote(inner) => json!({ "type": "blockquote", "content": block_nodes(inner) }),
+        Block::Table { header, rows } => {
+            let mut table_rows = vec![];
+            if let Some(header) = header {
+                table_rows.push(table_row(header, "tableHeader"));
+            }
+            table_rows.extend(rows.iter().map(|row| table_row(row, "tableCell")));
+            json!({ "type": "table", "content": table_rows })
+        }
+        Block::Rule => json!({ "type": "rule" }),
+    }
+}
+
+/// lines of a paragraph are joined with hard breaks
+fn paragraph(lines: &[Vec<Span>]) -> Value {
+    let mut nodes = vec![];
+    for (index, line) in lines.iter().enumerate() {
+        if index > 0 {
+            nodes.push(json!({ "type": "hardBreak" }));
+        }
+        nodes.extend(inline_nodes(line));
+    }
+    json!({ "type": "paragraph", "content": nodes })
+}
+
+fn table_row(cells: &[Vec<Span>], cell_type: &str) -> Value {
+    let cells = cells
+        .iter()
+        .map(
+            |cell| json!({ "type": cell_type, "content": [paragraph(std::slice::from_ref(cell))] }),
+        )
+        .collect::<Vec<Value>>();
+    json!({ "type": "tableRow", "content": cells })
+}
+
+/// items deeper than the first item of the slice nest in the preceding item, adf lists hold
+/// a single kind so the first item decides between bullets and numbers
+fn list_node(items: &[ListItem]) -> Value {
+    let depth = items.first().map(|item| item.depth).unwrap_or(1);
+    let mut nodes: Vec<Value> = vec![];
+    let mut i = 0;
+    while i < items.len() {
+        let mut end = i + 1;
+        while end < items.len() && items[end].depth > depth {
+            end += 1;
+        }
+        let mut content =
+            vec![json!({ "type": "paragraph", "content": inline_nodes(&items[i].spans) })];
+        if end > i + 1 {
+            content.push(list_node(&items[i + 1..end]));
+        }
+        nodes.push(json!({ "type": "listItem", "content": content }));
+        i = end;
+    }
+    let list_type = match items.first().is_some_and(|item| item.ordered) {
+        true => "orderedList",
+        false => "bulletList",
+    };
+    json!({ "type": list_type, "content": nodes })
+}
+
+fn inline_nodes(spans: &[Span]) -> Vec<Value> {
+    let mut nodes = vec![];
+    marked_spans(spans, &[], &mut nodes);
+    nodes
+}
+
+/// text nodes carrying the marks of the enclosing spans
+fn marked_spans(spans: &[Span], marks: &[Value], nodes: &mut Vec<Value>) {
+    let with = |mark: Value| {
+        let mut marks = marks.to_vec();
+        marks.push(mark);
+        marks
+    };
+    for span in spans {
+        match span {
+            Span::Text(text) => nodes.push(text_node(text, marks)),
+            // code only combines with links
+            Span::Code(code) => {
+                let mut marks = marks
+                    .iter()
+                    .filter(|mark| node_type(mark) == "link")
+                    .cloned()
+                    .collect::<Vec<Value>>();
+                marks.push(json!({ "type": "code" }));
+                nodes.push(text_node(code, &marks));
+            }
+            Span::Strong(inner) => marked_spans(inner, &with(json!({ "type": "strong" })), nodes),
+            Span::Emphasis(inner) => marked_spans(inner, &with(json!({ "type": "em" })), nodes),
+            Span::Strike(inner) => marked_spans(inner, &with(json!({ "type": "strike" })), nodes),
+            Span::Link { text, url } => marked_spans(
+                text,
+                &with(json!({ "type": "link", "attrs": { "href": url } })),
+                nodes,
+            ),
+            // media needs an attachment id, images become links
+            Span::Image { alt, url } => {
+                let text = match alt.is_empty() {
+                    true => url,
+                    false => alt,
+                };
+                let link = json!({ "type": "link", "attrs": { "href": url } });
+                nodes.push(text_node(text, &with(link)));
+            }
+        }
+    }
+    nodes.retain(|node| node_type(node) != "text" || node["text"] != "");
+}
+
+fn text_node(text: &str, marks: &[Value]) -> Value {
+    match marks.is_empty() {
+        true => json!({ "type": "text", "text": text }),
+        false => json!({ "type": "text", "text": text, "marks": marks }),
+    }
+}
+
+fn node_type(node: &Value) -> &str {
+    node.get("type").and_then(Value::as_str).unwrap_or_default()
+}
+
+fn content(node: &Value) -> &[Value] {
+    node.get("content")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+
+fn attr<'a>(node: &'a Value, name: &str) -> Option<&'a Value> {
+    node.get("attrs")?.get(name)
+}
+
+fn attr_str<'a>(node: &'a Value, name: &str) -> Option<&'a str> {
+    attr(node, name).and_then(Value::as_str)
+}
+
+fn blocks(nodes: &[Value], prefix: &str, lines: &mut Vec<String>) {
+    for node in nodes {
+        block(node, prefix, lines);
+    }
+}
+
+fn block(node: &Value, prefix: &str, lines: &mut Vec<String>) {
+    match node_type(node) {
+        "paragraph" => {
+            let text = inline(content(node));
+            if !text.trim().is_empty() {
+                push_text(&text, prefix, lines);
+            }
+        }
+        "heading" => {
+            let level = attr(node, "level")
+                .and_then(Value::as_u64)
+                .unwrap_or(1)
+                .clamp(1, 6) as usize;
+            lines.push(format!(
+                "{}{} {}",
+                prefix,
+                "#".repeat(level),
+                inline(content(node)).trim()
+            ));
+        }
+        "bulletList" | "orderedList" | "taskList" | "decisionList" => list(node, prefix, lines),
+        "codeBlock" => {
+            let language = attr_str(node, "language").unwrap_or_default();
+            let code = content(node)
+                .iter()
+                .filter_map(|text| text.get("text").and_then(Value::as_str))
+                .collect::<String>();
+            lines.push(format!("{}```{}", prefix, language));
+            for line in code.trim_matches('\n').split('\n') {
+                lines.push(format!("{}{}", prefix, line).trim_end().to_string());
+            }
+            lines.push(format!("{}```", prefix));
+        }
+        // panels have no markdown equivalent, they are quoted like wiki panels
+        "blockquote" | "panel" => {
+            let quoted = format!("{}> ", prefix);
+            blocks(content(node), &quoted, lines);
+            // the last quoted block leaves an empty quote line behind
+            if lines.last().is_some_and(|line| line == quoted.trim_end()) {
+                lines.pop();
+            }
+        }
+        "expand" | "nestedExpand" => {
+            if let Some(title) = attr_str(node, "title").filter(|title| !title.is_empty()) {
+                lines.push(format!("{}**{}**", prefix, title));
+                lines.push(prefix.trim_end().to_string());
+            }
+            blocks(content(node), prefix, lines);
+        }
+        "rule" => lines.push(format!("{}---", prefix)),
+        "table" => table(node, prefix, lines),
+        // media refers to attachments by id, there is no url to link to
+        "mediaSingle" | "mediaGroup" | "media" => return,
+        _ => match node.get("content") {
+            Some(_) => blocks(content(node), prefix, lines),
+            None => {
+                let text = inline(std::slice::from_ref(node));
+                if !text.trim().is_empty() {
+                    push_text(&text, prefix, lines);
+                }
+            }
+        },
+    }
+    lines.push(prefix.trim_end().to_string());
+}
+
+/// inline text, hard breaks continue on the next line with the same prefix
+fn push_text(text: &str, prefix: &str, lines: &mut Vec<String>) {
+    lines.push(format!(
+        "{}{}",
+        prefix,
+        text.replace('\n', &format!("\n{}", prefix))
+    ));
+}
+
+/// list items are not separated by empty lines, nested lists line up with the text of their
+/// parent ("- " or "1. ") like the wiki markup conversion
+fn list(node: &Value, prefix: &str, lines: &mut Vec<String>) {
+    let bullet = match node_type(node) {
+        "orderedList" => "1.",
+        _ => "-",
+    };
+    let nested = format!("{}{}", prefix, " ".repeat(bullet.len() + 1));
+    for item in content(node) {
+        match node_type(item) {
+            // task and decision items hold inline nodes directly
+            "taskItem" | "decisionItem" => {
+                let check = match (node_type(item), attr_str(item, "state")) {
+                    ("taskItem", Some("DONE")) => "[x] ",
+                    ("taskItem", _) => "[ ] ",
+                    _ => "",
+                };
+                let text = inline(content(item));
+                push_text(
+                    &format!("{} {}{}", bullet, check, text.trim()),
+                    prefix,
+                    lines,
+                );
+            }
+            "taskList" => list(item, &nested, lines),
+            _ => {
+                for (index, child) in content(item).iter().enumerate() {
+                    match node_type(child) {
+                        "paragraph" if index == 0 => {
+                            let text = inline(content(child));
+                            push_text(&format!("{} {}", bullet, text.trim()), prefix, lines);
+                        }
+                        "bulletList" | "orderedList" | "taskList" => list(child, &nested, lines),
+                        _ => {
+                            if index == 0 {
+                                lines.push(format!("{}{}", prefix, bullet));
+                            }
+                            block(child, &nested, lines);
+                            // keep the list tight
+                            if lines.last().is_some_and(|line| line.trim().is_empty()) {
+                                lines.pop();
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// the separator follows the first row made of header cells, like || rows in wiki markup
+fn table(node: &Value, prefix: &str, lines: &mut Vec<String>) {
+    let mut has_header = false;
+    for row in content(node) {
+        let cells = content(row);
+        let header = !cells.is_empty() && cells.iter().all(|cell| node_type(cell) == "tableHeader");
+        let text = cells
+            .iter()
+            .map(|cell| {
+                content(cell)
+                    .iter()
+                    .map(|block| inline(content(block)).replace('\n', " "))
+                    .filter(|text| !text.trim().is_empty())
+                    .collect::<Vec<String>>()
+                    .join(" ")
+                    .replace('|', "\\|")
+            })
+            .collect::<Vec<String>>();
+        lines.push(format!("{}| {} |", prefix, text.join(" | ")));
+        if header && !has_header {
+            lines.push(format!(
+                "{}|{}",
+                prefix,
+                " --- |".repeat(cells.len().max(1))
+            ));
+            has_header = true;
+        }
+    }
+}
+
+fn inline(nodes: &[Value]) -> String {
+    let mut out = String::new();
+    for node in nodes {
+        match node_type(node) {
+            "text" => out.push_str(&marked(node)),
+            "hardBreak" => out.push_str("  \n"),
+            "mention" => {
+                let name = attr_str(node, "text")
+                    .or(attr_str(node, "id"))
+                    .unwrap_or_default();
+                match name.starts_with('@') {
+                    true => out.push_str(name),
+                    false => out.push_str(&format!("@{}", name)),
+                }
+            }
+            "emoji" => out.push_str(
+                attr_str(node, "text")
+                    .or(attr_str(node, "shortName"))
+                    .unwrap_or_default(),
+            ),
+            "inlineCard" | "blockCard" => {
+                if let Some(url) = attr_str(node, "url") {
+                    out.push_str(&format!("<{}>", url));
+                }
+            }
+            "status" => out.push_str(attr_str(node, "text").unwrap_or_default()),
+            "date" => {
+                // milliseconds since the epoch, as a string
+                let date = attr(node, "timestamp")
+                    .and_then(|value| match value {
+                        Value::String(value) => value.parse::<i64>().ok(),
+                        value => value.as_i64(),
+                    })
+                    .and_then(DateTime::from_timestamp_millis)
+                    .map(|dt| dt.format("%Y-%m-%d").to_string());
+                out.push_str(&date.unwrap_or_default());
+            }
+            _ => out.push_str(&inline(content(node))),
+        }
+    }
+    out
+}
+
+/// applies the marks of a text node, code is exclusive in adf so it is copied verbatim
+fn marked(node: &Value) -> String {
+    let text = node.get("text").and_then(Value::as_str).unwrap_or_default();
+    let marks = node
+        .get("marks")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let has = |name: &str| marks.iter().any(|mark| node_type(mark) == name);
+    let mut out = match has("code") {
+        true => format!("`{}`", text),
+        false => {
+            // markers must touch the text, surrounding spaces stay outside
+            let trimmed = text.trim();
+            if trimmed.is_empty() {
+                return text.to_string();
+            }
+            let mut inner = trimmed.to_string();
+            for (mark, marker) in [("em", "*"), ("strong", "**"), ("strike", "~~")] {
+                if has(mark) {
+                    inner = format!("{}{}{}", marker, inner, marker);
+                }
+            }
+            let start = text.len() - text.trim_start().len();
+            let end = text.trim_end().len();
+            format!("{}{}{}", &text[..start], inner, &text[end..])
+        }
+    };
+    if let Some(href) = marks
+        .iter()
+        .find(|mark| node_type(mark) == "link")
+        .and_then(|mark| attr_str(mark, "href"))
+    {
+        out = format!("[{}]({})", out.trim(), href);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::service::JiraResponse;
+
+    /// a jira cloud issue as returned by the v3 api
+    fn v3_issue() -> JiraResponse {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/docs/fixtures/v3/WRIG-8.json");
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn v3_description_to_markdown() {
+        let description = v3_issue().fields.description.unwrap();
+        assert_eq!(
+            description.to_markdown(),
+            [
+                "## Rollout plan",
+                "",
+                "Owner @Alex Smith 🚀 ships `cosign` with **keyless** signing, see [the docs](https://docs.sigstore.dev).",
+                "",
+                "- Sign images",
+                "  - amd64",
+                "  - *arm64*",
+                "- Publish attestations",
+                "",
+                "1. Enable the pipeline step",
+                "1. Verify in staging",
+                "",
+                "```shell",
+                "cosign sign --yes $IMAGE",
+                "cosign verify $IMAGE",
+                "```",
+                "",
+                "| **Arch** | **Status** |",
+                "| --- | --- |",
+                "| amd64 | done \\| verified |",
+                "",
+                // unknown containers keep their content, unknown leaves are dropped
+                "Left column",
+                "",
+                "Tracked in <https://example.atlassian.net/browse/WRIG-1> IN REVIEW",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn v3_comments_to_markdown() {
+        let comments = v3_issue()
+            .fields
+            .comment
+            .comments
+            .iter()
+            .map(|comment| comment.body.to_markdown())
+            .collect::<Vec<String>>();
+        assert_eq!(
+            comments,
+            [
+                "Thanks @Jamie Doe  \nVerified with `cosign verify`.",
+                "> ~~old key~~ rotated on 2026-10-10",
+            ]
+        );
+    }
+
+    #[test]
+    fn wiki_comment_to_adf() {
+        let doc = from_wiki(
+            "h3. Biweekly (2024-05-01)\n*Status* : In Progress\n\n||Story||Status||\n|[WRIG-2]|Done|",
+        );
+        let content = content(&doc);
+        assert_eq!(
+            content[0],
+            json!({
+                "type": "heading",
+                "attrs": { "level": 3 },
+                "content": [{ "type": "text", "text": "Biweekly (2024-05-01)" }],
+            })
+        );
+        assert_eq!(
+            content[1],
+            json!({
+                "type": "paragraph",
+                "content": [
+                    { "type": "text", "text": "Status", "marks": [{ "type": "strong" }] },
+                    { "type": "text", "text": " : In Progress" },
+                ],
+            })
+        );
+        assert_eq!(node_type(&content[2]), "table");
+        let rows = super::content(&content[2]);
+        assert_eq!(node_type(&super::content(&rows[0])[0]), "tableHeader");
+        assert_eq!(node_type(&super::content(&rows[1])[0]), "tableCell");
+        assert_eq!(
+            to_markdown(&doc),
+            "### Biweekly (2024-05-01)\n\n**Status** : In Progress\n\n| Story | Status |\n| --- | --- |\n| WRIG-2 | Done |"
+        );
+    }
+
+    #[test]
+    fn wiki_markup_round_trips() {
+        for wiki in [
+            "* one\n** nested _emphasis_\n* two",
+            "# first\n# second",
+            "{code:rust}\nfn main() {}\n{code}",
+            "bq. quoted -struck- text",
+            "see [the docs|https://example.com/docs] and {{value}}",
+            "----",
+        ] {
+            assert_eq!(
+                to_markdown(&from_wiki(wiki)),
+                markup::to_markdown(wiki),
+                "{}",
+                wiki
+            );
+        }
+    }
+
+    #[test]
+    fn wiki_lines_are_hard_breaks() {
+        assert_eq!(
+            from_wiki("line one\nline two")["content"][0]["content"],
+            json!([
+                { "type": "text", "text": "line one" },
+                { "type": "hardBreak" },
+                { "type": "text", "text": "line two" },
+            ])
+        );
+    }
+}
@@ -0,0 +1,343 @@
+// module auth
+//
+// credentials sent with every jira request, resolved once from the auth settings
+//
+// bearer   token file (auth.token_path, else api_key_path)
+// basic    auth.user and the token file
+// netrc    login and password of the base_url host in auth.netrc_path (~/.netrc)
+// env      token from auth.token_env (JIRA_API_TOKEN), basic when auth.user is set
+//
+// secrets never reach the logs, Debug is redacted and reqwest marks the authorization header
+// as sensitive
+
+use crate::config::load::{AuthMethod, Parameters};
+use crate::error::jira::JiraServiceError;
+use std::fmt;
+use std::fs;
+
+/// variable read by the env method when token_env is not set
+const DEFAULT_TOKEN_ENV: &str = "JIRA_API_TOKEN";
+
+#[derive(Clone)]
+pub enum Credentials {
+    Bearer(String),
+    Basic { user: String, token: String },
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Credentials::Bearer(_) => write!(f, "Bearer(<redacted>)"),
+            Credentials::Basic { user, .. } => write!(f, "Basic({}, <redacted>)", user),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct NetrcEntry {
+    /// None for the default entry
+    machine: Option<String>,
+    login: Option<String>,
+    password: Option<String>,
+}
+
+impl Credentials {
+    pub fn resolve(params: &Parameters) -> Result<Self, JiraServiceError> {
+        let auth = &params.auth;
+        match auth.method {
+            AuthMethod::Bearer => Ok(Credentials::Bearer(read_token(params)?)),
+            AuthMethod::Basic => Ok(Credentials::Basic {
+                user: auth
+                    .user
+                    .clone()
+                    .ok_or_else(|| config("auth.user", "basic auth needs a user"))?,
+                token: read_token(params)?,
+            }),
+            AuthMethod::Netrc => read_netrc(params),
+            AuthMethod::Env => {
+                let name = auth
+                    .token_env
+                    .clone()
+                    .unwrap_or(DEFAULT_TOKEN_ENV.to_string());
+                let token = std::env::var(&name)
+                    .ok()
+                    .map(|token| token.trim().to_string())
+                    .filter(|token| !token.is_empty())
+                    .ok_or_else(|| config("auth.token_env", &format!("{} is not set", name)))?;
+                match &auth.user {
+                    Some(user) => Ok(Credentials::Basic {
+                        user: user.clone(),
+                        token,
+                    }),
+                    None => Ok(Credentials::Bearer(token)),
+                }
+            }
+        }
+    }
+
+    pub fn apply(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self {
+            Credentials::Bearer(token) => request.bearer_auth(token),
+            Credentials::Basic { user, token } => request.basic_auth(user, Some(token)),
+        }
+    }
+
+    /// scheme and user, safe to log
+    pub fn describe(&self) -> String {
+        match self {
+            Credentials::Bearer(_) => "bearer".to_string(),
+            Credentials::Basic { user, .. } => format!("basic ({})", user),
+        }
+    }
+}
+
+fn config(path: &str, message: &str) -> JiraServiceError {
+    JiraServiceError::Config {
+        path: path.to_string(),
+        message: message.to_string(),
+    }
+}
+
+fn read_token(params: &Parameters) -> Result<String, JiraServiceError> {
+    let path = params
+        .auth
+        .token_path
+        .clone()
+        .unwrap_or(params.api_key_path.clone());
+    if path.is_empty() {
+        return Err(config(
+            "api_key_path",
+            "no token file (set auth.token_path or api_key_path)",
+        ));
+    }
+    let token = fs::read_to_string(&path).map_err(|e| JiraServiceError::config(&path, e))?;
+    match token.trim() {
+        "" => Err(config(&path, "the token file is empty")),
+        token => Ok(token.to_string()),
+    }
+}
+
+fn read_netrc(params: &Parameters) -> Result<Credentials, JiraServiceError> {
+    let path = params.auth.netrc_path.clone().unwrap_or(format!(
+        "{}/.netrc",
+        std::env::var("HOME").unwrap_or_default()
+    ));
+    let data = fs::read_to_string(&path).map_err(|e| JiraServiceError::config(&path, e))?;
+    let host = reqwest::Url::parse(&params.base_url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .ok_or_else(|| config("base_url", "no host to look up in the netrc file"))?;
+    let entries = parse_netrc(&data);
+    // the first entry for the host wins, the default entry is the fallback
+    let entry = entries
+        .iter()
+        .find(|entry| entry.machine.as_deref() == Some(host.as_str()))
+        .or(entries.iter().find(|entry| entry.machine.is_none()));
+    match entry {
+        Some(NetrcEntry {
+            login: Some(login),
+            password: Some(password),
+            ..
+        }) => Ok(Credentials::Basic {
+            user: login.clone(),
+            token: password.clone(),
+        }),
+        _ => Err(config(
+            &path,
+            &format!("no login and password for {} (or default)", host),
+        )),
+    }
+}
+
+/// machine, default, login, password and account tokens, macdef bodies run to the next empty
+/// line and are skipped
+fn parse_netrc(data: &str) -> Vec<NetrcEntry> {
+    let mut entries: Vec<NetrcEntry> = vec![];
+    let mut in_macro = false;
+    for line in data.lines() {
+        if in_macro {
+            in_macro = !line.trim().is_empty();
+            continue;
+        }
+        let mut tokens = line.split_whitespace();
+        while let Some(token) = tokens.next() {
+            match token {
+                "machine" => entries.push(NetrcEntry {
+                    machine: tokens.next().map(str::to_string),
+                    ..Default::default()
+                }),
+                "default" => entries.push(NetrcEntry::default()),
+                "login" | "password" => {
+                    let value = tokens.next().map(str::to_string);
+                    if let Some(entry) = entries.last_mut() {
+                        match token {
+                            "login" => entry.login = value,
+                            _ => entry.password = value,
+                        }
+                    }
+                }
+                "account" => {
+                    tokens.next();
+                }
+                "macdef" => {
+                    in_macro = true;
+                    break;
+                }
+                _ if token.starts_with('#') => break,
+                _ => {}
+            }
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+
+    const TOKEN: &str = "s3cr3t-t0ken";
+
+    fn params(auth: Value) -> Parameters {
+        serde_json::from_value(json!({
+            "base_url": "https://jira.example.com/rest/api/2/issue/",
+            "auth": auth,
+        }))
+        .unwrap()
+    }
+
+    /// scratch file holding the given text
+    fn scratch(name: &str, data: &str) -> String {
+        let path =
+            std::env::temp_dir().join(format!("jira-service-{}-{}", name, std::process::id()));
+        fs::write(&path, data).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    fn is_config(err: &JiraServiceError) -> bool {
+        matches!(err, JiraServiceError::Config { .. })
+    }
+
+    #[test]
+    fn netrc_entries() {
+        let data = "# jira hosts\n\
+                    machine other.example.com login other password nope\n\
+                    macdef init\n\
+                    machine jira.example.com login inside password macro\n\
+                    \n\
+                    machine jira.example.com\n\
+                    \tlogin alice\n\
+                    \tpassword wonderland # trailing comment\n\
+                    default login anonymous password guest\n";
+        let entries = parse_netrc(data);
+        let machines = entries
+            .iter()
+            .map(|entry| {
+                (
+                    entry.machine.as_deref(),
+                    entry.login.as_deref(),
+                    entry.password.as_deref(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            machines,
+            [
+                (Some("other.example.com"), Some("other"), Some("nope")),
+                (Some("jira.example.com"), Some("alice"), Some("wonderland")),
+                (None, Some("anonymous"), Some("guest")),
+            ]
+        );
+    }
+
+    #[test]
+    fn netrc_host_and_default() {
+        let path = scratch(
+            "netrc-host",
+            "machine jira.example.com login alice password wonderland\n\
+             default login anonymous password guest\n",
+        );
+        let creds = Credentials::resolve(&params(json!({ "method": "netrc", "netrc_path": path })));
+        assert_eq!(creds.unwrap().describe(), "basic (alice)");
+
+        let mut other = params(json!({ "method": "netrc", "netrc_path": path }));
+        other.base_url = "https://other.example.com/rest/api/2/issue/".to_string();
+        assert_eq!(
+            Credentials::resolve(&other).unwrap().describe(),
+            "basic (anonymous)"
+        );
+        let _ = fs::remove_file(&path);
+
+        let path = scratch(
+            "netrc-no-match",
+            "machine other.example.com login other password nope\n",
+        );
+        let err = Credentials::resolve(&params(json!({ "method": "netrc", "netrc_path": path })))
+            .unwrap_err();
+        let _ = fs::remove_file(&path);
+        assert!(is_config(&err));
+        assert!(err.to_string().contains("jira.example.com"));
+    }
+
+    #[test]
+    fn basic_needs_a_user() {
+        let path = scratch("basic-no-user", TOKEN);
+        let err = Credentials::resolve(&params(json!({ "method": "basic", "token_path": path })))
+            .unwrap_err();
+        let creds = Credentials::resolve(&params(
+            json!({ "method": "basic", "user": "alice", "token_path": path }),
+        ));
+        let _ = fs::remove_file(&path);
+        assert!(is_config(&err));
+        assert!(err.to_string().contains("auth.user"));
+        assert_eq!(creds.unwrap().describe(), "basic (alice)");
+    }
+
+    #[test]
+    fn env_tokens() {
+        let name = format!("JIRA_SERVICE_TEST_TOKEN_{}", std::process::id());
+        let resolve = |user: Option<&str>| {
+            Credentials::resolve(&params(
+                json!({ "method": "env", "token_env": name, "user": user }),
+            ))
+        };
+        // the variable name is unique to this test, no other test reads it
+        unsafe { std::env::remove_var(&name) };
+        assert!(is_config(&resolve(None).unwrap_err()));
+        unsafe { std::env::set_var(&name, "  \n") };
+        assert!(is_config(&resolve(None).unwrap_err()));
+        unsafe { std::env::set_var(&name, format!(" {}\n", TOKEN)) };
+        assert_eq!(resolve(None).unwrap().describe(), "bearer");
+        assert_eq!(resolve(Some("alice")).unwrap().describe(), "basic (alice)");
+        unsafe { std::env::remove_var(&name) };
+    }
+
+    #[test]
+    fn empty_token_files() {
+        for (name, data) in [("token-empty", ""), ("token-blank", " \n\t\n")] {
+            let path = scratch(name, data);
+            let err = Credentials::resolve(&params(json!({ "token_path": path }))).unwrap_err();
+            let _ = fs::remove_file(&path);
+            assert!(is_config(&err));
+            assert!(err.to_string().contains("empty"));
+        }
+        let err = Credentials::resolve(&params(json!({}))).unwrap_err();
+        assert!(is_config(&err));
+    }
+
+    #[test]
+    fn debug_is_redacted() {
+        let path = scratch("token-debug", &format!("{}\n", TOKEN));
+        let bearer = Credentials::resolve(&params(json!({ "token_path": path }))).unwrap();
+        let basic = Credentials::resolve(&params(
+            json!({ "method": "basic", "user": "alice", "token_path": path }),
+        ))
+        .unwrap();
+        let _ = fs::remove_file(&path);
+        for creds in [bearer, basic] {
+            let debug = format!("{:?} {:#?} {}", creds, creds, creds.describe());
+            assert!(!debug.contains(TOKEN), "{}", debug);
+            assert!(debug.contains("<redacted>"));
+        }
+    }
+}
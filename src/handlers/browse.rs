@@ -0,0 +1,242 @@
+// module browse
+//
+// links to the jira web ui, the root is browse_url when set, otherwise it is derived from
+// base_url (https://jira.example.com/context/rest/api/2/issue/ -> .../context/browse/)
+//
+// issue keys in description and comment text are turned into markdown links, only keys of the
+// projects seen in the report are linked so UTF-8 or SHA-256 stay plain text. code, existing
+// links and urls are left as they are
+
+use std::collections::HashSet;
+
+#[derive(Clone, Debug, Default)]
+pub struct BrowseLinks {
+    /// ends with /
+    root: String,
+    projects: HashSet<String>,
+}
+
+impl BrowseLinks {
+    pub fn new(base_url: &str, browse_url: Option<&str>) -> Self {
+        let root = match browse_url.filter(|url| !url.trim().is_empty()) {
+            Some(url) => format!("{}/", url.trim().trim_end_matches('/')),
+            None => browse_root(base_url),
+        };
+        BrowseLinks {
+            root,
+            projects: HashSet::new(),
+        }
+    }
+
+    /// projects whose keys are linked in text (WRIG for WRIG-1)
+    pub fn with_projects<'a>(mut self, keys: impl IntoIterator<Item = &'a str>) -> Self {
+        self.projects.extend(
+            keys.into_iter()
+                .filter_map(|key| key.rsplit_once('-'))
+                .map(|(project, _)| project.to_string()),
+        );
+        self
+    }
+
+    pub fn url(&self, key: &str) -> String {
+        format!("{}{}", self.root, key)
+    }
+
+    /// links the issue keys of markdown text, fenced code blocks are skipped
+    pub fn linkify(&self, text: &str) -> String {
+        if self.projects.is_empty() {
+            return text.to_string();
+        }
+        let mut out = String::with_capacity(text.len());
+        let mut in_fence = false;
+        for (index, line) in text.split('\n').enumerate() {
+            if index > 0 {
+                out.push('\n');
+            }
+            let content = line.trim_start_matches(|c: char| c == '>' || c.is_whitespace());
+            if content.starts_with("```") {
+                in_fence = !in_fence;
+                out.push_str(line);
+                continue;
+            }
+            match in_fence {
+                true => out.push_str(line),
+                false => self.linkify_line(line, &mut out),
+            }
+        }
+        out
+    }
+
+    fn linkify_line(&self, line: &str, out: &mut String) {
+        let mut rest = line;
+        while let Some(c) = rest.chars().next() {
+            // code spans, links, autolinks and bare urls are copied verbatim
+            let verbatim = match c {
+                '`' => rest[1..].find('`').map(|end| end + 2),
+                '[' => link_end(rest),
+                '<' if rest[1..].starts_with("http") => rest.find('>').map(|end| end + 1),
+                'h' if rest.starts_with("http://") || rest.starts_with("https://") => {
+                    Some(rest.find(char::is_whitespace).unwrap_or(rest.len()))
+                }
+                _ => None,
+            };
+            if let Some(end) = verbatim {
+                out.push_str(&rest[..end]);
+                rest = &rest[end..];
+                continue;
+            }
+            let after_word = out
+                .chars()
+                .last()
+                .is_some_and(|last| last.is_alphanumeric() || "_-/.".contains(last));
+            if !after_word && let Some(end) = self.key_end(rest) {
+                out.push_str(&format!("[{}]({})", &rest[..end], self.url(&rest[..end])));
+                rest = &rest[end..];
+                continue;
+            }
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+
+    /// length of the issue key at the start of text, when its project is known
+    fn key_end(&self, text: &str) -> Option<usize> {
+        let (project, number) = text.split_once('-')?;
+        if !project.starts_with(|c: char| c.is_ascii_uppercase())
+            || !project
+                .chars()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+            || !self.projects.contains(project)
+        {
+            return None;
+        }
+        let digits = number
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(number.len());
+        // WRIG-12abc is not a key
+        if digits == 0
+            || number[digits..]
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_alphanumeric() || c == '_')
+        {
+            return None;
+        }
+        Some(project.len() + 1 + digits)
+    }
+}
+
+/// [text](url) at the start of text, returns the length of the whole link
+fn link_end(text: &str) -> Option<usize> {
+    let close = text.find(']')?;
+    match text[close + 1..].starts_with('(') {
+        true => text[close..].find(')').map(|end| close + end + 1),
+        false => None,
+    }
+}
+
+/// web root of the rest api url, the context path is kept and the api path dropped
+fn browse_root(base_url: &str) -> String {
+    let root = match base_url.split_once("/rest/") {
+        Some((root, _)) => root.to_string(),
+        None => match reqwest::Url::parse(base_url) {
+            Ok(url) => url.origin().ascii_serialization(),
+            Err(_) => base_url.trim_end_matches('/').to_string(),
+        },
+    };
+    format!("{}/browse/", root.trim_end_matches('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE_URL: &str = "https://jira.example.com/rest/api/2/issue/";
+
+    fn links() -> BrowseLinks {
+        BrowseLinks::new(BASE_URL, None).with_projects(["WRIG-1", "OPS-7"])
+    }
+
+    #[test]
+    fn urls_from_base_url() {
+        let url = |base_url: &str, browse_url: Option<&str>| {
+            BrowseLinks::new(base_url, browse_url).url("WRIG-1")
+        };
+        assert_eq!(
+            url(BASE_URL, None),
+            "https://jira.example.com/browse/WRIG-1"
+        );
+        assert_eq!(
+            url("https://example.com/jira/rest/api/latest/issue/", None),
+            "https://example.com/jira/browse/WRIG-1"
+        );
+        assert_eq!(
+            url("https://jira.example.com/some/path", None),
+            "https://jira.example.com/browse/WRIG-1"
+        );
+        assert_eq!(
+            url(BASE_URL, Some("https://issues.example.com/browse")),
+            "https://issues.example.com/browse/WRIG-1"
+        );
+        assert_eq!(
+            url(BASE_URL, Some("  ")),
+            "https://jira.example.com/browse/WRIG-1"
+        );
+    }
+
+    #[test]
+    fn keys_are_linked() {
+        assert_eq!(
+            links().linkify("see WRIG-12 and OPS-7."),
+            "see [WRIG-12](https://jira.example.com/browse/WRIG-12) and \
+             [OPS-7](https://jira.example.com/browse/OPS-7)."
+        );
+        assert_eq!(
+            links().linkify("> (WRIG-3)"),
+            "> ([WRIG-3](https://jira.example.com/browse/WRIG-3))"
+        );
+        // nothing is linked without known projects
+        assert_eq!(
+            BrowseLinks::new(BASE_URL, None).linkify("WRIG-12"),
+            "WRIG-12"
+        );
+    }
+
+    #[test]
+    fn links_and_urls_are_kept() {
+        for text in [
+            "[WRIG-1](https://jira.example.com/browse/WRIG-1)",
+            "[the story](https://jira.example.com/browse/WRIG-1)",
+            "<https://jira.example.com/browse/WRIG-1>",
+            "https://jira.example.com/browse/WRIG-1 is done",
+        ] {
+            assert_eq!(links().linkify(text), text);
+        }
+    }
+
+    #[test]
+    fn other_words_are_kept() {
+        for text in [
+            "XWRIG-12",
+            "wrig-12",
+            "WRIG-12abc",
+            "WRIG-12_a",
+            "WRIG-",
+            "abcWRIG-1",
+            "path/WRIG-1",
+            "UTF-8 and SHA-256",
+        ] {
+            assert_eq!(links().linkify(text), text);
+        }
+    }
+
+    #[test]
+    fn code_is_kept() {
+        let text = "run `git log WRIG-1`\n```\nWRIG-2\n```\nWRIG-3";
+        assert_eq!(
+            links().linkify(text),
+            "run `git log WRIG-1`\n```\nWRIG-2\n```\n\
+             [WRIG-3](https://jira.example.com/browse/WRIG-3)"
+        );
+    }
+}
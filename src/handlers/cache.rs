@@ -0,0 +1,466 @@
+use crate::config::load::Parameters;
+use crate::error::jira::JiraServiceError;
+use crate::handlers::client::JiraClient;
+use crate::handlers::common::{get_error, get_opts};
+use crate::handlers::service::{
+    BulkCreateResponse, Comment, JiraResponse, SearchResponse, Transition,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use custom_logger as log;
+use hyper::body::Bytes;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::sync::Arc;
+use surrealkv::Tree;
+
+/// seconds a cached issue is served without revalidation when cache_ttl_secs is not set
+const DEFAULT_CACHE_TTL_SECS: u64 = 3600;
+
+/// version of the stored issue json, bumped when the request or the model changes what an
+/// entry holds (1 added changelogs, 2 stores the json as jira returned it), entries of another
+/// version are a miss
+const CACHE_SCHEMA: u32 = 2;
+
+/// cache entry holding the raw issue json, its fields.updated timestamp and the fetch time
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub key: String,
+    pub updated: String,
+    pub fetched_at: i64,
+    pub data: String,
+    /// CACHE_SCHEMA when the entry was stored, 0 for entries stored before versioning
+    #[serde(default)]
+    pub schema: u32,
+}
+
+/// issue cache stored in a surrealkv tree ({working_dir}/cache/issues.kv)
+pub struct IssueCache {
+    tree: Tree,
+    ttl: i64,
+    refresh: bool,
+}
+
+impl IssueCache {
+    pub fn open(db: String, ttl: u64, refresh: bool) -> Result<Self, Box<dyn std::error::Error>> {
+        let tree = get_opts(db)?;
+        Ok(IssueCache {
+            tree,
+            ttl: ttl as i64,
+            refresh,
+        })
+    }
+
+    /// the issue cache of the config ({working_dir}/cache/issues), test mode keeps the
+    /// fixtures in their own tree so they are never served to a real run
+    pub fn open_for(params: &Parameters, refresh: bool) -> Result<Self, JiraServiceError> {
+        let cache_dir = format!("{}/cache", params.working_dir);
+        fs::create_dir_all(&cache_dir).map_err(|e| JiraServiceError::io(&cache_dir, e))?;
+        let tree = match params.test {
+            true => "fixtures",
+            false => "issues",
+        };
+        IssueCache::open(
+            format!("{}/{}", cache_dir, tree),
+            params.cache_ttl_secs.unwrap_or(DEFAULT_CACHE_TTL_SECS),
+            refresh,
+        )
+        .map_err(|e| JiraServiceError::io(&cache_dir, e))
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Option<CacheEntry>, Box<dyn std::error::Error>> {
+        let mut txn = self.tree.begin().map_err(|e| get_error(e.to_string()))?;
+        let b_key = Bytes::from(key.to_string());
+        let result = txn.get(&b_key).map_err(|e| get_error(e.to_string()))?;
+        txn.commit().await?;
+        match result {
+            Some(value) => {
+                let entry: CacheEntry =
+                    serde_json::from_slice(&value).map_err(|e| get_error(e.to_string()))?;
+                Ok(Some(entry))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub async fn put(&self, entry: &CacheEntry) -> Result<(), Box<dyn std::error::Error>> {
+        let mut txn = self.tree.begin().map_err(|e| get_error(e.to_string()))?;
+        txn.set_durability(surrealkv::Durability::Immediate);
+        let key = Bytes::from(entry.key.clone());
+        let value = Bytes::from(serde_json::to_string(entry)?);
+        txn.set(&key, &value)
+            .map_err(|e| get_error(e.to_string()))?;
+        txn.commit().await?;
+        Ok(())
+    }
+
+    pub async fn remove(&self, key: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut txn = self.tree.begin().map_err(|e| get_error(e.to_string()))?;
+        txn.set_durability(surrealkv::Durability::Immediate);
+        let b_key = Bytes::from(key.to_string());
+        txn.delete(&b_key).map_err(|e| get_error(e.to_string()))?;
+        txn.commit().await?;
+        Ok(())
+    }
+
+    /// every entry in key order
+    pub async fn list(&self) -> Result<Vec<CacheEntry>, Box<dyn std::error::Error>> {
+        let mut txn = self.tree.begin().map_err(|e| get_error(e.to_string()))?;
+        // keys are utf-8 so they all sort below 0xff
+        let range = txn
+            .range(b"".as_slice(), [0xffu8].as_slice())
+            .map_err(|e| get_error(e.to_string()))?;
+        let mut entries = vec![];
+        for item in range {
+            let (_, value) = item.map_err(|e| get_error(e.to_string()))?;
+            let entry: CacheEntry =
+                serde_json::from_slice(&value).map_err(|e| get_error(e.to_string()))?;
+            entries.push(entry);
+        }
+        txn.commit().await?;
+        Ok(entries)
+    }
+
+    pub async fn close(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.tree.close().await?;
+        Ok(())
+    }
+
+    fn is_fresh(&self, entry: &CacheEntry) -> bool {
+        Utc::now().timestamp() - entry.fetched_at < self.ttl
+    }
+
+    /// data is the issue json as jira returned it
+    async fn store(&self, jira: &JiraResponse, data: &str) {
+        let entry = CacheEntry {
+            key: jira.key.clone(),
+            updated: jira.fields.updated.clone(),
+            fetched_at: Utc::now().timestamp(),
+            data: data.to_string(),
+            schema: CACHE_SCHEMA,
+        };
+        if let Err(err) = self.put(&entry).await {
+            log::warn!("[cache] unable to store {} : {}", jira.key, err);
+        }
+    }
+
+    async fn lookup(&self, key: &str) -> Option<CacheEntry> {
+        if self.refresh {
+            return None;
+        }
+        match self.get(key).await {
+            Ok(entry) => entry,
+            Err(err) => {
+                log::warn!("[cache] unable to read {} : {}", key, err);
+                None
+            }
+        }
+    }
+}
+
+/// JiraClient decorator serving unchanged issues from the cache
+///
+/// entries younger than the ttl are served as is, older entries are revalidated
+/// against fields.updated (a single field request) before the full issue is fetched
+pub struct CachingJiraClient {
+    inner: Arc<dyn JiraClient>,
+    cache: Arc<IssueCache>,
+}
+
+impl CachingJiraClient {
+    pub fn new(inner: Arc<dyn JiraClient>, cache: Arc<IssueCache>) -> Self {
+        CachingJiraClient { inner, cache }
+    }
+
+    /// the cached issue when the entry is fresh or jira still reports its fields.updated, an
+    /// entry that no longer parses is logged and treated as a miss so it is refetched and
+    /// overwritten
+    async fn revalidate(
+        &self,
+        mut entry: CacheEntry,
+    ) -> Result<Option<JiraResponse>, Box<dyn std::error::Error>> {
+        let fresh = self.cache.is_fresh(&entry);
+        if !fresh && self.inner.get_updated(&entry.key).await? != entry.updated {
+            return Ok(None);
+        }
+        let jira: JiraResponse = match serde_json::from_str(&entry.data) {
+            Ok(jira) => jira,
+            Err(err) => {
+                log::warn!("[cache] unreadable entry {} refetched : {}", entry.key, err);
+                return Ok(None);
+            }
+        };
+        if fresh {
+            log::debug!("[cache] hit {}", entry.key);
+        } else {
+            log::debug!(
+                "[cache] revalidated {} (updated {})",
+                entry.key,
+                entry.updated
+            );
+            entry.fetched_at = Utc::now().timestamp();
+            if let Err(err) = self.cache.put(&entry).await {
+                log::warn!("[cache] unable to store {} : {}", entry.key, err);
+            }
+        }
+        Ok(Some(jira))
+    }
+
+    /// drops the entry of an issue that was just written to
+    async fn evict(&self, key: &str) {
+        if let Err(err) = self.cache.remove(key).await {
+            log::warn!("[cache] unable to remove {} : {}", key, err);
+        }
+    }
+}
+
+#[async_trait]
+impl JiraClient for CachingJiraClient {
+    async fn get_issue(&self, key: &str) -> Result<JiraResponse, Box<dyn std::error::Error>> {
+        let (jira, _) = self.get_issue_json(key).await?;
+        Ok(jira)
+    }
+
+    async fn get_issue_json(
+        &self,
+        key: &str,
+    ) -> Result<(JiraResponse, String), Box<dyn std::error::Error>> {
+        if let Some(entry) = self.cache.lookup(key).await
+            && entry.schema == CACHE_SCHEMA
+        {
+            let data = entry.data.clone();
+            if let Some(jira) = self.revalidate(entry).await? {
+                return Ok((jira, data));
+            }
+        }
+        log::debug!("[cache] miss {}", key);
+        let (jira, data) = self.inner.get_issue_json(key).await?;
+        self.cache.store(&jira, &data).await;
+        Ok((jira, data))
+    }
+
+    async fn get_updated(&self, key: &str) -> Result<String, Box<dyn std::error::Error>> {
+        self.inner.get_updated(key).await
+    }
+
+    async fn search(
+        &self,
+        jql: &str,
+        start_at: i64,
+        page_token: Option<&str>,
+        max_results: i64,
+    ) -> Result<SearchResponse, Box<dyn std::error::Error>> {
+        let page = self
+            .inner
+            .search(jql, start_at, page_token, max_results)
+            .await?;
+        // only the rest client keeps the json of the issues found
+        for (jira, data) in page.issues.iter().zip(page.issues_json.iter()) {
+            self.cache.store(jira, data).await;
+        }
+        Ok(page)
+    }
+
+    async fn get_comments(&self, key: &str) -> Result<Comment, Box<dyn std::error::Error>> {
+        self.inner.get_comments(key).await
+    }
+
+    async fn add_comment(&self, key: &str, body: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.inner.add_comment(key, body).await?;
+        self.evict(key).await;
+        Ok(())
+    }
+
+    async fn get_transitions(
+        &self,
+        key: &str,
+    ) -> Result<Vec<Transition>, Box<dyn std::error::Error>> {
+        self.inner.get_transitions(key).await
+    }
+
+    async fn transition_issue(
+        &self,
+        key: &str,
+        transition_id: &str,
+        comment: Option<&str>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.inner
+            .transition_issue(key, transition_id, comment)
+            .await?;
+        self.evict(key).await;
+        Ok(())
+    }
+
+    async fn create_issues(
+        &self,
+        issues: Vec<Value>,
+    ) -> Result<BulkCreateResponse, Box<dyn std::error::Error>> {
+        self.inner.create_issues(issues).await
+    }
+
+    async fn link_issues(
+        &self,
+        link_type: &str,
+        inward: &str,
+        outward: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.inner.link_issues(link_type, inward, outward).await?;
+        self.evict(inward).await;
+        self.evict(outward).await;
+        Ok(())
+    }
+}
+
+/// cache command, lists the cached issues or prints the entry of key
+pub async fn show_cache(
+    params: &Parameters,
+    key: Option<&str>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let cache = IssueCache::open_for(params, false)?;
+    let res = match key {
+        Some(key) => cache.get(key).await.and_then(|entry| match entry {
+            Some(entry) => Ok(entry.data),
+            None => Err(Box::from(format!("{} is not cached", key))),
+        }),
+        None => cache
+            .list()
+            .await
+            .map(|entries| cache_table(&cache, &entries)),
+    };
+    cache.close().await?;
+    res
+}
+
+/// cache command, removes the entry of key or every entry
+pub async fn clear_cache(
+    params: &Parameters,
+    key: Option<&str>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let cache = IssueCache::open_for(params, false)?;
+    let res = remove_entries(&cache, key).await;
+    cache.close().await?;
+    Ok(format!("removed {} cached issues", res?))
+}
+
+async fn remove_entries(
+    cache: &IssueCache,
+    key: Option<&str>,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let keys = match key {
+        Some(key) => match cache.get(key).await? {
+            Some(_) => vec![key.to_string()],
+            None => return Err(Box::from(format!("{} is not cached", key))),
+        },
+        None => cache
+            .list()
+            .await?
+            .into_iter()
+            .map(|entry| entry.key)
+            .collect(),
+    };
+    for key in keys.iter() {
+        cache.remove(key).await?;
+    }
+    Ok(keys.len())
+}
+
+fn cache_table(cache: &IssueCache, entries: &[CacheEntry]) -> String {
+    let mut table = format!(
+        "{:<12} {:<30} {:<20} {}\n",
+        "KEY", "UPDATED", "FETCHED", "STATE"
+    );
+    for entry in entries.iter() {
+        let fetched = DateTime::from_timestamp(entry.fetched_at, 0)
+            .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+        table.push_str(&format!(
+            "{:<12} {:<30} {:<20} {}\n",
+            entry.key,
+            entry.updated,
+            fetched,
+            match cache.is_fresh(entry) {
+                true => "fresh",
+                false => "stale",
+            }
+        ));
+    }
+    table.push_str(&format!("\n{} cached issues", entries.len()));
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::client::FakeJiraClient;
+
+    #[tokio::test]
+    async fn entries_of_another_schema_are_a_miss() {
+        let dir = std::env::temp_dir().join(format!("jira-service-cache-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let cache = Arc::new(
+            IssueCache::open(format!("{}/issues", dir.to_string_lossy()), 3600, false).unwrap(),
+        );
+        let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/docs/fixtures").to_string();
+        let client = CachingJiraClient::new(Arc::new(FakeJiraClient::new(fixtures)), cache.clone());
+
+        let mut jira = client.inner.get_issue("WRIG-2").await.unwrap();
+        let fixture_summary = jira.fields.summary.clone();
+        // the word alone no longer marks an entry as current
+        jira.fields.summary = "cached, no \"changelog\" here".to_string();
+        let entry = |schema: u32| CacheEntry {
+            key: jira.key.clone(),
+            updated: jira.fields.updated.clone(),
+            fetched_at: Utc::now().timestamp(),
+            data: serde_json::to_string(&jira).unwrap(),
+            schema,
+        };
+
+        cache.put(&entry(0)).await.unwrap();
+        let fetched = client.get_issue("WRIG-2").await.unwrap();
+        assert_eq!(fetched.fields.summary, fixture_summary);
+
+        cache.put(&entry(CACHE_SCHEMA)).await.unwrap();
+        let cached = client.get_issue("WRIG-2").await.unwrap();
+        assert_eq!(cached.fields.summary, jira.fields.summary);
+
+        cache.close().await.unwrap();
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn corrupt_entries_are_refetched_as_jira_returned_them() {
+        let dir = std::env::temp_dir().join(format!("jira-service-corrupt-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let cache = Arc::new(
+            IssueCache::open(format!("{}/issues", dir.to_string_lossy()), 3600, false).unwrap(),
+        );
+        let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/docs/fixtures").to_string();
+        let client = CachingJiraClient::new(
+            Arc::new(FakeJiraClient::new(fixtures.clone())),
+            cache.clone(),
+        );
+
+        cache
+            .put(&CacheEntry {
+                key: "WRIG-2".to_string(),
+                updated: String::new(),
+                fetched_at: Utc::now().timestamp(),
+                data: "{\"key\": \"WRIG-2\", \"fields\": ".to_string(),
+                schema: CACHE_SCHEMA,
+            })
+            .await
+            .unwrap();
+        let jira = client.get_issue("WRIG-2").await.unwrap();
+        assert_eq!(jira.key, "WRIG-2");
+
+        // the entry is overwritten with the fixture text, not a re-serialized model
+        let entry = cache.get("WRIG-2").await.unwrap().unwrap();
+        let fixture = fs::read_to_string(format!("{}/WRIG-2.json", fixtures)).unwrap();
+        assert_eq!(entry.data, fixture);
+        assert_eq!(entry.updated, jira.fields.updated);
+
+        cache.close().await.unwrap();
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,866 @@
+use crate::error::jira::JiraServiceError;
+use crate::handlers::adf;
+use crate::handlers::auth::Credentials;
+use crate::handlers::common::{get_api_root, url_api_version, url_with_api_version};
+use crate::handlers::retry::RetryPolicy;
+use crate::handlers::service::{
+    BulkCreateResponse, Comment, CreatedIssue, JiraResponse, SearchResponse, Transition,
+    TransitionsResponse,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use custom_logger as log;
+use serde::de::DeserializeOwned;
+use serde_derive::Deserialize;
+use serde_json::{Value, json};
+use std::fs;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// all jira http access goes through this trait
+#[async_trait]
+pub trait JiraClient: Send + Sync {
+    async fn get_issue(&self, key: &str) -> Result<JiraResponse, Box<dyn std::error::Error>>;
+    /// the issue with the json it was parsed from, as jira returned it (the issue cache stores
+    /// it so fields the model does not know survive)
+    async fn get_issue_json(
+        &self,
+        key: &str,
+    ) -> Result<(JiraResponse, String), Box<dyn std::error::Error>>;
+    /// returns only fields.updated, used to revalidate cached issues
+    async fn get_updated(&self, key: &str) -> Result<String, Box<dyn std::error::Error>>;
+    /// one page of a jql search, the v2 api pages with start_at and the v3 api with the
+    /// next_page_token of the previous page
+    async fn search(
+        &self,
+        jql: &str,
+        start_at: i64,
+        page_token: Option<&str>,
+        max_results: i64,
+    ) -> Result<SearchResponse, Box<dyn std::error::Error>>;
+    #[allow(unused)]
+    async fn get_comments(&self, key: &str) -> Result<Comment, Box<dyn std::error::Error>>;
+    /// posts a comment (jira wiki markup)
+    async fn add_comment(&self, key: &str, body: &str) -> Result<(), Box<dyn std::error::Error>>;
+    /// transitions available from the current status of the issue
+    async fn get_transitions(
+        &self,
+        key: &str,
+    ) -> Result<Vec<Transition>, Box<dyn std::error::Error>>;
+    /// moves the issue through the transition, optionally adding a comment
+    async fn transition_issue(
+        &self,
+        key: &str,
+        transition_id: &str,
+        comment: Option<&str>,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+    /// creates the issues ({"fields": {..}} each) in one bulk request
+    async fn create_issues(
+        &self,
+        issues: Vec<Value>,
+    ) -> Result<BulkCreateResponse, Box<dyn std::error::Error>>;
+    /// links two issues, the outward issue shows up in the issuelinks of the inward issue
+    async fn link_issues(
+        &self,
+        link_type: &str,
+        inward: &str,
+        outward: &str,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+#[derive(Debug, Deserialize)]
+struct UpdatedResponse {
+    fields: UpdatedFields,
+}
+
+#[derive(Debug, Deserialize)]
+struct UpdatedFields {
+    updated: String,
+}
+
+/// the issues of a search page as jira returned them
+#[derive(Debug, Deserialize)]
+struct RawSearchResponse {
+    issues: Vec<Value>,
+}
+
+/// seconds allowed to establish a connection before the attempt fails (and is retried)
+const CONNECT_TIMEOUT_SECS: u64 = 10;
+
+/// production client using the jira rest api
+pub struct RestJiraClient {
+    client: reqwest::Client,
+    base_url: String,
+    credentials: Credentials,
+    /// print write requests instead of sending them
+    dry_run: bool,
+    retry: RetryPolicy,
+    /// 3 sends descriptions and comments as atlassian documents and searches with search/jql
+    api_version: u32,
+}
+
+impl RestJiraClient {
+    pub fn new(base_url: String, credentials: Credentials) -> Self {
+        let client = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(CONNECT_TIMEOUT_SECS))
+            .build()
+            .unwrap_or_default();
+        RestJiraClient {
+            client,
+            credentials,
+            dry_run: false,
+            retry: RetryPolicy::default(),
+            api_version: url_api_version(&base_url).unwrap_or(2),
+            base_url,
+        }
+    }
+
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// switches the version segment of base_url (.../rest/api/2/issue/) as well
+    pub fn with_api_version(mut self, api_version: u32) -> Self {
+        self.base_url = url_with_api_version(&self.base_url, api_version);
+        self.api_version = api_version;
+        self
+    }
+
+    /// text fields are wiki markup on v2 and atlassian documents on v3
+    fn text_body(&self, text: &str) -> Value {
+        match self.api_version {
+            3 => adf::from_wiki(text),
+            _ => json!(text),
+        }
+    }
+
+    /// posts a json body and returns the response body, in dry run mode the request is printed
+    /// and nothing is sent (None)
+    async fn post_json(
+        &self,
+        url: &str,
+        body: Value,
+        key: Option<&str>,
+    ) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        if self.dry_run {
+            println!("POST {}\n{}", url, serde_json::to_string_pretty(&body)?);
+            return Ok(None);
+        }
+        log::debug!("[post_json] url {}", url);
+        let request = self
+            .client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(&body)?);
+        let data = self
+            .send(self.credentials.apply(request), key, url, false)
+            .await?;
+        Ok(Some(data))
+    }
+
+    async fn get_bytes(
+        &self,
+        url: &str,
+        query: &[(&str, String)],
+        key: Option<&str>,
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        log::debug!("[get_bytes] url {}", url);
+        let request = self.credentials.apply(self.client.get(url).query(query));
+        let data = self.send(request, key, url, true).await?;
+        Ok(data)
+    }
+
+    /// sends the request, retrying transient failures with the retry policy, non success
+    /// statuses are mapped to a JiraServiceError
+    ///
+    /// requests that are not idempotent (posts) are only retried when jira did not act on
+    /// them, a 429 or a failed connect, a 5xx or a read timeout may follow a created issue
+    async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+        key: Option<&str>,
+        url: &str,
+        idempotent: bool,
+    ) -> Result<Vec<u8>, JiraServiceError> {
+        let started = Instant::now();
+        let mut attempt = 1;
+        loop {
+            // bodies are in memory so the request can be cloned, a request that failed to
+            // build (invalid url) cannot and is sent once to report the error
+            let Some(current) = request.try_clone() else {
+                return self
+                    .send_once(request, key, url, idempotent)
+                    .await
+                    .map_err(|(err, _)| err);
+            };
+            let err = match self.send_once(current, key, url, idempotent).await {
+                Ok(data) => return Ok(data),
+                Err((err, false)) => return Err(err),
+                Err((err, true)) => err,
+            };
+            let message = err.to_string();
+            let delay = self.retry.next_delay(err, attempt, started.elapsed())?;
+            log::debug!(
+                "[send] retry {}/{} for {} in {}ms : {}",
+                attempt,
+                self.retry.max_attempts - 1,
+                url,
+                delay.as_millis(),
+                message
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// single attempt, the flag tells whether the error is worth retrying
+    async fn send_once(
+        &self,
+        request: reqwest::RequestBuilder,
+        key: Option<&str>,
+        url: &str,
+        idempotent: bool,
+    ) -> Result<Vec<u8>, (JiraServiceError, bool)> {
+        let network = |e: reqwest::Error| {
+            let transient = e.is_connect() || (idempotent && e.is_timeout());
+            (JiraServiceError::network(key, url, e), transient)
+        };
+        let res = request.send().await.map_err(network)?;
+        let status = res.status();
+        let retry_after = res
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_retry_after);
+        let data = res.bytes().await.map_err(network)?;
+        log::trace!("jira raw response {}", String::from_utf8_lossy(&data));
+        if !status.is_success() {
+            let err = JiraServiceError::from_status(status.as_u16(), key, url, &data, retry_after);
+            let transient = idempotent || status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+            return Err((err, transient));
+        }
+        Ok(data.to_vec())
+    }
+}
+
+#[async_trait]
+impl JiraClient for RestJiraClient {
+    async fn get_issue(&self, key: &str) -> Result<JiraResponse, Box<dyn std::error::Error>> {
+        let (jira, _) = self.get_issue_json(key).await?;
+        Ok(jira)
+    }
+
+    async fn get_issue_json(
+        &self,
+        key: &str,
+    ) -> Result<(JiraResponse, String), Box<dyn std::error::Error>> {
+        let url = format!("{}{}", self.base_url, key);
+        let data = self
+            .get_bytes(&url, &[("expand", "changelog".to_string())], Some(key))
+            .await?;
+        let data =
+            String::from_utf8(data).map_err(|e| JiraServiceError::parse(Some(key), &url, e))?;
+        let jira: JiraResponse =
+            serde_json::from_str(&data).map_err(|e| JiraServiceError::parse(Some(key), &url, e))?;
+        log::trace!("jira response {:?}", jira);
+        Ok((jira, data))
+    }
+
+    async fn get_updated(&self, key: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let url = format!("{}{}", self.base_url, key);
+        let data = self
+            .get_bytes(&url, &[("fields", "updated".to_string())], Some(key))
+            .await?;
+        let updated: UpdatedResponse = serde_json::from_slice(&data)
+            .map_err(|e| JiraServiceError::parse(Some(key), &url, e))?;
+        Ok(updated.fields.updated)
+    }
+
+    async fn search(
+        &self,
+        jql: &str,
+        start_at: i64,
+        page_token: Option<&str>,
+        max_results: i64,
+    ) -> Result<SearchResponse, Box<dyn std::error::Error>> {
+        let mut query = vec![
+            ("jql", jql.to_string()),
+            ("maxResults", max_results.to_string()),
+            ("fields", "*all".to_string()),
+            ("expand", "changelog".to_string()),
+        ];
+        // jira cloud removed the offset based search
+        let url = match self.api_version {
+            3 => {
+                if let Some(token) = page_token {
+                    query.push(("nextPageToken", token.to_string()));
+                }
+                format!("{}search/jql", get_api_root(&self.base_url))
+            }
+            _ => {
+                query.push(("startAt", start_at.to_string()));
+                format!("{}search", get_api_root(&self.base_url))
+            }
+        };
+        let data = self.get_bytes(&url, &query, None).await?;
+        let mut page: SearchResponse =
+            serde_json::from_slice(&data).map_err(|e| JiraServiceError::parse(None, &url, e))?;
+        let raw: RawSearchResponse =
+            serde_json::from_slice(&data).map_err(|e| JiraServiceError::parse(None, &url, e))?;
+        page.issues_json = raw.issues.iter().map(|issue| issue.to_string()).collect();
+        Ok(page)
+    }
+
+    async fn get_comments(&self, key: &str) -> Result<Comment, Box<dyn std::error::Error>> {
+        let url = format!("{}{}/comment", self.base_url, key);
+        let data = self.get_bytes(&url, &[], Some(key)).await?;
+        let comment: Comment = serde_json::from_slice(&data)
+            .map_err(|e| JiraServiceError::parse(Some(key), &url, e))?;
+        Ok(comment)
+    }
+
+    async fn add_comment(&self, key: &str, body: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let url = format!("{}{}/comment", self.base_url, key);
+        self.post_json(&url, json!({ "body": self.text_body(body) }), Some(key))
+            .await?;
+        Ok(())
+    }
+
+    async fn get_transitions(
+        &self,
+        key: &str,
+    ) -> Result<Vec<Transition>, Box<dyn std::error::Error>> {
+        let url = format!("{}{}/transitions", self.base_url, key);
+        let data = self.get_bytes(&url, &[], Some(key)).await?;
+        let res: TransitionsResponse = serde_json::from_slice(&data)
+            .map_err(|e| JiraServiceError::parse(Some(key), &url, e))?;
+        Ok(res.transitions)
+    }
+
+    async fn transition_issue(
+        &self,
+        key: &str,
+        transition_id: &str,
+        comment: Option<&str>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let url = format!("{}{}/transitions", self.base_url, key);
+        let mut body = json!({ "transition": { "id": transition_id } });
+        if let Some(comment) = comment {
+            body["update"] = json!({ "comment": [{ "add": { "body": self.text_body(comment) } }] });
+        }
+        self.post_json(&url, body, Some(key)).await?;
+        Ok(())
+    }
+
+    async fn create_issues(
+        &self,
+        issues: Vec<Value>,
+    ) -> Result<BulkCreateResponse, Box<dyn std::error::Error>> {
+        let url = format!("{}issue/bulk", get_api_root(&self.base_url));
+        let count = issues.len();
+        let mut issues = issues;
+        for issue in issues.iter_mut() {
+            if let Some(description) = issue["fields"]["description"].as_str() {
+                issue["fields"]["description"] = self.text_body(description);
+            }
+        }
+        match self
+            .post_json(&url, json!({ "issueUpdates": issues }), None)
+            .await?
+        {
+            Some(data) => {
+                let res: BulkCreateResponse = serde_json::from_slice(&data)
+                    .map_err(|e| JiraServiceError::parse(None, &url, e))?;
+                Ok(res)
+            }
+            // placeholders so the link requests can be printed as well
+            None => Ok(BulkCreateResponse {
+                issues: (1..=count)
+                    .map(|index| CreatedIssue {
+                        key: format!("<new-{}>", index),
+                        ..Default::default()
+                    })
+                    .collect(),
+                errors: vec![],
+            }),
+        }
+    }
+
+    async fn link_issues(
+        &self,
+        link_type: &str,
+        inward: &str,
+        outward: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let url = format!("{}issueLink", get_api_root(&self.base_url));
+        let body = json!({
+            "type": { "name": link_type },
+            "inwardIssue": { "key": inward },
+            "outwardIssue": { "key": outward },
+        });
+        self.post_json(&url, body, Some(outward)).await?;
+        Ok(())
+    }
+}
+
+/// offline client serving issues from json fixtures ({fixtures_dir}/{key}.json)
+///
+/// search results are read from {fixtures_dir}/search.json and paged like the real endpoint,
+/// child queries (parent = KEY) return the issue fixtures whose parent is KEY
+pub struct FakeJiraClient {
+    fixtures_dir: String,
+    /// false rejects jql naming the Epic Link field, like jira cloud team-managed projects
+    epic_link: bool,
+    /// caps maxResults, like the server side limit of jira
+    page_size: Option<i64>,
+    /// reported instead of the number of results, jira can count issues it does not return
+    total: Option<i64>,
+    /// pages with nextPageToken and no total, like the v3 search
+    page_tokens: bool,
+    /// requests served, in order
+    calls: Mutex<Vec<String>>,
+}
+
+impl FakeJiraClient {
+    pub fn new(fixtures_dir: String) -> Self {
+        FakeJiraClient {
+            fixtures_dir,
+            epic_link: true,
+            page_size: None,
+            total: None,
+            page_tokens: false,
+            calls: Mutex::new(vec![]),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn without_epic_link(mut self) -> Self {
+        self.epic_link = false;
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with_page_size(mut self, page_size: i64) -> Self {
+        self.page_size = Some(page_size);
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with_total(mut self, total: i64) -> Self {
+        self.total = Some(total);
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with_page_tokens(mut self) -> Self {
+        self.page_tokens = true;
+        self
+    }
+
+    /// requests served so far (search 0, comment on WRIG-1 ...)
+    #[cfg(test)]
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    fn record(&self, call: String) {
+        log::debug!("[fake] {}", call);
+        self.calls.lock().unwrap().push(call);
+    }
+
+    /// a missing fixture is reported like a 404 from jira
+    fn read_fixture(&self, name: &str) -> Result<String, JiraServiceError> {
+        let path = format!("{}/{}.json", self.fixtures_dir, name);
+        log::debug!("[read_fixture] path {}", path);
+        match fs::read_to_string(&path) {
+            Ok(data) => Ok(data),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(JiraServiceError::NotFound {
+                key: Some(name.to_string()),
+                url: path,
+            }),
+            Err(e) => Err(JiraServiceError::io(&path, e)),
+        }
+    }
+
+    /// issue fixtures whose parent field is the given key, ordered by key
+    fn children_of(&self, parent: &str) -> Result<Vec<JiraResponse>, JiraServiceError> {
+        let entries = fs::read_dir(&self.fixtures_dir)
+            .map_err(|e| JiraServiceError::io(&self.fixtures_dir, e))?;
+        let mut children = entries
+            .flatten()
+            .filter_map(|entry| fs::read_to_string(entry.path()).ok())
+            // search and transitions fixtures are not issues and fail to parse
+            .filter_map(|data| serde_json::from_str::<JiraResponse>(&data).ok())
+            .filter(|jira| {
+                jira.fields
+                    .parent
+                    .as_ref()
+                    .is_some_and(|issue| issue.key == parent)
+            })
+            .collect::<Vec<JiraResponse>>();
+        children.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(children)
+    }
+
+    fn parse_fixture<T: DeserializeOwned>(&self, name: &str) -> Result<T, JiraServiceError> {
+        let data = self.read_fixture(name)?;
+        serde_json::from_str(&data).map_err(|e| {
+            JiraServiceError::parse(
+                Some(name),
+                &format!("{}/{}.json", self.fixtures_dir, name),
+                e,
+            )
+        })
+    }
+}
+
+#[async_trait]
+impl JiraClient for FakeJiraClient {
+    async fn get_issue(&self, key: &str) -> Result<JiraResponse, Box<dyn std::error::Error>> {
+        let jira: JiraResponse = self.parse_fixture(key)?;
+        Ok(jira)
+    }
+
+    async fn get_issue_json(
+        &self,
+        key: &str,
+    ) -> Result<(JiraResponse, String), Box<dyn std::error::Error>> {
+        let jira = self.get_issue(key).await?;
+        Ok((jira, self.read_fixture(key)?))
+    }
+
+    async fn get_updated(&self, key: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let jira = self.get_issue(key).await?;
+        Ok(jira.fields.updated)
+    }
+
+    async fn search(
+        &self,
+        jql: &str,
+        start_at: i64,
+        page_token: Option<&str>,
+        max_results: i64,
+    ) -> Result<SearchResponse, Box<dyn std::error::Error>> {
+        // the token is the index of the next issue
+        let start_at = match self.page_tokens {
+            true => page_token
+                .and_then(|token| token.parse::<i64>().ok())
+                .unwrap_or_default(),
+            false => start_at,
+        };
+        self.record(format!("search {}", start_at));
+        if !self.epic_link && jql.contains("\"Epic Link\"") {
+            return Err(Box::new(JiraServiceError::Request {
+                status: 400,
+                key: None,
+                url: format!("{}/search.json", self.fixtures_dir),
+                message:
+                    "Field 'Epic Link' does not exist or you do not have permission to view it."
+                        .to_string(),
+            }));
+        }
+        let mut page: SearchResponse = match parent_clause(jql) {
+            Some(parent) => SearchResponse {
+                issues: self.children_of(&parent)?,
+                ..Default::default()
+            },
+            None => self.parse_fixture("search")?,
+        };
+        let found = page.issues.len() as i64;
+        let max_results = self
+            .page_size
+            .map_or(max_results, |size| size.min(max_results));
+        page.total = self.total.unwrap_or(found);
+        page.issues = page
+            .issues
+            .into_iter()
+            .skip(start_at as usize)
+            .take(max_results as usize)
+            .collect();
+        page.start_at = start_at;
+        page.max_results = max_results;
+        if self.page_tokens {
+            let next = start_at + page.issues.len() as i64;
+            page.total = 0;
+            page.next_page_token = (next < found).then(|| next.to_string());
+        }
+        Ok(page)
+    }
+
+    async fn get_comments(&self, key: &str) -> Result<Comment, Box<dyn std::error::Error>> {
+        let jira = self.get_issue(key).await?;
+        Ok(jira.fields.comment)
+    }
+
+    async fn add_comment(&self, key: &str, body: &str) -> Result<(), Box<dyn std::error::Error>> {
+        // nothing is written in test mode, the issue has to exist
+        self.read_fixture(key)?;
+        self.record(format!("comment on {}\n{}", key, body));
+        Ok(())
+    }
+
+    async fn get_transitions(
+        &self,
+        key: &str,
+    ) -> Result<Vec<Transition>, Box<dyn std::error::Error>> {
+        self.read_fixture(key)?;
+        self.record(format!("transitions of {}", key));
+        let res: TransitionsResponse = self.parse_fixture("transitions")?;
+        Ok(res.transitions)
+    }
+
+    async fn transition_issue(
+        &self,
+        key: &str,
+        transition_id: &str,
+        comment: Option<&str>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.read_fixture(key)?;
+        self.record(format!(
+            "transition {} with {}{}",
+            key,
+            transition_id,
+            comment.map(|c| format!("\n{}", c)).unwrap_or_default()
+        ));
+        Ok(())
+    }
+
+    async fn create_issues(
+        &self,
+        issues: Vec<Value>,
+    ) -> Result<BulkCreateResponse, Box<dyn std::error::Error>> {
+        let mut created = vec![];
+        for (index, issue) in issues.iter().enumerate() {
+            let project = issue["fields"]["project"]["key"].as_str().unwrap_or("NEW");
+            let key = format!("{}-{}", project, 1001 + index);
+            self.record(format!(
+                "create {} {}",
+                key,
+                issue["fields"]["summary"].as_str().unwrap_or_default()
+            ));
+            created.push(CreatedIssue {
+                id: (1001 + index).to_string(),
+                key,
+                ..Default::default()
+            });
+        }
+        Ok(BulkCreateResponse {
+            issues: created,
+            errors: vec![],
+        })
+    }
+
+    async fn link_issues(
+        &self,
+        link_type: &str,
+        inward: &str,
+        outward: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.read_fixture(inward)?;
+        self.record(format!("link {} {} {}", inward, link_type, outward));
+        Ok(())
+    }
+}
+
+/// seconds of a Retry-After header, given as seconds or as an http date
+fn parse_retry_after(value: &str) -> Option<u64> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(secs);
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    let secs = (date.with_timezone(&Utc) - Utc::now()).num_seconds();
+    Some(secs.max(0) as u64)
+}
+
+/// key of a "parent = KEY" clause, the fake client answers child queries from the fixtures
+fn parent_clause(jql: &str) -> Option<String> {
+    const CLAUSE: &str = "parent = ";
+    // matched on the original text, lowercasing can change byte lengths (İ)
+    let index = jql.char_indices().map(|(index, _)| index).find(|index| {
+        jql[*index..]
+            .get(..CLAUSE.len())
+            .is_some_and(|candidate| candidate.eq_ignore_ascii_case(CLAUSE))
+    })?;
+    let key = jql[index + CLAUSE.len()..]
+        .split_whitespace()
+        .next()?
+        .trim_matches(|c| c == '"' || c == '\'');
+    Some(key.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn parent_clause_keys() {
+        assert_eq!(parent_clause("parent = WRIG-6"), Some("WRIG-6".to_string()));
+        assert_eq!(
+            parent_clause("\"Epic Link\" = WRIG-4 OR Parent = \"WRIG-4\" ORDER BY key ASC"),
+            Some("WRIG-4".to_string())
+        );
+        assert_eq!(parent_clause("project = WRIG"), None);
+    }
+
+    #[test]
+    fn parent_clause_non_ascii() {
+        // İ lowercases to three bytes
+        assert_eq!(
+            parent_clause("summary ~ \"İİİ\" AND parent = WRIG-6"),
+            Some("WRIG-6".to_string())
+        );
+    }
+
+    #[test]
+    fn retry_after_seconds_and_dates() {
+        assert_eq!(parse_retry_after(" 120 "), Some(120));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(0));
+        let later = (Utc::now() + chrono::Duration::seconds(90)).to_rfc2822();
+        assert!(parse_retry_after(&later).is_some_and(|secs| (85..=90).contains(&secs)));
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    /// answers every request with the status line and Retry-After, returns the base url and
+    /// the request count
+    async fn jira_stub(status: &'static str, retry_after: u64) -> (String, Arc<AtomicUsize>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!(
+            "http://{}/rest/api/2/issue/",
+            listener.local_addr().unwrap()
+        );
+        let count = Arc::new(AtomicUsize::new(0));
+        let requests = count.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                requests.fetch_add(1, Ordering::SeqCst);
+                let mut request = vec![];
+                let mut buf = [0u8; 4096];
+                // headers, then the body announced by content-length
+                while let Ok(n) = stream.read(&mut buf).await {
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request).to_lowercase();
+                    if let Some(end) = text.find("\r\n\r\n") {
+                        let length = text
+                            .lines()
+                            .find_map(|line| line.strip_prefix("content-length:"))
+                            .and_then(|value| value.trim().parse::<usize>().ok())
+                            .unwrap_or(0);
+                        if request.len() >= end + 4 + length {
+                            break;
+                        }
+                    }
+                    if n == 0 {
+                        break;
+                    }
+                }
+                let response = format!(
+                    "HTTP/1.1 {}\r\nRetry-After: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status, retry_after
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        (base_url, count)
+    }
+
+    fn stub_client(base_url: String) -> RestJiraClient {
+        stub_client_with(base_url, 3, Duration::from_secs(10))
+    }
+
+    fn stub_client_with(base_url: String, max_attempts: u32, deadline: Duration) -> RestJiraClient {
+        RestJiraClient::new(base_url, Credentials::Bearer("token".to_string())).with_retry(
+            RetryPolicy {
+                max_attempts,
+                deadline,
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn gets_retry_server_errors() {
+        let (base_url, count) = jira_stub("503 Service Unavailable", 0).await;
+        let err = stub_client(base_url)
+            .get_updated("WRIG-1")
+            .await
+            .unwrap_err();
+        assert_eq!(crate::error::jira::exit_code(err.as_ref()), 7);
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn posts_retry_only_rate_limits() {
+        let (base_url, count) = jira_stub("503 Service Unavailable", 0).await;
+        let client = stub_client(base_url);
+        assert!(client.add_comment("WRIG-1", "status").await.is_err());
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        let (base_url, count) = jira_stub("429 Too Many Requests", 0).await;
+        let client = stub_client(base_url);
+        assert!(client.add_comment("WRIG-1", "status").await.is_err());
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn attempts_bound_the_retries() {
+        let (base_url, count) = jira_stub("429 Too Many Requests", 0).await;
+        let err = stub_client_with(base_url, 5, Duration::from_secs(10))
+            .get_updated("WRIG-1")
+            .await
+            .unwrap_err();
+        assert_eq!(crate::error::jira::exit_code(err.as_ref()), 6);
+        assert_eq!(count.load(Ordering::SeqCst), 5);
+    }
+
+    #[tokio::test]
+    async fn deadline_bounds_the_retries() {
+        // the second wait of 1s would end past the deadline
+        let (base_url, count) = jira_stub("429 Too Many Requests", 1).await;
+        let err = stub_client_with(base_url, 10, Duration::from_millis(1500))
+            .get_updated("WRIG-1")
+            .await
+            .unwrap_err();
+        assert_eq!(crate::error::jira::exit_code(err.as_ref()), 6);
+        assert!(err.to_string().contains("retry after 1s"));
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+
+        // a wait past the deadline is not attempted, an outage stays a server error
+        let (base_url, count) = jira_stub("503 Service Unavailable", 120).await;
+        let err = stub_client_with(base_url, 10, Duration::from_secs(10))
+            .get_updated("WRIG-1")
+            .await
+            .unwrap_err();
+        assert_eq!(crate::error::jira::exit_code(err.as_ref()), 7);
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn dry_run_sends_no_writes() {
+        let (base_url, count) = jira_stub("500 Internal Server Error", 0).await;
+        let client = stub_client(base_url).with_dry_run(true);
+        client.add_comment("WRIG-1", "status").await.unwrap();
+        client
+            .transition_issue("WRIG-1", "21", Some("started"))
+            .await
+            .unwrap();
+        client
+            .link_issues("Relates", "WRIG-1", "WRIG-2")
+            .await
+            .unwrap();
+        let created = client
+            .create_issues(vec![json!({ "fields": { "summary": "new story" } })])
+            .await
+            .unwrap();
+        assert_eq!(created.issues[0].key, "<new-1>");
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+    }
+}
@@ -1,9 +1,11 @@
+use chrono::{DateTime, NaiveDate, Utc};
 use custom_logger as log;
 use surrealkv::{Tree, TreeBuilder};
+use tokio::signal::unix::{SignalKind, signal};
 
 #[allow(unused)]
 pub fn get_error(msg: String) -> Box<dyn std::error::Error> {
-    Box::from(format!("{}", msg.to_lowercase()))
+    Box::from(msg.to_lowercase())
 }
 
 #[allow(unused)]
@@ -18,3 +20,95 @@ pub fn get_opts(db: String) -> Result<Tree, Box<dyn std::error::Error>> {
     log::trace!("[get_opts] tree built");
     Ok(t)
 }
+
+/// strips the trailing issue resource from base_url (.../rest/api/2/issue/ -> .../rest/api/2/)
+pub fn get_api_root(base_url: &str) -> String {
+    let url = base_url.trim_end_matches('/');
+    match url.strip_suffix("/issue") {
+        Some(root) => format!("{}/", root),
+        None => format!("{}/", url),
+    }
+}
+
+/// version segment of a rest api url (.../rest/api/2/issue/ is 2)
+pub fn url_api_version(base_url: &str) -> Option<u32> {
+    let (_, rest) = base_url.split_once("/rest/api/")?;
+    rest.split('/').next()?.parse().ok()
+}
+
+/// replaces the version segment of a rest api url, urls without one are returned as they are
+pub fn url_with_api_version(base_url: &str, version: u32) -> String {
+    match base_url.split_once("/rest/api/") {
+        Some((root, rest)) => match rest.split_once('/') {
+            Some((_, path)) => format!("{}/rest/api/{}/{}", root, version, path),
+            None => format!("{}/rest/api/{}", root, version),
+        },
+        None => base_url.to_string(),
+    }
+}
+
+/// parses jira timestamps (2024-05-13T10:20:30.000+0000)
+pub fn parse_jira_date(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f%z")
+        .or_else(|_| DateTime::parse_from_rfc3339(value))
+        .map(|dt| dt.with_timezone(&Utc))
+        .ok()
+}
+
+/// parses a cli date (2024-05-13 or rfc3339), plain dates start at 00:00 or, when end_of_day
+/// is set, run to 23:59:59 so the day is included
+pub fn parse_date_arg(
+    value: &str,
+    end_of_day: bool,
+) -> Result<DateTime<Utc>, Box<dyn std::error::Error>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| format!("invalid date {} (expected YYYY-MM-DD)", value))?;
+    let time = match end_of_day {
+        true => date.and_hms_opt(23, 59, 59),
+        false => date.and_hms_opt(0, 0, 0),
+    };
+    Ok(time.unwrap().and_utc())
+}
+
+/// jira style duration (1w 2d 4h 30m) in working time, 0m for zero
+pub fn format_duration(seconds: i64, hours_per_day: i64, days_per_week: i64) -> String {
+    let minutes = seconds.max(0) / 60;
+    let hour = 60;
+    let day = hour * hours_per_day.max(1);
+    let week = day * days_per_week.max(1);
+    let parts = [
+        (minutes / week, "w"),
+        (minutes % week / day, "d"),
+        (minutes % day / hour, "h"),
+        (minutes % hour, "m"),
+    ];
+    let duration = parts
+        .iter()
+        .filter(|(value, _)| *value > 0)
+        .map(|(value, unit)| format!("{}{}", value, unit))
+        .collect::<Vec<String>>()
+        .join(" ");
+    match duration.is_empty() {
+        true => "0m".to_string(),
+        false => duration,
+    }
+}
+
+/// resolves on ctrl-c or sigterm
+pub async fn shutdown_signal() {
+    let mut terminate = match signal(SignalKind::terminate()) {
+        Ok(terminate) => terminate,
+        Err(e) => {
+            log::warn!("[shutdown_signal] sigterm handler : {}", e);
+            let _ = tokio::signal::ctrl_c().await;
+            return;
+        }
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate.recv() => {}
+    }
+}
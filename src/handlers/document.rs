@@ -5,7 +5,6 @@ use hyper::body::Bytes;
 use serde_derive::{Deserialize, Serialize};
 
 #[async_trait]
-#[allow(unused)]
 pub trait DocumentformInterface {
     async fn save_formdata(
         db: String,
@@ -16,10 +15,29 @@ pub trait DocumentformInterface {
         db_path: String,
         key: String,
     ) -> Result<FormData, Box<dyn std::error::Error>>;
+    async fn save_document(
+        db_path: String,
+        file: String,
+        document: String,
+    ) -> Result<String, Box<dyn std::error::Error>>;
+    async fn list_queue(db_path: String) -> Result<Vec<String>, Box<dyn std::error::Error>>;
+    async fn queue_formdata(
+        db_path: String,
+        key: String,
+        fd: FormData,
+    ) -> Result<String, Box<dyn std::error::Error>>;
+    async fn remove_formdata(
+        db_path: String,
+        key: String,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+    async fn fail_formdata(
+        db_path: String,
+        key: String,
+        fd: FormData,
+    ) -> Result<String, Box<dyn std::error::Error>>;
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
-#[allow(unused)]
 pub struct FormData {
     pub key: Option<String>,
     pub title: String,
@@ -29,10 +47,15 @@ pub struct FormData {
     pub credentials: String,
     pub run_once: String,
     pub db: String,
+    /// failed runs of the job so far
+    #[serde(default)]
+    pub attempts: u32,
+    /// error of the last failed run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
-#[allow(unused)]
 pub struct Document {}
 
 #[async_trait]
@@ -64,9 +87,49 @@ impl DocumentformInterface for Document {
         let fd = db_read(db, key).await?;
         Ok(fd)
     }
+
+    async fn save_document(
+        db_path: String,
+        file: String,
+        document: String,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        log::debug!("[save_document] saving document with key {}", file);
+        db_upsert(format!("{}/documents", db_path), file, document).await
+    }
+
+    async fn list_queue(db_path: String) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        db_keys(format!("{}/queue", db_path)).await
+    }
+
+    async fn queue_formdata(
+        db_path: String,
+        key: String,
+        fd: FormData,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let json_data = serde_json::to_string(&fd)?;
+        db_upsert(format!("{}/queue", db_path), key, json_data).await
+    }
+
+    async fn remove_formdata(
+        db_path: String,
+        key: String,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        db_delete(format!("{}/queue", db_path), key).await
+    }
+
+    /// moves a job that keeps failing from the queue to the failed store
+    async fn fail_formdata(
+        db_path: String,
+        key: String,
+        fd: FormData,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let json_data = serde_json::to_string(&fd)?;
+        let result = db_upsert(format!("{}/failed", db_path), key.clone(), json_data).await?;
+        db_delete(format!("{}/queue", db_path), key).await?;
+        Ok(result)
+    }
 }
 
-#[allow(unused)]
 async fn db_upsert(
     db: String,
     id: String,
@@ -87,20 +150,24 @@ async fn db_upsert(
     Ok(msg)
 }
 
-#[allow(unused)]
-async fn db_read(db: String, key: String) -> Result<FormData, Box<dyn std::error::Error>> {
+/// the value stored under key (a formdata json or a document)
+pub async fn db_get(db: String, key: String) -> Result<Option<String>, Box<dyn std::error::Error>> {
     let tree = get_opts(db)?;
     // start transaction
     let mut txn = tree.begin().map_err(|e| get_error(e.to_string()))?;
     let b_key = Bytes::from(key.clone());
-    log::debug!("[db_read] key {}", key);
+    log::debug!("[db_get] key {}", key);
     let result = txn.get(&b_key).map_err(|e| get_error(e.to_string()))?;
     // commit transaction
     txn.commit().await?;
     tree.close().await?;
-    match result {
+    Ok(result.map(|value| String::from_utf8_lossy(&value).to_string()))
+}
+
+async fn db_read(db: String, key: String) -> Result<FormData, Box<dyn std::error::Error>> {
+    match db_get(db, key).await? {
         Some(value) => {
-            let fd = serde_json::from_slice(&value).map_err(|e| get_error(e.to_string()))?;
+            let fd = serde_json::from_str(&value).map_err(|e| get_error(e.to_string()))?;
             log::trace!("[db_read] {:?}", fd);
             Ok(fd)
         }
@@ -114,13 +181,14 @@ async fn db_read(db: String, key: String) -> Result<FormData, Box<dyn std::error
                 credentials: "".to_string(),
                 run_once: "on".to_string(),
                 db: "formdata".to_string(),
+                attempts: 0,
+                error: None,
             };
             Ok(fd)
         }
     }
 }
 
-#[allow(unused)]
 async fn db_delete(db: String, key: String) -> Result<(), Box<dyn std::error::Error>> {
     let tree = get_opts(db)?;
     // start transaction
@@ -133,3 +201,23 @@ async fn db_delete(db: String, key: String) -> Result<(), Box<dyn std::error::Er
     tree.close().await?;
     Ok(())
 }
+
+async fn db_keys(db: String) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let tree = get_opts(db)?;
+    // start transaction
+    let mut txn = tree.begin().map_err(|e| get_error(e.to_string()))?;
+    // keys are utf-8 so they all sort below 0xff
+    let range = txn
+        .range(b"".as_slice(), [0xffu8].as_slice())
+        .map_err(|e| get_error(e.to_string()))?;
+    let mut keys = vec![];
+    for item in range {
+        let (key, _) = item.map_err(|e| get_error(e.to_string()))?;
+        keys.push(String::from_utf8_lossy(&key).to_string());
+    }
+    log::trace!("[db_keys] {:?}", keys);
+    // commit transaction
+    txn.commit().await?;
+    tree.close().await?;
+    Ok(keys)
+}
@@ -0,0 +1,399 @@
+use crate::handlers::markdown::{Block, Span, parse_blocks};
+use crate::handlers::render::ReportRenderer;
+use crate::handlers::report::{EffortView, EpicView, OverviewRow, Report, SubtaskView};
+
+const STYLE: &str = r#"
+body { font-family: -apple-system, "Segoe UI", Helvetica, Arial, sans-serif; line-height: 1.5; color: #1f2328; max-width: 960px; margin: 2rem auto; padding: 0 1rem; }
+h1 { border-bottom: 2px solid #d0d7de; padding-bottom: .3rem; }
+h2 { margin-top: 2.5rem; border-bottom: 1px solid #d0d7de; padding-bottom: .2rem; }
+a { color: #0969da; text-decoration: none; }
+a:hover { text-decoration: underline; }
+.tag { font-size: .75em; background: #6639ba; color: #fff; border-radius: 4px; padding: .1rem .4rem; vertical-align: middle; }
+.status { display: inline-block; font-size: .8em; font-weight: 600; border-radius: 1rem; padding: .1rem .6rem; background: #eaeef2; }
+.status-to-do { background: #ddf4ff; color: #0969da; }
+.status-in-progress { background: #fff8c5; color: #9a6700; }
+.status-done { background: #dafbe1; color: #1a7f37; }
+.story { border: 1px solid #d0d7de; border-radius: 6px; padding: .5rem 1rem; margin: 1rem 0; }
+.story h4 { margin: .3rem 0; }
+.comment { border-left: 3px solid #d0d7de; padding-left: .8rem; margin: .8rem 0; }
+.comment .meta { color: #656d76; font-size: .85em; }
+.link { color: #656d76; font-size: .85em; }
+.bar { display: inline-block; width: 8rem; height: .6rem; background: #eaeef2; border-radius: .3rem; overflow: hidden; vertical-align: middle; margin-right: .4rem; }
+.bar span { display: block; height: 100%; background: #1a7f37; }
+.over { color: #cf222e; font-weight: 600; margin-left: .4rem; }
+pre { background: #f6f8fa; padding: .8rem; border-radius: 6px; overflow-x: auto; }
+code { background: #f6f8fa; padding: .1rem .3rem; border-radius: 4px; font-size: .9em; }
+pre code { padding: 0; }
+blockquote { color: #656d76; border-left: 4px solid #d0d7de; margin: 0; padding: 0 1rem; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #d0d7de; padding: .3rem .7rem; }
+th { background: #f6f8fa; }
+"#;
+
+/// standalone html document with embedded css
+pub struct HtmlRenderer {}
+
+impl ReportRenderer for HtmlRenderer {
+    fn extension(&self) -> &'static str {
+        "html"
+    }
+
+    fn content_type(&self) -> &'static str {
+        "text/html; charset=utf-8"
+    }
+
+    fn render(&self, report: &Report) -> Result<String, Box<dyn std::error::Error>> {
+        let mut doc = String::from("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n");
+        doc.push_str("<meta charset=\"utf-8\">\n");
+        doc.push_str(&format!("<title>{}</title>\n", escape(&report.title)));
+        doc.push_str(&format!("<style>{}</style>\n</head>\n<body>\n", STYLE));
+        doc.push_str(&format!(
+            "<h1>[{}] {}</h1>\n",
+            escape(&report.date),
+            escape(&report.title)
+        ));
+        doc.push_str("<h2>Summary</h2>\n");
+        doc.push_str(&overview_html(report));
+        for epic in report.epics.iter() {
+            doc.push_str("<section class=\"epic\">\n");
+            doc.push_str(&format!(
+                "<h2><span class=\"tag\">EPIC</span> {} {}</h2>\n",
+                link(&epic.url, &epic.key),
+                escape(&epic.summary)
+            ));
+            doc.push_str(&format!("<p>{}</p>\n", status(&epic.status)));
+            doc.push_str("<h3>Description</h3>\n");
+            doc.push_str(&blocks_to_html(&parse_blocks(&epic.description), 3));
+            doc.push_str(&format!(
+                "<h3>Changes {} - {}</h3>\n",
+                escape(&report.since),
+                escape(&report.until)
+            ));
+            match epic.changes.is_empty() {
+                true => doc.push_str("<p>No status, assignee or resolution changes</p>\n"),
+                false => {
+                    doc.push_str("<table class=\"changes\">\n<tr><th>Date</th><th>Issue</th><th>Field</th><th>From</th><th>To</th><th>By</th></tr>\n");
+                    for change in epic.changes.iter() {
+                        doc.push_str(&format!(
+                            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                            escape(&change.date),
+                            link(&change.url, &change.key),
+                            escape(&change.field),
+                            escape(&change.from),
+                            escape(&change.to),
+                            escape(&change.author)
+                        ));
+                    }
+                    doc.push_str("</table>\n");
+                }
+            }
+            doc.push_str("<h3>Effort</h3>\n");
+            doc.push_str(&effort_html(epic));
+            doc.push_str("<h3>Stories</h3>\n");
+            for story in epic.stories.iter() {
+                doc.push_str("<article class=\"story\">\n");
+                doc.push_str(&format!(
+                    "<h4>{} {}</h4>\n",
+                    link(&story.url, &story.key),
+                    escape(&story.summary)
+                ));
+                doc.push_str(&format!(
+                    "<p>{} <span class=\"link\">{}</span></p>\n",
+                    status(&story.status),
+                    escape(&story.link)
+                ));
+                doc.push_str(&subtasks_html(&story.subtasks));
+                if story.in_progress {
+                    doc.push_str("<h5>Description</h5>\n");
+                    doc.push_str(&blocks_to_html(&parse_blocks(&story.description), 5));
+                    doc.push_str("<h5>Comments</h5>\n");
+                    for comment in story.comments.iter() {
+                        doc.push_str("<div class=\"comment\">\n");
+                        doc.push_str(&format!(
+                            "<div class=\"meta\">{} {}</div>\n",
+                            escape(&comment.author),
+                            escape(&comment.created)
+                        ));
+                        doc.push_str(&blocks_to_html(&parse_blocks(&comment.body), 5));
+                        doc.push_str("</div>\n");
+                    }
+                }
+                doc.push_str("</article>\n");
+            }
+            doc.push_str("</section>\n");
+        }
+        doc.push_str("</body>\n</html>\n");
+        Ok(doc)
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn link(url: &str, text: &str) -> String {
+    format!("<a href=\"{}\">{}</a>", escape(url), escape(text))
+}
+
+/// story counts per status category of every epic and the total
+fn overview_html(report: &Report) -> String {
+    let mut html = String::from(
+        "<table class=\"overview\">\n<tr><th>Epic</th><th>To Do</th><th>In Progress</th><th>Done</th><th>Complete</th>",
+    );
+    if report.story_points {
+        html.push_str("<th>Points</th>");
+    }
+    html.push_str("<th>Updated</th></tr>\n");
+    let row = |epic: String, row: &OverviewRow| {
+        let mut tr = format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td><span class=\"bar\"><span style=\"width: {}%\"></span></span>{}%</td>",
+            epic, row.to_do, row.in_progress, row.done, row.percent, row.percent
+        );
+        if report.story_points {
+            tr.push_str(&format!("<td>{}</td>", escape(&row.points)));
+        }
+        tr.push_str(&format!("<td>{}</td></tr>\n", escape(&row.updated)));
+        tr
+    };
+    for epic in report.overview.iter() {
+        html.push_str(&row(
+            format!("{} {}", link(&epic.url, &epic.key), escape(&epic.summary)),
+            epic,
+        ));
+    }
+    html.push_str(&row(
+        format!("<strong>Total</strong> ({})", escape(&report.total.summary)),
+        &report.total,
+    ));
+    html.push_str("</table>\n");
+    html
+}
+
+/// estimate, remaining and logged time of the epic (when tracked), its stories and the total
+fn effort_html(epic: &EpicView) -> String {
+    if !epic.effort.tracked {
+        return "<p>No estimates or logged time</p>\n".to_string();
+    }
+    let mut html = String::from(
+        "<table class=\"effort\">\n<tr><th>Issue</th><th>Estimate</th><th>Remaining</th><th>Logged</th><th>Progress</th></tr>\n",
+    );
+    let mut rows = vec![];
+    if epic.own_effort.tracked {
+        rows.push((link(&epic.url, &epic.key), &epic.own_effort));
+    }
+    for story in epic.stories.iter().filter(|story| story.effort.tracked) {
+        rows.push((link(&story.url, &story.key), &story.effort));
+    }
+    rows.push(("<strong>Total</strong>".to_string(), &epic.effort));
+    for (issue, effort) in rows {
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            issue,
+            escape(&effort.original),
+            escape(&effort.remaining),
+            escape(&effort.logged),
+            progress(effort)
+        ));
+    }
+    html.push_str("</table>\n");
+    html
+}
+
+fn progress(effort: &EffortView) -> String {
+    let over = match effort.over_estimate {
+        true => "<span class=\"over\">over estimate</span>",
+        false => "",
+    };
+    format!(
+        "<span class=\"bar\"><span style=\"width: {}%\"></span></span>{}%{}",
+        effort.percent, effort.percent, over
+    )
+}
+
+/// nested lists following the depth of the flattened subtasks
+fn subtasks_html(subtasks: &[SubtaskView]) -> String {
+    let mut html = String::new();
+    let mut depth = 0;
+    for subtask in subtasks.iter() {
+        while depth < subtask.depth {
+            html.push_str("<ul class=\"subtasks\">\n");
+            depth += 1;
+        }
+        while depth > subtask.depth {
+            html.push_str("</ul>\n");
+            depth -= 1;
+        }
+        html.push_str(&format!(
+            "<li>{} {} {}</li>\n",
+            link(&subtask.url, &subtask.key),
+            escape(&subtask.summary),
+            status(&subtask.status)
+        ));
+    }
+    html.push_str(&"</ul>\n".repeat(depth));
+    html
+}
+
+fn status(name: &str) -> String {
+    format!(
+        "<span class=\"status status-{}\">{}</span>",
+        escape(&name.to_lowercase().replace(' ', "-")),
+        escape(name)
+    )
+}
+
+/// headings inside descriptions are shifted below the section they are rendered in
+fn blocks_to_html(blocks: &[Block], offset: usize) -> String {
+    let mut html = String::new();
+    for block in blocks {
+        match block {
+            Block::Heading(level, spans) => {
+                let level = (level + offset).min(6);
+                html.push_str(&format!(
+                    "<h{}>{}</h{}>\n",
+                    level,
+                    spans_to_html(spans),
+                    level
+                ));
+            }
+            Block::Paragraph(lines) => {
+                let lines = lines
+                    .iter()
+                    .map(|line| spans_to_html(line))
+                    .collect::<Vec<String>>();
+                html.push_str(&format!("<p>{}</p>\n", lines.join("<br>\n")));
+            }
+            Block::List(items) => {
+                // open and close nested lists as the depth changes
+                let mut open: Vec<&str> = vec![];
+                for item in items {
+                    let tag = if item.ordered { "ol" } else { "ul" };
+                    while open.len() > item.depth {
+                        html.push_str(&format!("</li></{}>\n", open.pop().unwrap()));
+                    }
+                    if open.len() == item.depth && open.last() != Some(&tag) {
+                        html.push_str(&format!("</li></{}>\n", open.pop().unwrap()));
+                    }
+                    if open.len() == item.depth {
+                        html.push_str("</li>\n");
+                    }
+                    while open.len() < item.depth {
+                        html.push_str(&format!("<{}>\n", tag));
+                        open.push(tag);
+                    }
+                    html.push_str(&format!("<li>{}", spans_to_html(&item.spans)));
+                }
+                while let Some(tag) = open.pop() {
+                    html.push_str(&format!("</li></{}>\n", tag));
+                }
+            }
+            Block::Code { language, body } => {
+                let class = match language.is_empty() {
+                    true => String::new(),
+                    false => format!(" class=\"language-{}\"", escape(language)),
+                };
+                html.push_str(&format!(
+                    "<pre><code{}>{}</code></pre>\n",
+                    class,
+                    escape(body)
+                ));
+            }
+            Block::Quote(inner) => {
+                html.push_str(&format!(
+                    "<blockquote>\n{}</blockquote>\n",
+                    blocks_to_html(inner, offset)
+                ));
+            }
+            Block::Table { header, rows } => {
+                html.push_str("<table>\n");
+                if let Some(header) = header {
+                    html.push_str("<tr>");
+                    for cell in header {
+                        html.push_str(&format!("<th>{}</th>", spans_to_html(cell)));
+                    }
+                    html.push_str("</tr>\n");
+                }
+                for row in rows {
+                    html.push_str("<tr>");
+                    for cell in row {
+                        html.push_str(&format!("<td>{}</td>", spans_to_html(cell)));
+                    }
+                    html.push_str("</tr>\n");
+                }
+                html.push_str("</table>\n");
+            }
+            Block::Rule => html.push_str("<hr>\n"),
+        }
+    }
+    html
+}
+
+fn spans_to_html(spans: &[Span]) -> String {
+    spans
+        .iter()
+        .map(|span| match span {
+            Span::Text(text) => escape(text),
+            Span::Code(code) => format!("<code>{}</code>", escape(code)),
+            Span::Strong(inner) => format!("<strong>{}</strong>", spans_to_html(inner)),
+            Span::Emphasis(inner) => format!("<em>{}</em>", spans_to_html(inner)),
+            Span::Strike(inner) => format!("<del>{}</del>", spans_to_html(inner)),
+            Span::Link { text, url } if is_safe_url(url) => {
+                format!("<a href=\"{}\">{}</a>", escape(url), spans_to_html(text))
+            }
+            Span::Link { text, .. } => spans_to_html(text),
+            Span::Image { alt, url } if is_safe_url(url) => {
+                format!("<img src=\"{}\" alt=\"{}\">", escape(url), escape(alt))
+            }
+            Span::Image { alt, .. } => escape(alt),
+        })
+        .collect()
+}
+
+/// link targets come from jira text, only http, https and mailto (or relative) urls are
+/// rendered as links so javascript: or data: targets stay text
+fn is_safe_url(url: &str) -> bool {
+    // browsers ignore whitespace and control characters inside the scheme
+    let url = url
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace() && !c.is_ascii_control())
+        .collect::<String>()
+        .to_lowercase();
+    match url.find([':', '/', '?', '#']) {
+        Some(index) if url[index..].starts_with(':') => {
+            ["http", "https", "mailto"].contains(&&url[..index])
+        }
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_web_links_are_rendered() {
+        let html = blocks_to_html(
+            &parse_blocks(
+                "[docs](https://example.com) [mail](mailto:a@example.com) [local](#top) [click](javascript:alert`1`) [tab](java\tscript:x) ![x](data:image/png;base64,AA)",
+            ),
+            0,
+        );
+        assert!(html.contains("<a href=\"https://example.com\">docs</a>"));
+        assert!(html.contains("<a href=\"mailto:a@example.com\">mail</a>"));
+        assert!(html.contains("<a href=\"#top\">local</a>"));
+        assert!(!html.contains("script:"), "{}", html);
+        assert!(!html.contains("<img"), "{}", html);
+        assert!(html.contains(" click "));
+    }
+
+    #[test]
+    fn status_class_is_escaped() {
+        assert_eq!(
+            status("In \"Review\""),
+            "<span class=\"status status-in-&quot;review&quot;\">In &quot;Review&quot;</span>"
+        );
+    }
+}
@@ -0,0 +1,360 @@
+// module markdown
+//
+// parses the markdown subset produced by the markup module (headings, lists, fenced code,
+// quotes, tables, rules and inline emphasis/code/links/images) so the html and asciidoc
+// renderers can re-emit descriptions and comments in their own syntax
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Block {
+    Heading(usize, Vec<Span>),
+    Paragraph(Vec<Vec<Span>>),
+    List(Vec<ListItem>),
+    Code {
+        language: String,
+        body: String,
+    },
+    Quote(Vec<Block>),
+    Table {
+        header: Option<Vec<Vec<Span>>>,
+        rows: Vec<Vec<Vec<Span>>>,
+    },
+    Rule,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListItem {
+    /// nesting level starting at 1
+    pub depth: usize,
+    pub ordered: bool,
+    pub spans: Vec<Span>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Span {
+    Text(String),
+    Code(String),
+    Strong(Vec<Span>),
+    Emphasis(Vec<Span>),
+    Strike(Vec<Span>),
+    Link { text: Vec<Span>, url: String },
+    Image { alt: String, url: String },
+}
+
+pub fn parse_blocks(text: &str) -> Vec<Block> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut blocks = vec![];
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            i += 1;
+            continue;
+        }
+        // fenced code
+        if let Some(language) = trimmed.strip_prefix("```") {
+            let mut body = vec![];
+            i += 1;
+            while i < lines.len() && lines[i].trim() != "```" {
+                body.push(lines[i]);
+                i += 1;
+            }
+            blocks.push(Block::Code {
+                language: language.trim().to_string(),
+                body: body.join("\n"),
+            });
+            i += 1;
+            continue;
+        }
+        // quotes (the inner text is parsed again)
+        if trimmed.starts_with('>') {
+            let mut inner = vec![];
+            while i < lines.len() && lines[i].trim().starts_with('>') {
+                let quoted = lines[i].trim().trim_start_matches('>');
+                inner.push(quoted.strip_prefix(' ').unwrap_or(quoted));
+                i += 1;
+            }
+            blocks.push(Block::Quote(parse_blocks(&inner.join("\n"))));
+            continue;
+        }
+        if let Some((level, rest)) = heading(trimmed) {
+            blocks.push(Block::Heading(level, parse_spans(rest)));
+            i += 1;
+            continue;
+        }
+        if trimmed == "---" {
+            blocks.push(Block::Rule);
+            i += 1;
+            continue;
+        }
+        if trimmed.starts_with('|') {
+            let mut header = None;
+            let mut rows = vec![];
+            while i < lines.len() && lines[i].trim().starts_with('|') {
+                let cells = table_cells(lines[i].trim());
+                let is_separator = cells
+                    .iter()
+                    .all(|cell| !cell.is_empty() && cell.chars().all(|c| c == '-' || c == ':'));
+                if is_separator {
+                    // the row above the separator is the header
+                    header = rows.pop();
+                } else {
+                    rows.push(cells.iter().map(|cell| parse_spans(cell)).collect());
+                }
+                i += 1;
+            }
+            blocks.push(Block::Table { header, rows });
+            continue;
+        }
+        if list_item(line).is_some() {
+            let mut items = vec![];
+            // indents of the open levels, nested items line up with the text of their parent
+            // so the step is 2 below bullets and 3 below numbers
+            let mut levels: Vec<usize> = vec![];
+            while i < lines.len()
+                && let Some((indent, mut item)) = list_item(lines[i])
+            {
+                while levels.last().is_some_and(|last| *last > indent) {
+                    levels.pop();
+                }
+                if levels.last() != Some(&indent) {
+                    levels.push(indent);
+                }
+                item.depth = levels.len();
+                items.push(item);
+                i += 1;
+            }
+            blocks.push(Block::List(items));
+            continue;
+        }
+        let mut paragraph = vec![];
+        while i < lines.len() {
+            let current = lines[i].trim();
+            if current.is_empty()
+                || current.starts_with("```")
+                || current.starts_with('>')
+                || current.starts_with('|')
+                || heading(current).is_some()
+                || list_item(lines[i]).is_some()
+            {
+                break;
+            }
+            paragraph.push(parse_spans(current));
+            i += 1;
+        }
+        blocks.push(Block::Paragraph(paragraph));
+    }
+    blocks
+}
+
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if (1..=6).contains(&level) && line[level..].starts_with(' ') {
+        return Some((level, line[level..].trim()));
+    }
+    None
+}
+
+/// indent and item, the depth is set from the indents of the enclosing items
+fn list_item(line: &str) -> Option<(usize, ListItem)> {
+    let indent = line.len() - line.trim_start().len();
+    let trimmed = line.trim_start();
+    let (ordered, rest) = if let Some(rest) = trimmed.strip_prefix("- ") {
+        (false, rest)
+    } else {
+        let digits = trimmed.chars().take_while(|c| c.is_ascii_digit()).count();
+        if digits == 0 || !trimmed[digits..].starts_with(". ") {
+            return None;
+        }
+        (true, &trimmed[digits + 2..])
+    };
+    Some((
+        indent,
+        ListItem {
+            depth: 1,
+            ordered,
+            spans: parse_spans(rest.trim()),
+        },
+    ))
+}
+
+/// cells split on unescaped pipes, \| is a pipe inside a cell (also inside code spans)
+fn table_cells(line: &str) -> Vec<String> {
+    let inner = line.strip_prefix('|').unwrap_or(line);
+    let inner = match inner.strip_suffix('|') {
+        Some(rest) if !rest.ends_with('\\') => rest,
+        _ => inner,
+    };
+    let mut cells = vec![];
+    let mut cell = String::new();
+    let mut chars = inner.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                cell.push('|');
+                chars.next();
+            }
+            '|' => cells.push(std::mem::take(&mut cell).trim().to_string()),
+            _ => cell.push(c),
+        }
+    }
+    cells.push(cell.trim().to_string());
+    cells
+}
+
+/// wraps the parsed inner spans of an emphasis
+type SpanBuilder = fn(Vec<Span>) -> Span;
+
+pub fn parse_spans(text: &str) -> Vec<Span> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut spans = vec![];
+    let mut buffer = String::new();
+    let mut i = 0;
+    let flush = |buffer: &mut String, spans: &mut Vec<Span>| {
+        if !buffer.is_empty() {
+            spans.push(Span::Text(std::mem::take(buffer)));
+        }
+    };
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\\' && i + 1 < chars.len() && chars[i + 1].is_ascii_punctuation() {
+            buffer.push(chars[i + 1]);
+            i += 2;
+            continue;
+        }
+        if c == '`'
+            && let Some(end) = find(&chars, i + 1, &['`'])
+        {
+            flush(&mut buffer, &mut spans);
+            spans.push(Span::Code(chars[i + 1..end].iter().collect()));
+            i = end + 1;
+            continue;
+        }
+        if c == '!'
+            && chars.get(i + 1) == Some(&'[')
+            && let Some((alt, url, end)) = link_at(&chars, i + 1)
+        {
+            flush(&mut buffer, &mut spans);
+            spans.push(Span::Image { alt, url });
+            i = end;
+            continue;
+        }
+        if c == '['
+            && let Some((text, url, end)) = link_at(&chars, i)
+        {
+            flush(&mut buffer, &mut spans);
+            spans.push(Span::Link {
+                text: parse_spans(&text),
+                url,
+            });
+            i = end;
+            continue;
+        }
+        if c == '<'
+            && let Some(end) = find(&chars, i + 1, &['>'])
+        {
+            let url: String = chars[i + 1..end].iter().collect();
+            if url.contains("://") || url.starts_with("mailto:") {
+                flush(&mut buffer, &mut spans);
+                spans.push(Span::Link {
+                    text: vec![Span::Text(url.clone())],
+                    url,
+                });
+                i = end + 1;
+                continue;
+            }
+        }
+        let delimiters: [(&[char], SpanBuilder); 3] = [
+            (&['*', '*'], Span::Strong),
+            (&['~', '~'], Span::Strike),
+            (&['*'], Span::Emphasis),
+        ];
+        let mut matched = false;
+        for (delimiter, build) in delimiters {
+            if chars[i..].starts_with(delimiter)
+                && let Some(end) = find(&chars, i + delimiter.len(), delimiter)
+                && end > i + delimiter.len()
+            {
+                flush(&mut buffer, &mut spans);
+                let inner: String = chars[i + delimiter.len()..end].iter().collect();
+                spans.push(build(parse_spans(&inner)));
+                i = end + delimiter.len();
+                matched = true;
+                break;
+            }
+        }
+        if matched {
+            continue;
+        }
+        buffer.push(c);
+        i += 1;
+    }
+    flush(&mut buffer, &mut spans);
+    spans
+}
+
+fn find(chars: &[char], from: usize, seq: &[char]) -> Option<usize> {
+    (from..chars.len()).find(|i| chars[*i..].starts_with(seq))
+}
+
+/// [text](url) starting at start, returns text, url and the index after the link
+fn link_at(chars: &[char], start: usize) -> Option<(String, String, usize)> {
+    let close = find(chars, start + 1, &[']'])?;
+    if chars.get(close + 1) != Some(&'(') {
+        return None;
+    }
+    let end = find(chars, close + 2, &[')'])?;
+    Some((
+        chars[start + 1..close].iter().collect(),
+        chars[close + 2..end].iter().collect(),
+        end + 1,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn depths(text: &str) -> Vec<(usize, bool)> {
+        match parse_blocks(text).as_slice() {
+            [Block::List(items)] => items
+                .iter()
+                .map(|item| (item.depth, item.ordered))
+                .collect(),
+            blocks => panic!("expected a single list, got {:?}", blocks),
+        }
+    }
+
+    #[test]
+    fn nested_list_depths() {
+        assert_eq!(
+            depths("- a\n  - b\n    - c\n- d"),
+            [(1, false), (2, false), (3, false), (1, false)]
+        );
+        // numbered items nest by 3, the width of "1. "
+        assert_eq!(
+            depths("1. a\n   1. b\n      1. c\n1. d"),
+            [(1, true), (2, true), (3, true), (1, true)]
+        );
+        assert_eq!(
+            depths("1. a\n   - b\n     - c\n   1. d"),
+            [(1, true), (2, false), (3, false), (2, true)]
+        );
+    }
+
+    #[test]
+    fn table_cells_with_escaped_pipes() {
+        let blocks = parse_blocks("| Key | Value |\n| --- | --- |\n| a \\| b | `x\\|y` |");
+        let [Block::Table { header, rows }] = blocks.as_slice() else {
+            panic!("expected a table, got {:?}", blocks);
+        };
+        assert_eq!(header.as_ref().map(Vec::len), Some(2));
+        assert_eq!(
+            rows,
+            &[vec![
+                vec![Span::Text("a | b".to_string())],
+                vec![Span::Code("x|y".to_string())],
+            ]]
+        );
+    }
+}
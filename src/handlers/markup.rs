@@ -0,0 +1,680 @@
+// module markup
+//
+// converts jira wiki markup to markdown
+//
+// the text is first split into code ({code}, {noformat}) and text segments, code segments are
+// emitted verbatim as fenced blocks, text segments are converted line by line (block level)
+// and then span by span (inline level)
+
+/// converts jira wiki markup (descriptions and comment bodies) to markdown
+pub fn to_markdown(text: &str) -> String {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let mut lines: Vec<String> = vec![];
+    let mut state = BlockState::default();
+    for segment in split_segments(&text) {
+        match segment {
+            Segment::Text(value) => {
+                for line in value.split('\n') {
+                    convert_line(line, &mut state, &mut lines);
+                }
+            }
+            Segment::Code { language, body } => {
+                let prefix = state.prefix();
+                lines.push(format!("{}```{}", prefix, language));
+                for line in body.trim_matches('\n').split('\n') {
+                    lines.push(format!("{}{}", prefix, line).trim_end().to_string());
+                }
+                lines.push(format!("{}```", prefix));
+            }
+        }
+    }
+    // code segments split a line in two, collapse the empty lines this leaves behind
+    let mut result: Vec<String> = vec![];
+    for line in lines {
+        if line.is_empty() && result.last().is_some_and(|last| last.is_empty()) {
+            continue;
+        }
+        result.push(line);
+    }
+    result.join("\n").trim_matches('\n').to_string()
+}
+
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Text(String),
+    Code { language: String, body: String },
+}
+
+/// splits the text on {code[:params]}...{code} and {noformat}...{noformat}
+fn split_segments(text: &str) -> Vec<Segment> {
+    let mut segments = vec![];
+    let mut rest = text;
+    loop {
+        let code = find_macro(rest, "code");
+        let noformat = find_macro(rest, "noformat");
+        let next = match (code, noformat) {
+            (Some(c), Some(n)) => Some(if c.0 <= n.0 {
+                ("code", c)
+            } else {
+                ("noformat", n)
+            }),
+            (Some(c), None) => Some(("code", c)),
+            (None, Some(n)) => Some(("noformat", n)),
+            (None, None) => None,
+        };
+        let Some((name, (start, end, params))) = next else {
+            segments.push(Segment::Text(rest.to_string()));
+            break;
+        };
+        segments.push(Segment::Text(rest[..start].to_string()));
+        let close = format!("{{{}}}", name);
+        let body_rest = &rest[end..];
+        let (body, after) = match body_rest.find(&close) {
+            Some(pos) => (&body_rest[..pos], &body_rest[pos + close.len()..]),
+            // unterminated block runs to the end of the text
+            None => (body_rest, ""),
+        };
+        let language = match name {
+            "code" => code_language(&params),
+            _ => String::new(),
+        };
+        segments.push(Segment::Code {
+            language,
+            body: body.to_string(),
+        });
+        rest = after;
+    }
+    segments
+}
+
+/// finds an opening {name} or {name:params} tag, returns (start, end, params)
+fn find_macro(text: &str, name: &str) -> Option<(usize, usize, String)> {
+    let open = format!("{{{}", name);
+    let mut offset = 0;
+    while let Some(pos) = text[offset..].find(&open) {
+        let start = offset + pos;
+        let after = &text[start + open.len()..];
+        if after.starts_with('}') {
+            return Some((start, start + open.len() + 1, String::new()));
+        }
+        if let Some(params) = after.strip_prefix(':')
+            && let Some(close) = params.find('}')
+        {
+            return Some((
+                start,
+                start + open.len() + 1 + close + 1,
+                params[..close].to_string(),
+            ));
+        }
+        offset = start + open.len();
+    }
+    None
+}
+
+/// {code:java}, {code:language=java|title=x} or {code:title=x|java}
+fn code_language(params: &str) -> String {
+    for param in params.split('|') {
+        match param.split_once('=') {
+            Some(("language", value)) | Some(("lang", value)) => {
+                return value.trim().to_lowercase();
+            }
+            Some(_) => {}
+            None if !param.trim().is_empty() => return param.trim().to_lowercase(),
+            None => {}
+        }
+    }
+    String::new()
+}
+
+#[derive(Debug, Default)]
+struct BlockState {
+    quote: bool,
+    panel: bool,
+    table_header: bool,
+}
+
+impl BlockState {
+    fn prefix(&self) -> &'static str {
+        if self.quote || self.panel { "> " } else { "" }
+    }
+}
+
+fn convert_line(line: &str, state: &mut BlockState, lines: &mut Vec<String>) {
+    let mut line = line.trim_end().to_string();
+    let mut had_macro = false;
+
+    // block macros can share a line with text ({quote}text{quote})
+    for (tag, is_panel) in [("{quote}", false), ("{panel", true)] {
+        while let Some(pos) = line.find(tag) {
+            let end = match is_panel {
+                true => match line[pos..].find('}') {
+                    Some(close) => pos + close + 1,
+                    None => break,
+                },
+                false => pos + tag.len(),
+            };
+            let params = line[pos + tag.len()..end]
+                .trim_start_matches(':')
+                .trim_end_matches('}');
+            let before = line[..pos].to_string();
+            let after = line[end..].to_string();
+            if !before.trim().is_empty() {
+                convert_line(&before, state, lines);
+            }
+            match is_panel {
+                true => {
+                    state.panel = !state.panel;
+                    if state.panel {
+                        if let Some(title) = panel_title(params) {
+                            lines.push(format!("> **{}**", convert_inline(&title)));
+                            lines.push(">".to_string());
+                        }
+                    } else {
+                        lines.push(String::new());
+                    }
+                }
+                false => {
+                    state.quote = !state.quote;
+                    if !state.quote {
+                        lines.push(String::new());
+                    }
+                }
+            }
+            line = after;
+            had_macro = true;
+        }
+    }
+    if had_macro && line.trim().is_empty() {
+        return;
+    }
+
+    let trimmed = line.trim();
+    let prefix = state.prefix();
+
+    if trimmed.is_empty() {
+        state.table_header = false;
+        lines.push(prefix.trim_end().to_string());
+        return;
+    }
+
+    // tables
+    if trimmed.starts_with("||") || trimmed.starts_with('|') {
+        let header = trimmed.starts_with("||");
+        let cells = split_cells(trimmed, header);
+        lines.push(format!(
+            "{}| {} |",
+            prefix,
+            cells
+                .iter()
+                .map(|cell| convert_inline(cell))
+                .collect::<Vec<String>>()
+                .join(" | ")
+        ));
+        if header && !state.table_header {
+            lines.push(format!(
+                "{}|{}",
+                prefix,
+                " --- |".repeat(cells.len().max(1))
+            ));
+        }
+        state.table_header = true;
+        return;
+    }
+    state.table_header = false;
+
+    // headings
+    if let Some(level) = heading_level(trimmed) {
+        lines.push(format!(
+            "{}{} {}",
+            prefix,
+            "#".repeat(level),
+            convert_inline(trimmed[3..].trim())
+        ));
+        return;
+    }
+
+    // block quote
+    if let Some(rest) = trimmed.strip_prefix("bq. ") {
+        lines.push(format!("> {}", convert_inline(rest.trim())));
+        return;
+    }
+
+    // horizontal rule
+    if trimmed == "----" {
+        lines.push(format!("{}---", prefix));
+        return;
+    }
+
+    // lists
+    if let Some((markers, rest)) = list_item(trimmed) {
+        let bullet = match markers.ends_with('#') {
+            true => "1.",
+            false => "-",
+        };
+        // nested items line up with the text of their parent ("- " or "1. ")
+        let indent: usize = markers[..markers.len() - 1]
+            .chars()
+            .map(|marker| if marker == '#' { 3 } else { 2 })
+            .sum();
+        lines.push(format!(
+            "{}{}{} {}",
+            prefix,
+            " ".repeat(indent),
+            bullet,
+            convert_inline(rest)
+        ));
+        return;
+    }
+
+    lines.push(format!("{}{}", prefix, convert_inline(trimmed)));
+}
+
+fn panel_title(params: &str) -> Option<String> {
+    params
+        .split('|')
+        .find_map(|param| match param.split_once('=') {
+            Some(("title", value)) if !value.trim().is_empty() => Some(value.trim().to_string()),
+            _ => None,
+        })
+}
+
+/// h1. to h6.
+fn heading_level(line: &str) -> Option<usize> {
+    let bytes = line.as_bytes();
+    if bytes.len() >= 4
+        && bytes[0] == b'h'
+        && (b'1'..=b'6').contains(&bytes[1])
+        && bytes[2] == b'.'
+        && bytes[3] == b' '
+    {
+        return Some((bytes[1] - b'0') as usize);
+    }
+    None
+}
+
+/// "* a", "** b", "# c", "*# d" and "- e", returns the markers and the item text
+fn list_item(line: &str) -> Option<(&str, &str)> {
+    let end = line
+        .find(|c: char| c != '*' && c != '#' && c != '-')
+        .unwrap_or(line.len());
+    if end == 0 || !line[end..].starts_with(' ') {
+        return None;
+    }
+    let markers = &line[..end];
+    // "-" only marks a single level list, "--" or "---" are text (dashes, rules)
+    if markers.contains('-') && markers != "-" {
+        return None;
+    }
+    Some((markers, line[end..].trim()))
+}
+
+/// splits a table row on | (or || for headers), ignoring pipes inside [links] and {{mono}}
+fn split_cells(line: &str, header: bool) -> Vec<String> {
+    let separator = if header { "||" } else { "|" };
+    let inner = line
+        .strip_prefix(separator)
+        .unwrap_or(line)
+        .trim_end()
+        .strip_suffix(separator)
+        .unwrap_or_else(|| line.strip_prefix(separator).unwrap_or(line));
+    let mut cells = vec![];
+    let mut current = String::new();
+    let mut depth = 0i32;
+    let chars: Vec<char> = inner.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '[' | '{' => depth += 1,
+            ']' | '}' => depth = (depth - 1).max(0),
+            '|' if depth == 0 => {
+                cells.push(current.trim().to_string());
+                current.clear();
+                // header rows may use || between cells
+                if header && chars.get(i + 1) == Some(&'|') {
+                    i += 1;
+                }
+                i += 1;
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+        i += 1;
+    }
+    cells.push(current.trim().to_string());
+    cells
+}
+
+/// converts inline markup: {{mono}}, [links], !images!, *bold*, _italic_, -strike-,
+/// +inserted+, ??citation??, {color} and escapes
+pub fn convert_inline(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        // monospace is copied verbatim
+        if c == '{'
+            && chars.get(i + 1) == Some(&'{')
+            && let Some(end) = find_seq(&chars, i + 2, &['}', '}'])
+        {
+            let mono: String = chars[i + 2..end].iter().collect();
+            out.push_str(&format!("`{}`", mono));
+            i = end + 2;
+            continue;
+        }
+        // {color:red}, {color} and {anchor} carry no markdown equivalent
+        if c == '{'
+            && let Some(end) = chars[i..].iter().position(|c| *c == '}')
+        {
+            let tag: String = chars[i + 1..i + end].iter().collect();
+            if tag == "color" || tag.starts_with("color:") || tag.starts_with("anchor") {
+                i += end + 1;
+                continue;
+            }
+        }
+        if c == '\\' && i + 1 < chars.len() {
+            let next = chars[i + 1];
+            if next == '\\' {
+                out.push_str("  \n");
+                i += 2;
+                continue;
+            }
+            if "*_-+?{}[]!^~|".contains(next) {
+                out.push('\\');
+                out.push(next);
+                i += 2;
+                continue;
+            }
+        }
+        if c == '['
+            && let Some(end) = chars[i..].iter().position(|c| *c == ']')
+        {
+            let inner: String = chars[i + 1..i + end].iter().collect();
+            if let Some(link) = convert_link(&inner) {
+                out.push_str(&link);
+                i += end + 1;
+                continue;
+            }
+        }
+        if c == '!'
+            && let Some(end) = chars[i + 1..].iter().position(|c| *c == '!')
+        {
+            let inner: String = chars[i + 1..i + 1 + end].iter().collect();
+            if is_image(&inner) {
+                let source = inner.split('|').next().unwrap_or("").trim();
+                let name = source.rsplit('/').next().unwrap_or(source);
+                out.push_str(&format!("![{}]({})", name, source));
+                i += end + 2;
+                continue;
+            }
+        }
+        if c == '?'
+            && chars.get(i + 1) == Some(&'?')
+            && let Some(end) = find_seq(&chars, i + 2, &['?', '?'])
+        {
+            let cite: String = chars[i + 2..end].iter().collect();
+            out.push_str(&format!("*{}*", convert_inline(&cite)));
+            i = end + 2;
+            continue;
+        }
+        if let Some(replacement) = emphasis_for(c)
+            && let Some(end) = find_emphasis_end(&chars, i, c)
+        {
+            let inner: String = chars[i + 1..end].iter().collect();
+            out.push_str(replacement);
+            out.push_str(&convert_inline(&inner));
+            out.push_str(replacement);
+            i = end + 1;
+            continue;
+        }
+        out.push(c);
+        i += 1;
+    }
+    out
+}
+
+fn emphasis_for(c: char) -> Option<&'static str> {
+    match c {
+        '*' => Some("**"),
+        '_' => Some("*"),
+        '-' => Some("~~"),
+        '+' => Some(""),
+        _ => None,
+    }
+}
+
+/// an emphasis opens after a boundary and before a non space, it closes after a
+/// non space and before a boundary (so snake_case and a-b-c stay untouched)
+fn find_emphasis_end(chars: &[char], start: usize, marker: char) -> Option<usize> {
+    let boundary = |c: Option<&char>| match c {
+        None => true,
+        Some(c) => c.is_whitespace() || (c.is_ascii_punctuation() && *c != marker),
+    };
+    let before = if start == 0 {
+        None
+    } else {
+        chars.get(start - 1)
+    };
+    if !boundary(before) {
+        return None;
+    }
+    match chars.get(start + 1) {
+        Some(c) if !c.is_whitespace() && *c != marker => {}
+        _ => return None,
+    }
+    (start + 2..chars.len()).find(|i| {
+        chars[*i] == marker && !chars[i - 1].is_whitespace() && boundary(chars.get(i + 1))
+    })
+}
+
+fn find_seq(chars: &[char], from: usize, seq: &[char]) -> Option<usize> {
+    (from..chars.len().saturating_sub(seq.len() - 1)).find(|i| chars[*i..].starts_with(seq))
+}
+
+/// [text|url], [url], [~user], [mailto:x], [#anchor] and [KEY-1], other brackets (arr[0],
+/// [WIP], [x]) are not links and stay as they are
+fn convert_link(inner: &str) -> Option<String> {
+    if inner.is_empty() || inner.starts_with(' ') {
+        return None;
+    }
+    if let Some(user) = inner.strip_prefix('~') {
+        return Some(format!("@{}", user));
+    }
+    match inner.split_once('|') {
+        Some((text, url)) => Some(format!("[{}]({})", convert_inline(text.trim()), url.trim())),
+        None if is_url(inner) => Some(format!("<{}>", inner.trim())),
+        None if inner.starts_with('#') => Some(inner[1..].to_string()),
+        None if is_issue_key(inner) => Some(inner.to_string()),
+        None => None,
+    }
+}
+
+/// PROJECT-123, the project key starts with an uppercase letter followed by uppercase letters,
+/// digits or underscores
+fn is_issue_key(value: &str) -> bool {
+    let Some((project, number)) = value.split_once('-') else {
+        return false;
+    };
+    project.starts_with(|c: char| c.is_ascii_uppercase())
+        && project
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+        && !number.is_empty()
+        && number.chars().all(|c| c.is_ascii_digit())
+}
+
+fn is_url(value: &str) -> bool {
+    ["http://", "https://", "ftp://", "mailto:", "file:"]
+        .iter()
+        .any(|scheme| value.starts_with(scheme))
+}
+
+fn is_image(inner: &str) -> bool {
+    let source = inner.split('|').next().unwrap_or("").trim().to_lowercase();
+    !source.is_empty()
+        && !source.contains(' ')
+        && [".png", ".jpg", ".jpeg", ".gif", ".svg", ".bmp", ".webp"]
+            .iter()
+            .any(|ext| source.ends_with(ext))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_converts(cases: &[(&str, &str)]) {
+        for (wiki, markdown) in cases {
+            assert_eq!(to_markdown(wiki), *markdown, "converting {:?}", wiki);
+        }
+    }
+
+    #[test]
+    fn headings() {
+        assert_converts(&[
+            ("h1. Title", "# Title"),
+            ("h3. *Bold* heading", "### **Bold** heading"),
+            ("h6. Last", "###### Last"),
+            ("h7. Not a heading", "h7. Not a heading"),
+        ]);
+    }
+
+    #[test]
+    fn code_blocks() {
+        assert_converts(&[
+            (
+                "{code:java}\nint a = 1;\n{code}",
+                "```java\nint a = 1;\n```",
+            ),
+            (
+                "{code:title=Run.sh|language=bash}\nmake *all*\n{code}",
+                "```bash\nmake *all*\n```",
+            ),
+            (
+                "before\n{noformat}\nkeep *this* [as|is]\n{noformat}\nafter",
+                "before\n\n```\nkeep *this* [as|is]\n```\n\nafter",
+            ),
+            // unterminated blocks run to the end of the text
+            ("{noformat}\nopen", "```\nopen\n```"),
+        ]);
+    }
+
+    #[test]
+    fn quotes() {
+        assert_converts(&[
+            ("{quote}\nquoted *text*\n{quote}", "> quoted **text**"),
+            ("{quote}one line{quote}", "> one line"),
+            ("bq. short quote", "> short quote"),
+        ]);
+    }
+
+    #[test]
+    fn panels() {
+        assert_converts(&[
+            (
+                "{panel:title=Note|borderStyle=dashed}\nInside\n{panel}",
+                "> **Note**\n>\n> Inside",
+            ),
+            ("{panel}\nNo title\n{panel}", "> No title"),
+        ]);
+    }
+
+    #[test]
+    fn tables() {
+        assert_converts(&[
+            (
+                "||Key||Summary||\n|WRIG-1|First|\n|WRIG-2|[Second|http://x.io/a]|",
+                "| Key | Summary |\n| --- | --- |\n| WRIG-1 | First |\n| WRIG-2 | [Second](http://x.io/a) |",
+            ),
+            ("|a|b|", "| a | b |"),
+        ]);
+    }
+
+    #[test]
+    fn links() {
+        assert_converts(&[
+            (
+                "see [the docs|https://example.com/docs]",
+                "see [the docs](https://example.com/docs)",
+            ),
+            ("[https://example.com]", "<https://example.com>"),
+            ("[mailto:dev@example.com]", "<mailto:dev@example.com>"),
+            ("ping [~jdoe]", "ping @jdoe"),
+            ("fixed in [WRIG-1]", "fixed in WRIG-1"),
+            ("[#anchor]", "anchor"),
+        ]);
+    }
+
+    #[test]
+    fn brackets_that_are_not_links() {
+        assert_converts(&[
+            ("read arr[0] first", "read arr[0] first"),
+            ("[WIP] signing", "[WIP] signing"),
+            ("[x] done", "[x] done"),
+            ("[wrig-1] and [WRIG-] stay", "[wrig-1] and [WRIG-] stay"),
+            ("blocked by [OPS_2-17]", "blocked by OPS_2-17"),
+        ]);
+    }
+
+    #[test]
+    fn emphasis() {
+        assert_converts(&[
+            ("*bold* and _italic_", "**bold** and *italic*"),
+            ("-gone- and +added+", "~~gone~~ and added"),
+            ("*bold _and italic_*", "**bold *and italic***"),
+            ("{{mono *text*}}", "`mono *text*`"),
+            ("??cited??", "*cited*"),
+            // markers inside words are left alone
+            ("snake_case_name and a-b-c", "snake_case_name and a-b-c"),
+            ("2 * 3 * 4", "2 * 3 * 4"),
+        ]);
+    }
+
+    #[test]
+    fn lists() {
+        assert_converts(&[
+            ("* one\n* two", "- one\n- two"),
+            (
+                "* one\n** nested\n*** deeper\n* two",
+                "- one\n  - nested\n    - deeper\n- two",
+            ),
+            (
+                "# first\n## sub\n# second",
+                "1. first\n   1. sub\n1. second",
+            ),
+            ("# step\n#* detail", "1. step\n   - detail"),
+            ("- dash item", "- dash item"),
+            ("---- ", "---"),
+        ]);
+    }
+
+    #[test]
+    fn colors() {
+        assert_converts(&[
+            ("{color:red}alert{color} raised", "alert raised"),
+            ("{color:#ff0000}*hot*{color}", "**hot**"),
+        ]);
+    }
+
+    #[test]
+    fn images() {
+        assert_converts(&[
+            ("!screen.png!", "![screen.png](screen.png)"),
+            (
+                "!http://x.io/img/chart.png|thumbnail!",
+                "![chart.png](http://x.io/img/chart.png)",
+            ),
+            // not an image, the exclamation marks stay
+            ("wow! really!", "wow! really!"),
+        ]);
+    }
+
+    #[test]
+    fn escapes_and_breaks() {
+        assert_converts(&[
+            ("not \\*bold\\*", "not \\*bold\\*"),
+            ("line\\\\next", "line  \nnext"),
+        ]);
+    }
+}
@@ -1,3 +1,19 @@
+pub mod adf;
+pub mod auth;
+pub mod browse;
+pub mod cache;
+pub mod client;
 pub mod common;
 pub mod document;
+pub mod html;
+pub mod markdown;
+pub mod markup;
+pub mod output;
+pub mod render;
+pub mod report;
+pub mod retry;
+pub mod server;
 pub mod service;
+pub mod spec;
+pub mod template;
+pub mod worker;
@@ -0,0 +1,154 @@
+// module output
+//
+// terminal output of the get and search commands
+//
+// text      key, summary and the main fields one per line, then the description
+// markdown  the same as a markdown document
+// json      the issue as returned by jira (custom fields included)
+
+use crate::handlers::browse::BrowseLinks;
+use crate::handlers::service::JiraResponse;
+use clap::ValueEnum;
+
+/// widest summary shown in the search table, longer ones are cut with ...
+const SUMMARY_WIDTH: usize = 60;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum IssueFormat {
+    Text,
+    #[value(alias = "md")]
+    Markdown,
+    Json,
+}
+
+pub fn render_issue(
+    jira: &JiraResponse,
+    format: IssueFormat,
+    links: &BrowseLinks,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let description = jira
+        .fields
+        .description
+        .as_ref()
+        .map(|description| description.to_markdown())
+        .unwrap_or_default();
+    let mut rows = issue_rows(jira);
+    rows.push(("Url", links.url(&jira.key)));
+    match format {
+        IssueFormat::Json => Ok(serde_json::to_string_pretty(jira)?),
+        IssueFormat::Text => {
+            let mut out = format!("{} {}\n\n", jira.key, jira.fields.summary);
+            for (name, value) in rows.iter() {
+                out.push_str(&format!("{:<12} {}\n", name, value));
+            }
+            if !description.is_empty() {
+                out.push_str(&format!("\n{}\n", description));
+            }
+            Ok(out.trim_end().to_string())
+        }
+        IssueFormat::Markdown => {
+            let mut out = format!("# {} {}\n\n", jira.key, jira.fields.summary);
+            for (name, value) in rows.iter() {
+                out.push_str(&format!("- **{}** : {}\n", name, value));
+            }
+            if !description.is_empty() {
+                out.push_str(&format!(
+                    "\n## Description\n\n{}\n",
+                    links.linkify(&description)
+                ));
+            }
+            Ok(out.trim_end().to_string())
+        }
+    }
+}
+
+/// fields without a value are left out
+fn issue_rows(jira: &JiraResponse) -> Vec<(&'static str, String)> {
+    let fields = &jira.fields;
+    let names = |values: Vec<&str>| values.join(", ");
+    let rows = vec![
+        ("Type", fields.issuetype.name.clone()),
+        ("Status", fields.status.name.clone()),
+        ("Priority", fields.priority.name.clone()),
+        (
+            "Assignee",
+            fields
+                .assignee
+                .as_ref()
+                .map(|user| user.display_name.clone())
+                .unwrap_or_default(),
+        ),
+        (
+            "Reporter",
+            fields
+                .reporter
+                .as_ref()
+                .map(|user| user.display_name.clone())
+                .unwrap_or_default(),
+        ),
+        (
+            "Labels",
+            names(fields.labels.iter().map(String::as_str).collect()),
+        ),
+        (
+            "Components",
+            names(fields.components.iter().map(|c| c.name.as_str()).collect()),
+        ),
+        (
+            "Fix versions",
+            names(
+                fields
+                    .fix_versions
+                    .iter()
+                    .map(|v| v.name.as_str())
+                    .collect(),
+            ),
+        ),
+        (
+            "Parent",
+            fields
+                .parent
+                .as_ref()
+                .map(|parent| parent.key.clone())
+                .unwrap_or_default(),
+        ),
+        ("Created", fields.created.clone()),
+        ("Updated", fields.updated.clone()),
+        ("Due", fields.duedate.clone().unwrap_or_default()),
+    ];
+    rows.into_iter()
+        .filter(|(_, value)| !value.is_empty())
+        .collect()
+}
+
+/// one row per issue, the summary column is last so long summaries do not break the layout
+pub fn search_table(issues: &[JiraResponse]) -> String {
+    let mut table = format!(
+        "{:<12} {:<10} {:<14} {:<20} {}\n",
+        "KEY", "TYPE", "STATUS", "ASSIGNEE", "SUMMARY"
+    );
+    for jira in issues.iter() {
+        let fields = &jira.fields;
+        table.push_str(&format!(
+            "{:<12} {:<10} {:<14} {:<20} {}\n",
+            jira.key,
+            fields.issuetype.name,
+            fields.status.name,
+            fields
+                .assignee
+                .as_ref()
+                .map(|user| user.display_name.as_str())
+                .unwrap_or("-"),
+            truncate(&fields.summary, SUMMARY_WIDTH)
+        ));
+    }
+    table.push_str(&format!("\n{} issues", issues.len()));
+    table
+}
+
+fn truncate(value: &str, width: usize) -> String {
+    match value.chars().count() > width {
+        true => format!("{}...", value.chars().take(width - 3).collect::<String>()),
+        false => value.to_string(),
+    }
+}
@@ -0,0 +1,494 @@
+use crate::config::load::Parameters;
+use crate::error::jira::JiraServiceError;
+use crate::handlers::html::HtmlRenderer;
+use crate::handlers::markdown::{Block, Span, parse_blocks};
+use crate::handlers::report::{EffortView, EpicView, OverviewRow, Report};
+use crate::handlers::template::Template;
+use clap::ValueEnum;
+use custom_logger as log;
+use serde_derive::{Deserialize, Serialize};
+use std::fs;
+
+/// built in markdown layout (used when template_path is not set)
+const DEFAULT_TEMPLATE: &str = include_str!("../../templates/report.md");
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    #[serde(alias = "md")]
+    #[value(alias = "md")]
+    Markdown,
+    Html,
+    Json,
+    Csv,
+    #[serde(alias = "adoc")]
+    #[value(alias = "adoc")]
+    Asciidoc,
+}
+
+/// renders the structured report model into a document
+pub trait ReportRenderer {
+    /// file extension of the rendered document
+    fn extension(&self) -> &'static str;
+    /// media type used when the document is served over http
+    fn content_type(&self) -> &'static str;
+    fn render(&self, report: &Report) -> Result<String, Box<dyn std::error::Error>>;
+}
+
+pub fn get_renderer(
+    format: ReportFormat,
+    params: &Parameters,
+) -> Result<Box<dyn ReportRenderer>, Box<dyn std::error::Error>> {
+    let renderer: Box<dyn ReportRenderer> = match format {
+        ReportFormat::Markdown => Box::new(MarkdownRenderer::new(params.template_path.as_deref())?),
+        ReportFormat::Html => Box::new(HtmlRenderer {}),
+        ReportFormat::Json => Box::new(JsonRenderer {}),
+        ReportFormat::Csv => Box::new(CsvRenderer {}),
+        ReportFormat::Asciidoc => Box::new(AsciiDocRenderer {}),
+    };
+    Ok(renderer)
+}
+
+/// markdown through the report template (the original layout by default)
+pub struct MarkdownRenderer {
+    template: Template,
+}
+
+impl MarkdownRenderer {
+    /// reads the report template from template_path, falling back to the built in layout
+    pub fn new(template_path: Option<&str>) -> Result<Self, Box<dyn std::error::Error>> {
+        let template = match template_path {
+            Some(path) => {
+                log::debug!("[markdown] using template {}", path);
+                let source = fs::read_to_string(path).map_err(|e| JiraServiceError::io(path, e))?;
+                Template::parse(&source).map_err(|e| JiraServiceError::config(path, e))?
+            }
+            None => Template::parse(DEFAULT_TEMPLATE)?,
+        };
+        Ok(MarkdownRenderer { template })
+    }
+}
+
+impl ReportRenderer for MarkdownRenderer {
+    fn extension(&self) -> &'static str {
+        "md"
+    }
+
+    fn content_type(&self) -> &'static str {
+        "text/markdown; charset=utf-8"
+    }
+
+    fn render(&self, report: &Report) -> Result<String, Box<dyn std::error::Error>> {
+        self.template.render(report)
+    }
+}
+
+/// the report model as pretty printed json (for downstream tooling)
+pub struct JsonRenderer {}
+
+impl ReportRenderer for JsonRenderer {
+    fn extension(&self) -> &'static str {
+        "json"
+    }
+
+    fn content_type(&self) -> &'static str {
+        "application/json"
+    }
+
+    fn render(&self, report: &Report) -> Result<String, Box<dyn std::error::Error>> {
+        let mut document = serde_json::to_string_pretty(report)?;
+        document.push('\n');
+        Ok(document)
+    }
+}
+
+/// one row per story, epics without stories get a single row with empty story columns
+pub struct CsvRenderer {}
+
+impl ReportRenderer for CsvRenderer {
+    fn extension(&self) -> &'static str {
+        "csv"
+    }
+
+    fn content_type(&self) -> &'static str {
+        "text/csv; charset=utf-8"
+    }
+
+    fn render(&self, report: &Report) -> Result<String, Box<dyn std::error::Error>> {
+        let mut document = String::from(
+            "epic_key,epic_summary,epic_status,story_key,story_link,story_summary,story_status,story_url,comments,estimate,remaining,logged,progress,over_estimate\n",
+        );
+        for epic in report.epics.iter() {
+            let epic_columns = [&epic.key, &epic.summary, &epic.status]
+                .iter()
+                .map(|value| csv_field(value))
+                .collect::<Vec<String>>()
+                .join(",");
+            if epic.stories.is_empty() {
+                document.push_str(&format!(
+                    "{},,,,,,0,{}\n",
+                    epic_columns,
+                    csv_effort(&epic.effort)
+                ));
+            }
+            for story in epic.stories.iter() {
+                document.push_str(&format!(
+                    "{},{},{},{},{},{},{},{}\n",
+                    epic_columns,
+                    csv_field(&story.key),
+                    csv_field(&story.link),
+                    csv_field(&story.summary),
+                    csv_field(&story.status),
+                    csv_field(&story.url),
+                    story.comments.len(),
+                    csv_effort(&story.effort)
+                ));
+                for subtask in story.subtasks.iter() {
+                    document.push_str(&format!(
+                        "{},{},{},{},{},{},,,,,,\n",
+                        epic_columns,
+                        csv_field(&subtask.key),
+                        csv_field(&subtask.link),
+                        csv_field(&subtask.summary),
+                        csv_field(&subtask.status),
+                        csv_field(&subtask.url)
+                    ));
+                }
+            }
+        }
+        Ok(document)
+    }
+}
+
+/// estimate, remaining, logged, progress and over_estimate columns, empty when not tracked
+fn csv_effort(effort: &EffortView) -> String {
+    match effort.tracked {
+        true => format!(
+            "{},{},{},{},{}",
+            effort.original, effort.remaining, effort.logged, effort.percent, effort.over_estimate
+        ),
+        false => ",,,,".to_string(),
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// asciidoc document mirroring the markdown layout
+pub struct AsciiDocRenderer {}
+
+impl ReportRenderer for AsciiDocRenderer {
+    fn extension(&self) -> &'static str {
+        "adoc"
+    }
+
+    fn content_type(&self) -> &'static str {
+        "text/asciidoc; charset=utf-8"
+    }
+
+    fn render(&self, report: &Report) -> Result<String, Box<dyn std::error::Error>> {
+        let mut doc = format!("= {}\n:revdate: {}\n:toc:\n\n", report.title, report.date);
+        doc.push_str("== Summary\n\n");
+        doc.push_str(&overview_asciidoc(report));
+        for epic in report.epics.iter() {
+            doc.push_str(&format!(
+                "== [EPIC] {}[{}] {}\n\n",
+                epic.url, epic.key, epic.summary
+            ));
+            doc.push_str(&format!("*Status* : {}\n\n", epic.status));
+            doc.push_str("=== Description\n\n");
+            doc.push_str(&blocks_to_asciidoc(&parse_blocks(&epic.description), 4));
+            doc.push_str(&format!(
+                "=== Changes {} - {}\n\n",
+                report.since, report.until
+            ));
+            if epic.changes.is_empty() {
+                doc.push_str("No status, assignee or resolution changes\n");
+            }
+            for change in epic.changes.iter() {
+                doc.push_str(&format!(
+                    "* {} {}[{}] {} : {} -> {} ({})\n",
+                    change.date,
+                    change.url,
+                    change.key,
+                    change.field,
+                    or_none(&change.from),
+                    or_none(&change.to),
+                    change.author
+                ));
+            }
+            doc.push_str("\n=== Effort\n\n");
+            doc.push_str(&effort_asciidoc(epic));
+            doc.push_str("\n=== Stories\n\n");
+            for story in epic.stories.iter() {
+                doc.push_str(&format!(
+                    "==== {}[{}] {}\n\n",
+                    story.url, story.key, story.summary
+                ));
+                doc.push_str(&format!(
+                    "*Status* : {} +\n*Link* : {}\n\n",
+                    story.status, story.link
+                ));
+                for subtask in story.subtasks.iter() {
+                    doc.push_str(&format!(
+                        "{} {}[{}] {} : {}\n",
+                        "*".repeat(subtask.depth),
+                        subtask.url,
+                        subtask.key,
+                        subtask.summary,
+                        subtask.status
+                    ));
+                }
+                if !story.subtasks.is_empty() {
+                    doc.push('\n');
+                }
+                if !story.in_progress {
+                    continue;
+                }
+                doc.push_str(".Description\n");
+                doc.push_str("====\n");
+                doc.push_str(&blocks_to_asciidoc(&parse_blocks(&story.description), 5));
+                doc.push_str("====\n\n");
+                for comment in story.comments.iter() {
+                    doc.push_str(&format!(".{} {}\n", comment.author, comment.created));
+                    doc.push_str("****\n");
+                    doc.push_str(&blocks_to_asciidoc(&parse_blocks(&comment.body), 5));
+                    doc.push_str("****\n\n");
+                }
+            }
+        }
+        Ok(doc)
+    }
+}
+
+fn overview_asciidoc(report: &Report) -> String {
+    let points = match report.story_points {
+        true => " |Points",
+        false => "",
+    };
+    let mut doc = format!(
+        "|===\n|Epic |To Do |In Progress |Done |Complete{} |Updated\n\n",
+        points
+    );
+    let row = |epic: String, row: &OverviewRow| {
+        let points = match report.story_points {
+            true => format!(" |{}", row.points),
+            false => String::new(),
+        };
+        format!(
+            "|{} |{} |{} |{} |{}%{} |{}\n",
+            epic, row.to_do, row.in_progress, row.done, row.percent, points, row.updated
+        )
+    };
+    for epic in report.overview.iter() {
+        doc.push_str(&row(
+            format!("{}[{}] {}", epic.url, epic.key, epic.summary),
+            epic,
+        ));
+    }
+    doc.push_str(&row(
+        format!("*Total* ({})", report.total.summary),
+        &report.total,
+    ));
+    doc.push_str("|===\n\n");
+    doc
+}
+
+fn effort_asciidoc(epic: &EpicView) -> String {
+    if !epic.effort.tracked {
+        return "No estimates or logged time\n".to_string();
+    }
+    let mut doc = String::from("|===\n|Issue |Estimate |Remaining |Logged |Progress\n\n");
+    let mut rows = vec![];
+    if epic.own_effort.tracked {
+        rows.push((format!("{}[{}]", epic.url, epic.key), &epic.own_effort));
+    }
+    for story in epic.stories.iter().filter(|story| story.effort.tracked) {
+        rows.push((format!("{}[{}]", story.url, story.key), &story.effort));
+    }
+    rows.push(("*Total*".to_string(), &epic.effort));
+    for (issue, effort) in rows {
+        doc.push_str(&format!(
+            "|{} |{} |{} |{} |`{}` {}%{}\n",
+            issue,
+            effort.original,
+            effort.remaining,
+            effort.logged,
+            effort.bar,
+            effort.percent,
+            match effort.over_estimate {
+                true => " *over estimate*",
+                false => "",
+            }
+        ));
+    }
+    doc.push_str("|===\n");
+    doc
+}
+
+/// status summary of an epic in jira wiki markup, posted as a comment on the epic
+pub fn epic_comment(report: &Report, epic: &EpicView) -> String {
+    let mut comment = format!("h3. {} ({})\n", wiki_escape(&report.title), report.date);
+    comment.push_str(&format!("*Status* : {}\n", epic.status));
+    if let Some(row) = report.overview.iter().find(|row| row.key == epic.key) {
+        comment.push_str(&format!(
+            "*Stories* : {} to do, {} in progress, {} done ({}% complete)\n",
+            row.to_do, row.in_progress, row.done, row.percent
+        ));
+        if report.story_points {
+            comment.push_str(&format!("*Points* : {}\n", row.points));
+        }
+    }
+    if epic.effort.tracked {
+        comment.push_str(&format!(
+            "*Effort* : {} logged of {} estimated, {} remaining ({}%){}\n",
+            epic.effort.logged,
+            epic.effort.original,
+            epic.effort.remaining,
+            epic.effort.percent,
+            match epic.effort.over_estimate {
+                true => " (!) over estimate",
+                false => "",
+            }
+        ));
+    }
+    comment.push('\n');
+    if !epic.stories.is_empty() {
+        comment.push_str("||Story||Link||Summary||Status||\n");
+        for story in epic.stories.iter() {
+            comment.push_str(&format!(
+                "|[{}]|{}|{}|{}|\n",
+                story.key,
+                wiki_escape(&story.link),
+                wiki_escape(&story.summary),
+                story.status
+            ));
+            for subtask in story.subtasks.iter() {
+                comment.push_str(&format!(
+                    "|{}[{}]|{}|{}|{}|\n",
+                    "- ".repeat(subtask.depth),
+                    subtask.key,
+                    wiki_escape(&subtask.link),
+                    wiki_escape(&subtask.summary),
+                    subtask.status
+                ));
+            }
+        }
+        comment.push('\n');
+    }
+    comment.push_str(&format!("*Changes {} - {}*\n", report.since, report.until));
+    if epic.changes.is_empty() {
+        comment.push_str("No status, assignee or resolution changes\n");
+    }
+    for change in epic.changes.iter() {
+        comment.push_str(&format!(
+            "* {} [{}] {} : {} -> {} ({})\n",
+            change.date,
+            change.key,
+            change.field,
+            wiki_escape(or_none(&change.from)),
+            wiki_escape(or_none(&change.to)),
+            change.author
+        ));
+    }
+    comment
+}
+
+/// escapes the characters that start wiki markup inside plain text
+fn wiki_escape(value: &str) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        if matches!(c, '|' | '[' | ']' | '{' | '}' | '*' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn or_none(value: &str) -> &str {
+    if value.is_empty() { "none" } else { value }
+}
+
+/// headings inside descriptions start at min_level so they nest under the report sections
+fn blocks_to_asciidoc(blocks: &[Block], min_level: usize) -> String {
+    let mut doc = String::new();
+    for block in blocks {
+        match block {
+            Block::Heading(level, spans) => {
+                // asciidoc allows section levels up to 5 (======)
+                let level = (level + min_level - 1).min(6);
+                doc.push_str(&format!(
+                    "{} {}\n\n",
+                    "=".repeat(level),
+                    spans_to_asciidoc(spans)
+                ));
+            }
+            Block::Paragraph(lines) => {
+                let lines = lines
+                    .iter()
+                    .map(|line| spans_to_asciidoc(line))
+                    .collect::<Vec<String>>();
+                doc.push_str(&format!("{}\n\n", lines.join(" +\n")));
+            }
+            Block::List(items) => {
+                for item in items {
+                    let marker = if item.ordered { "." } else { "*" };
+                    doc.push_str(&format!(
+                        "{} {}\n",
+                        marker.repeat(item.depth),
+                        spans_to_asciidoc(&item.spans)
+                    ));
+                }
+                doc.push('\n');
+            }
+            Block::Code { language, body } => {
+                match language.is_empty() {
+                    true => doc.push_str("[source]\n"),
+                    false => doc.push_str(&format!("[source,{}]\n", language)),
+                }
+                doc.push_str(&format!("----\n{}\n----\n\n", body));
+            }
+            Block::Quote(inner) => {
+                doc.push_str("____\n");
+                doc.push_str(blocks_to_asciidoc(inner, min_level).trim_end());
+                doc.push_str("\n____\n\n");
+            }
+            Block::Table { header, rows } => {
+                match header {
+                    Some(_) => doc.push_str("[%header]\n|===\n"),
+                    None => doc.push_str("|===\n"),
+                }
+                for row in header.iter().chain(rows.iter()) {
+                    for cell in row {
+                        doc.push_str(&format!("| {} ", spans_to_asciidoc(cell)));
+                    }
+                    doc.push('\n');
+                }
+                doc.push_str("|===\n\n");
+            }
+            Block::Rule => doc.push_str("'''\n\n"),
+        }
+    }
+    doc
+}
+
+fn spans_to_asciidoc(spans: &[Span]) -> String {
+    spans
+        .iter()
+        .map(|span| match span {
+            Span::Text(text) => text.clone(),
+            Span::Code(code) => format!("`+{}+`", code),
+            Span::Strong(inner) => format!("**{}**", spans_to_asciidoc(inner)),
+            Span::Emphasis(inner) => format!("__{}__", spans_to_asciidoc(inner)),
+            Span::Strike(inner) => format!("[line-through]#{}#", spans_to_asciidoc(inner)),
+            Span::Link { text, url } => format!("{}[{}]", url, spans_to_asciidoc(text)),
+            Span::Image { alt, url } => format!("image:{}[{}]", url, alt),
+        })
+        .collect()
+}
@@ -0,0 +1,1049 @@
+use crate::config::load::{CommentOptions, TimeTrackingOptions};
+use crate::handlers::browse::BrowseLinks;
+use crate::handlers::common::{format_duration, parse_date_arg, parse_jira_date};
+use crate::handlers::service::{CommentData, JiraResponse};
+use chrono::{DateTime, Duration, Utc};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// changelog fields listed in the "what changed" sections
+const TRACKED_FIELDS: [&str; 3] = ["status", "assignee", "resolution"];
+
+/// days covered by the reporting window when --since is not set
+const DEFAULT_WINDOW_DAYS: i64 = 14;
+
+/// working day and week used for durations when time_tracking does not set them
+const DEFAULT_HOURS_PER_DAY: u32 = 8;
+const DEFAULT_DAYS_PER_WEEK: u32 = 5;
+
+/// characters in the textual progress bar
+const PROGRESS_BAR_WIDTH: usize = 20;
+
+/// reporting window for changelog entries
+#[derive(Clone, Debug)]
+pub struct ReportWindow {
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+}
+
+impl ReportWindow {
+    /// until defaults to now and since to 14 days before until
+    pub fn new(since: Option<DateTime<Utc>>, until: Option<DateTime<Utc>>) -> Self {
+        let until = until.unwrap_or(Utc::now());
+        let since = since.unwrap_or(until - Duration::days(DEFAULT_WINDOW_DAYS));
+        ReportWindow { since, until }
+    }
+
+    /// window from YYYY-MM-DD (or rfc3339) arguments, until includes the whole day
+    pub fn parse(
+        since: Option<&str>,
+        until: Option<&str>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let since = since
+            .map(|value| parse_date_arg(value, false))
+            .transpose()?;
+        let until = until.map(|value| parse_date_arg(value, true)).transpose()?;
+        let window = ReportWindow::new(since, until);
+        if window.since > window.until {
+            return Err(Box::from("since must be before until"));
+        }
+        Ok(window)
+    }
+
+    pub fn contains(&self, dt: &DateTime<Utc>) -> bool {
+        *dt >= self.since && *dt <= self.until
+    }
+}
+
+/// settings used while building the view model
+#[derive(Clone, Debug)]
+pub struct ReportContext {
+    pub window: ReportWindow,
+    pub comments: CommentOptions,
+    /// issue urls and issue keys linked in description and comment text
+    pub links: BrowseLinks,
+    pub time_tracking: TimeTrackingOptions,
+    /// custom field holding story points (customfield_10016), None leaves points out
+    pub story_points_field: Option<String>,
+}
+
+/// story (or subtask) with the link from its epic (or parent) and its subtasks
+#[derive(Clone, Debug, Default)]
+pub struct StoryNode {
+    pub link: String,
+    pub jira: JiraResponse,
+    pub subtasks: Vec<StoryNode>,
+}
+
+impl StoryNode {
+    /// keys of the node and all its subtasks
+    pub fn keys<'a>(&'a self, keys: &mut Vec<&'a str>) {
+        keys.push(&self.jira.key);
+        for subtask in self.subtasks.iter() {
+            subtask.keys(keys);
+        }
+    }
+}
+
+/// typed view model handed to the report templates
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Report {
+    pub title: String,
+    pub date: String,
+    /// reporting window (YYYY-MM-DD)
+    pub since: String,
+    pub until: String,
+    /// summary table, one row per epic
+    pub overview: Vec<OverviewRow>,
+    /// all epics together
+    pub total: OverviewRow,
+    /// story points are counted (story_points_field is set)
+    pub story_points: bool,
+    pub epics: Vec<EpicView>,
+}
+
+/// story counts by status category, completion and points of an epic
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct OverviewRow {
+    pub key: String,
+    pub url: String,
+    pub summary: String,
+    /// direct stories of the epic, subtasks are not counted
+    pub stories: usize,
+    pub to_do: usize,
+    pub in_progress: usize,
+    pub done: usize,
+    /// done stories out of all stories
+    pub percent: u32,
+    /// story points of the done stories and of all stories (8 of 13)
+    pub points: String,
+    pub points_done: f64,
+    pub points_total: f64,
+    /// latest update of the epic, its stories or their subtasks (YYYY-MM-DD)
+    pub updated: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct EpicView {
+    pub key: String,
+    pub url: String,
+    pub summary: String,
+    pub status: String,
+    /// description converted to markdown, issue keys are links
+    pub description: String,
+    pub stories: Vec<StoryView>,
+    /// status, assignee and resolution changes of the epic and its stories in the window
+    pub changes: Vec<ChangeView>,
+    /// time tracked on the epic itself
+    pub own_effort: EffortView,
+    /// the epic, its stories and their subtasks
+    pub effort: EffortView,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StoryView {
+    pub key: String,
+    pub url: String,
+    /// link from the epic to the story (relates to, is parent of)
+    pub link: String,
+    pub summary: String,
+    pub status: String,
+    pub in_progress: bool,
+    /// description converted to markdown, issue keys are links
+    pub description: String,
+    pub comments: Vec<CommentView>,
+    /// subtasks in depth first order, nesting is given by depth
+    pub subtasks: Vec<SubtaskView>,
+    /// the story and its subtasks
+    pub effort: EffortView,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SubtaskView {
+    pub key: String,
+    pub url: String,
+    /// subtask of PARENT
+    pub link: String,
+    pub summary: String,
+    pub status: String,
+    /// 1 for subtasks of the story, 2 for their subtasks ...
+    pub depth: usize,
+    /// one tab per level, nests subtasks under the list items of their story
+    pub indent: String,
+}
+
+/// original estimate, remaining estimate and time logged, summed over an issue tree
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct EffortView {
+    /// false when no issue in the tree has an estimate or logged time
+    pub tracked: bool,
+    /// durations in working time (3d 4h)
+    pub original: String,
+    pub remaining: String,
+    pub logged: String,
+    pub original_seconds: i64,
+    pub remaining_seconds: i64,
+    pub logged_seconds: i64,
+    /// logged / (logged + remaining), the progress jira shows
+    pub percent: u32,
+    /// [#####---------------]
+    pub bar: String,
+    /// more time logged than originally estimated
+    pub over_estimate: bool,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ChangeView {
+    /// YYYY-MM-DD HH:MM (utc)
+    pub date: String,
+    pub key: String,
+    pub url: String,
+    pub field: String,
+    pub from: String,
+    pub to: String,
+    pub author: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CommentView {
+    pub author: String,
+    pub created: String,
+    /// body converted to markdown, issue keys are links
+    pub body: String,
+}
+
+impl Report {
+    pub fn new(
+        title: String,
+        date: String,
+        ctx: &ReportContext,
+        epics: Vec<(JiraResponse, Vec<StoryNode>)>,
+    ) -> Self {
+        let overview = epics
+            .iter()
+            .map(|(epic, stories)| OverviewRow::new(epic, stories, ctx))
+            .collect::<Vec<OverviewRow>>();
+        Report {
+            title,
+            date,
+            since: ctx.window.since.format("%Y-%m-%d").to_string(),
+            until: ctx.window.until.format("%Y-%m-%d").to_string(),
+            total: OverviewRow::total(&overview, ctx.story_points_field.is_some()),
+            overview,
+            story_points: ctx.story_points_field.is_some(),
+            epics: epics
+                .into_iter()
+                .map(|(epic, stories)| EpicView::new(epic, stories, ctx))
+                .collect(),
+        }
+    }
+}
+
+impl OverviewRow {
+    pub fn new(epic: &JiraResponse, stories: &[StoryNode], ctx: &ReportContext) -> Self {
+        let mut row = OverviewRow {
+            key: epic.key.clone(),
+            url: ctx.links.url(&epic.key),
+            summary: epic.fields.summary.trim().to_string(),
+            stories: stories.len(),
+            ..Default::default()
+        };
+        let mut updated = parse_jira_date(&epic.fields.updated);
+        for story in stories.iter() {
+            let done = match story.jira.fields.status.status_category.key.as_str() {
+                "done" => {
+                    row.done += 1;
+                    true
+                }
+                "indeterminate" => {
+                    row.in_progress += 1;
+                    false
+                }
+                _ => {
+                    row.to_do += 1;
+                    false
+                }
+            };
+            if let Some(field) = &ctx.story_points_field {
+                let points = story_points(&story.jira, field);
+                row.points_total += points;
+                if done {
+                    row.points_done += points;
+                }
+            }
+            updated = updated.max(latest_update(story));
+        }
+        row.updated = updated
+            .map(|dt| dt.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        row.finish(ctx.story_points_field.is_some())
+    }
+
+    /// sum of the epic rows
+    pub fn total(rows: &[OverviewRow], points: bool) -> Self {
+        let total = OverviewRow {
+            key: "Total".to_string(),
+            summary: format!("{} epics", rows.len()),
+            stories: rows.iter().map(|row| row.stories).sum(),
+            to_do: rows.iter().map(|row| row.to_do).sum(),
+            in_progress: rows.iter().map(|row| row.in_progress).sum(),
+            done: rows.iter().map(|row| row.done).sum(),
+            points_done: rows.iter().map(|row| row.points_done).sum(),
+            points_total: rows.iter().map(|row| row.points_total).sum(),
+            // YYYY-MM-DD sorts by date
+            updated: rows
+                .iter()
+                .map(|row| row.updated.clone())
+                .max()
+                .unwrap_or_default(),
+            ..Default::default()
+        };
+        total.finish(points)
+    }
+
+    fn finish(mut self, points: bool) -> Self {
+        self.percent = (self.done * 100)
+            .checked_div(self.stories)
+            .unwrap_or_default() as u32;
+        if points {
+            self.points = format!(
+                "{} of {}",
+                format_points(self.points_done),
+                format_points(self.points_total)
+            );
+        }
+        self
+    }
+}
+
+impl EpicView {
+    pub fn new(jira: JiraResponse, stories: Vec<StoryNode>, ctx: &ReportContext) -> Self {
+        let mut changes = collect_changes(&jira, ctx);
+        for story in stories.iter() {
+            collect_node_changes(story, ctx, &mut changes);
+        }
+        changes.sort_by_key(|(created, _)| *created);
+        let own = Effort::of(&jira);
+        let total = stories
+            .iter()
+            .fold(own, |total, story| total.add(Effort::of_node(story)));
+        EpicView {
+            own_effort: EffortView::new(own, ctx),
+            effort: EffortView::new(total, ctx),
+            url: ctx.links.url(&jira.key),
+            summary: jira.fields.summary.trim().to_string(),
+            status: jira.fields.status.status_category.name.trim().to_string(),
+            description: description(&jira, ctx),
+            stories: stories
+                .into_iter()
+                .map(|story| StoryView::new(story, ctx))
+                .collect(),
+            changes: changes.into_iter().map(|(_, change)| change).collect(),
+            key: jira.key,
+        }
+    }
+}
+
+impl StoryView {
+    pub fn new(node: StoryNode, ctx: &ReportContext) -> Self {
+        let mut subtasks = vec![];
+        for subtask in node.subtasks.iter() {
+            flatten_subtasks(subtask, 1, ctx, &mut subtasks);
+        }
+        let effort = EffortView::new(Effort::of_node(&node), ctx);
+        let jira = node.jira;
+        let status = jira.fields.status.status_category.name.trim().to_string();
+        StoryView {
+            effort,
+            url: ctx.links.url(&jira.key),
+            link: node.link,
+            subtasks,
+            summary: jira.fields.summary.trim().to_string(),
+            in_progress: status.contains("In Progress"),
+            status,
+            description: description(&jira, ctx),
+            comments: filter_comments(jira.fields.comment.comments, ctx)
+                .into_iter()
+                .map(|comment| CommentView {
+                    author: comment.author.name_or_display(),
+                    created: comment.created,
+                    body: ctx.links.linkify(&comment.body.to_markdown()),
+                })
+                .collect(),
+            key: jira.key,
+        }
+    }
+}
+
+/// numeric (or numeric string) value of the story points field, 0 when not set
+fn story_points(jira: &JiraResponse, field: &str) -> f64 {
+    match jira.fields.custom_fields.get(field) {
+        Some(Value::Number(points)) => points.as_f64().unwrap_or_default(),
+        Some(Value::String(points)) => points.trim().parse().unwrap_or_default(),
+        _ => 0.0,
+    }
+}
+
+/// 5 or 2.5
+fn format_points(points: f64) -> String {
+    match points.fract() == 0.0 {
+        true => format!("{}", points as i64),
+        false => format!("{:.1}", points),
+    }
+}
+
+/// latest fields.updated of the node and its subtasks
+fn latest_update(node: &StoryNode) -> Option<DateTime<Utc>> {
+    node.subtasks
+        .iter()
+        .map(latest_update)
+        .fold(parse_jira_date(&node.jira.fields.updated), Option::max)
+}
+
+/// seconds of a single issue or summed over a tree
+#[derive(Clone, Copy, Debug, Default)]
+struct Effort {
+    tracked: bool,
+    original: i64,
+    remaining: i64,
+    logged: i64,
+}
+
+impl Effort {
+    /// own time of the issue (not the aggregate, subtasks are summed from the tree), the
+    /// timetracking block is the fallback when the plain fields are not returned
+    fn of(jira: &JiraResponse) -> Self {
+        let fields = &jira.fields;
+        let tracking = &fields.timetracking;
+        let original = fields
+            .timeoriginalestimate
+            .or(tracking.original_estimate_seconds);
+        let remaining = fields.timeestimate.or(tracking.remaining_estimate_seconds);
+        let logged = fields.timespent.or(tracking.time_spent_seconds);
+        Effort {
+            tracked: original.is_some() || remaining.is_some() || logged.is_some(),
+            original: original.unwrap_or_default(),
+            remaining: remaining.unwrap_or_default(),
+            logged: logged.unwrap_or_default(),
+        }
+    }
+
+    fn of_node(node: &StoryNode) -> Self {
+        node.subtasks
+            .iter()
+            .fold(Effort::of(&node.jira), |total, subtask| {
+                total.add(Effort::of_node(subtask))
+            })
+    }
+
+    fn add(self, other: Effort) -> Self {
+        Effort {
+            tracked: self.tracked || other.tracked,
+            original: self.original + other.original,
+            remaining: self.remaining + other.remaining,
+            logged: self.logged + other.logged,
+        }
+    }
+}
+
+impl EffortView {
+    fn new(effort: Effort, ctx: &ReportContext) -> Self {
+        let options = &ctx.time_tracking;
+        let hours_per_day = options.hours_per_day.unwrap_or(DEFAULT_HOURS_PER_DAY) as i64;
+        let days_per_week = options.days_per_week.unwrap_or(DEFAULT_DAYS_PER_WEEK) as i64;
+        let duration = |seconds| format_duration(seconds, hours_per_day, days_per_week);
+        let total = effort.logged + effort.remaining;
+        let percent = match total > 0 {
+            true => (effort.logged * 100 / total) as u32,
+            false => 0,
+        };
+        let filled = percent as usize * PROGRESS_BAR_WIDTH / 100;
+        EffortView {
+            tracked: effort.tracked,
+            original: duration(effort.original),
+            remaining: duration(effort.remaining),
+            logged: duration(effort.logged),
+            original_seconds: effort.original,
+            remaining_seconds: effort.remaining,
+            logged_seconds: effort.logged,
+            percent,
+            bar: format!(
+                "[{}{}]",
+                "#".repeat(filled),
+                "-".repeat(PROGRESS_BAR_WIDTH - filled)
+            ),
+            over_estimate: effort.original > 0 && effort.logged > effort.original,
+        }
+    }
+}
+
+/// description converted to markdown with its issue keys linked
+fn description(jira: &JiraResponse, ctx: &ReportContext) -> String {
+    jira.fields
+        .description
+        .as_ref()
+        .map(|description| ctx.links.linkify(&description.to_markdown()))
+        .unwrap_or_default()
+}
+
+fn flatten_subtasks(
+    node: &StoryNode,
+    depth: usize,
+    ctx: &ReportContext,
+    subtasks: &mut Vec<SubtaskView>,
+) {
+    subtasks.push(SubtaskView {
+        key: node.jira.key.clone(),
+        url: ctx.links.url(&node.jira.key),
+        link: node.link.clone(),
+        summary: node.jira.fields.summary.trim().to_string(),
+        status: node
+            .jira
+            .fields
+            .status
+            .status_category
+            .name
+            .trim()
+            .to_string(),
+        depth,
+        indent: "\t".repeat(depth),
+    });
+    for subtask in node.subtasks.iter() {
+        flatten_subtasks(subtask, depth + 1, ctx, subtasks);
+    }
+}
+
+/// changes of the story and all its subtasks
+fn collect_node_changes(
+    node: &StoryNode,
+    ctx: &ReportContext,
+    changes: &mut Vec<(DateTime<Utc>, ChangeView)>,
+) {
+    changes.extend(collect_changes(&node.jira, ctx));
+    for subtask in node.subtasks.iter() {
+        collect_node_changes(subtask, ctx, changes);
+    }
+}
+
+/// applies the window, excluded authors, limit and ordering from the comment options
+fn filter_comments(comments: Vec<CommentData>, ctx: &ReportContext) -> Vec<CommentData> {
+    let options = &ctx.comments;
+    let mut comments = comments
+        .into_iter()
+        .filter(|comment| {
+            !options.exclude_authors.iter().any(|author| {
+                author.eq_ignore_ascii_case(&comment.author.name)
+                    || author.eq_ignore_ascii_case(&comment.author.key)
+                    || author.eq_ignore_ascii_case(&comment.author.display_name)
+                    || comment
+                        .author
+                        .account_id
+                        .as_deref()
+                        .is_some_and(|id| author.eq_ignore_ascii_case(id))
+            })
+        })
+        .filter(|comment| {
+            // an edit inside the window brings an older comment back into the report
+            !options.in_window
+                || [&comment.created, &comment.updated]
+                    .iter()
+                    .filter_map(|value| parse_jira_date(value))
+                    .any(|dt| ctx.window.contains(&dt))
+        })
+        .collect::<Vec<CommentData>>();
+    comments.sort_by_key(|comment| parse_jira_date(&comment.created));
+    if let Some(limit) = options.limit {
+        let skip = comments.len().saturating_sub(limit);
+        comments.drain(..skip);
+    }
+    if options.newest_first {
+        comments.reverse();
+    }
+    comments
+}
+
+/// tracked changelog items inside the window, with their timestamp for sorting
+fn collect_changes(jira: &JiraResponse, ctx: &ReportContext) -> Vec<(DateTime<Utc>, ChangeView)> {
+    let window = &ctx.window;
+    let mut changes = vec![];
+    let Some(changelog) = &jira.changelog else {
+        return changes;
+    };
+    for history in changelog.histories.iter() {
+        let Some(created) = parse_jira_date(&history.created) else {
+            continue;
+        };
+        if !window.contains(&created) {
+            continue;
+        }
+        let author = match &history.author {
+            Some(author) if !author.display_name.is_empty() => author.display_name.clone(),
+            Some(author) => author.name.clone(),
+            None => String::new(),
+        };
+        for item in history.items.iter() {
+            if !TRACKED_FIELDS.contains(&item.field.to_lowercase().as_str()) {
+                continue;
+            }
+            changes.push((
+                created,
+                ChangeView {
+                    date: created.format("%Y-%m-%d %H:%M").to_string(),
+                    key: jira.key.clone(),
+                    url: ctx.links.url(&jira.key),
+                    field: item.field.to_lowercase(),
+                    from: item.from_value.clone().unwrap_or_default(),
+                    to: item.to_value.clone().unwrap_or_default(),
+                    author: author.clone(),
+                },
+            ));
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::service::{Changelog, User};
+
+    /// window of the first two weeks of may 2024, links to jira.example.com
+    fn ctx() -> ReportContext {
+        ReportContext {
+            window: ReportWindow::parse(Some("2024-05-01"), Some("2024-05-14")).unwrap(),
+            comments: CommentOptions::default(),
+            links: BrowseLinks::new("https://jira.example.com/rest/api/2/issue/", None),
+            time_tracking: TimeTrackingOptions::default(),
+            story_points_field: None,
+        }
+    }
+
+    /// issue in the given status category (new, indeterminate, done)
+    fn issue(key: &str, category: &str) -> JiraResponse {
+        let mut jira = JiraResponse {
+            key: key.to_string(),
+            ..Default::default()
+        };
+        jira.fields.status.status_category.key = category.to_string();
+        jira
+    }
+
+    fn node(jira: JiraResponse, subtasks: Vec<StoryNode>) -> StoryNode {
+        StoryNode {
+            link: "relates to".to_string(),
+            jira,
+            subtasks,
+        }
+    }
+
+    const HOUR: i64 = 3600;
+
+    fn comment(id: &str, author: &str, created: &str, updated: &str) -> CommentData {
+        CommentData {
+            id: id.to_string(),
+            author: User {
+                name: author.to_string(),
+                key: format!("key-{}", author),
+                display_name: format!("{} (display)", author),
+                ..Default::default()
+            },
+            created: format!("{}T10:00:00.000+0000", created),
+            updated: format!("{}T10:00:00.000+0000", updated),
+            ..Default::default()
+        }
+    }
+
+    fn comment_ids(comments: Vec<CommentData>, ctx: &ReportContext) -> Vec<String> {
+        filter_comments(comments, ctx)
+            .into_iter()
+            .map(|comment| comment.id)
+            .collect()
+    }
+
+    /// out of the window, edited in the window, in the window, after the window (unordered)
+    fn window_comments() -> Vec<CommentData> {
+        vec![
+            comment("in", "alice", "2024-05-03", "2024-05-03"),
+            comment("old", "alice", "2024-04-20", "2024-04-20"),
+            comment("later", "alice", "2024-05-20", "2024-05-20"),
+            comment("edited", "alice", "2024-04-21", "2024-05-02"),
+        ]
+    }
+
+    #[test]
+    fn durations() {
+        let duration = |seconds| format_duration(seconds, 8, 5);
+        assert_eq!(duration(0), "0m");
+        assert_eq!(duration(-HOUR), "0m");
+        assert_eq!(duration(59), "0m");
+        assert_eq!(duration(28 * HOUR), "3d 4h");
+        assert_eq!(duration((40 + 16 + 4) * HOUR + 30 * 60), "1w 2d 4h 30m");
+        // 6 hour days and 4 day weeks
+        assert_eq!(format_duration(28 * HOUR, 6, 4), "1w 4h");
+        assert_eq!(format_duration(6 * HOUR, 6, 4), "1d");
+    }
+
+    #[test]
+    fn effort_sums_the_epic_tree() {
+        let mut epic = issue("WRIG-1", "indeterminate");
+        epic.fields.timeoriginalestimate = Some(HOUR);
+        let mut story = issue("WRIG-2", "indeterminate");
+        story.fields.timeoriginalestimate = Some(4 * HOUR);
+        story.fields.timespent = Some(2 * HOUR);
+        story.fields.timeestimate = Some(HOUR);
+        // the plain fields win over the timetracking block
+        story.fields.timetracking.time_spent_seconds = Some(99 * HOUR);
+        // only the timetracking block is returned for the subtask
+        let mut subtask = issue("WRIG-3", "new");
+        subtask.fields.timetracking.original_estimate_seconds = Some(HOUR);
+        subtask.fields.timetracking.time_spent_seconds = Some(HOUR / 2);
+        subtask.fields.timetracking.remaining_estimate_seconds = Some(HOUR / 2);
+        let stories = vec![node(story, vec![node(subtask, vec![])])];
+
+        let view = EpicView::new(epic, stories, &ctx());
+        let effort = &view.effort;
+        assert!(effort.tracked);
+        assert_eq!(
+            (
+                effort.original_seconds,
+                effort.logged_seconds,
+                effort.remaining_seconds
+            ),
+            (6 * HOUR, 5 * HOUR / 2, 3 * HOUR / 2)
+        );
+        assert_eq!(
+            (
+                effort.original.as_str(),
+                effort.logged.as_str(),
+                effort.remaining.as_str()
+            ),
+            ("6h", "2h 30m", "1h 30m")
+        );
+        // 2h 30m of 4h, rounded down
+        assert_eq!(effort.percent, 62);
+        assert_eq!(effort.bar, "[############--------]");
+        assert!(!effort.over_estimate);
+
+        assert!(view.own_effort.tracked);
+        assert_eq!(view.own_effort.original, "1h");
+        assert_eq!(view.own_effort.logged, "0m");
+        assert_eq!(view.own_effort.bar, format!("[{}]", "-".repeat(20)));
+        let story = &view.stories[0].effort;
+        assert_eq!(
+            (story.original_seconds, story.logged_seconds),
+            (5 * HOUR, 5 * HOUR / 2)
+        );
+    }
+
+    #[test]
+    fn effort_without_time_tracking() {
+        let effort = EffortView::new(Effort::of(&issue("WRIG-1", "new")), &ctx());
+        assert!(!effort.tracked);
+        assert_eq!(effort.logged, "0m");
+        assert_eq!(effort.percent, 0);
+        assert!(!effort.over_estimate);
+    }
+
+    #[test]
+    fn effort_over_estimate() {
+        let effort = |original, logged, remaining| {
+            EffortView::new(
+                Effort {
+                    tracked: true,
+                    original,
+                    remaining,
+                    logged,
+                },
+                &ctx(),
+            )
+        };
+        let over = effort(HOUR, 2 * HOUR, 0);
+        assert!(over.over_estimate);
+        assert_eq!(over.percent, 100);
+        assert_eq!(over.bar, format!("[{}]", "#".repeat(20)));
+        // nothing estimated is not over the estimate
+        assert!(!effort(0, HOUR, 0).over_estimate);
+        assert!(!effort(2 * HOUR, 2 * HOUR, HOUR).over_estimate);
+        assert_eq!(effort(0, HOUR, 3 * HOUR).bar, "[#####---------------]");
+    }
+
+    #[test]
+    fn effort_in_configured_working_time() {
+        let mut ctx = ctx();
+        ctx.time_tracking.hours_per_day = Some(6);
+        ctx.time_tracking.days_per_week = Some(4);
+        let mut story = issue("WRIG-2", "new");
+        story.fields.timeoriginalestimate = Some(28 * HOUR);
+        story.fields.timespent = Some(6 * HOUR);
+        let effort = EffortView::new(Effort::of(&story), &ctx);
+        assert_eq!(
+            (effort.original.as_str(), effort.logged.as_str()),
+            ("1w 4h", "1d")
+        );
+    }
+
+    #[test]
+    fn comments_in_the_window() {
+        let mut ctx = ctx();
+        // an old comment edited inside the window comes back
+        assert_eq!(comment_ids(window_comments(), &ctx), ["edited", "in"]);
+        ctx.comments.in_window = false;
+        assert_eq!(
+            comment_ids(window_comments(), &ctx),
+            ["old", "edited", "in", "later"]
+        );
+    }
+
+    #[test]
+    fn comment_limit_and_order() {
+        let mut ctx = ctx();
+        ctx.comments.in_window = false;
+        ctx.comments.limit = Some(2);
+        // the newest two, not the first two given
+        assert_eq!(comment_ids(window_comments(), &ctx), ["in", "later"]);
+        // the order is reversed after the limit is applied
+        ctx.comments.newest_first = true;
+        assert_eq!(comment_ids(window_comments(), &ctx), ["later", "in"]);
+        ctx.comments.limit = Some(10);
+        assert_eq!(
+            comment_ids(window_comments(), &ctx),
+            ["later", "in", "edited", "old"]
+        );
+    }
+
+    #[test]
+    fn comments_of_excluded_authors() {
+        let mut cloud = comment("cloud", "", "2024-05-06", "2024-05-06");
+        cloud.author.key = String::new();
+        cloud.author.account_id = Some("5b10ac8d82e05b22cc7d4ef5".to_string());
+        let comments = vec![
+            comment("name", "jenkins", "2024-05-02", "2024-05-02"),
+            comment("key", "robot", "2024-05-03", "2024-05-03"),
+            comment("display", "automation", "2024-05-04", "2024-05-04"),
+            comment("human", "alice", "2024-05-05", "2024-05-05"),
+            cloud,
+        ];
+        let mut ctx = ctx();
+        ctx.comments.exclude_authors = [
+            "JENKINS",
+            "Key-Robot",
+            "AUTOMATION (Display)",
+            "5B10AC8D82E05B22CC7D4EF5",
+        ]
+        .iter()
+        .map(|author| author.to_string())
+        .collect();
+        assert_eq!(comment_ids(comments, &ctx), ["human"]);
+    }
+
+    fn updated(mut jira: JiraResponse, date: &str) -> JiraResponse {
+        jira.fields.updated = format!("{}T10:00:00.000+0000", date);
+        jira
+    }
+
+    #[test]
+    fn overview_counts_by_status_category() {
+        let stories = ["done", "done", "indeterminate", "new", ""]
+            .iter()
+            .enumerate()
+            .map(|(index, category)| node(issue(&format!("WRIG-{}", index + 2), category), vec![]))
+            .collect::<Vec<StoryNode>>();
+        let row = OverviewRow::new(&issue("WRIG-1", "indeterminate"), &stories, &ctx());
+        assert_eq!(
+            (
+                row.stories,
+                row.to_do,
+                row.in_progress,
+                row.done,
+                row.percent
+            ),
+            (5, 2, 1, 2, 40)
+        );
+        assert_eq!(row.url, "https://jira.example.com/browse/WRIG-1");
+        // no story points field, no points
+        assert_eq!(row.points, "");
+
+        let empty = OverviewRow::new(&issue("WRIG-9", "new"), &[], &ctx());
+        assert_eq!((empty.stories, empty.percent), (0, 0));
+    }
+
+    #[test]
+    fn overview_story_points() {
+        let mut ctx = ctx();
+        ctx.story_points_field = Some("customfield_10016".to_string());
+        let points = |category: &str, value: Option<Value>| {
+            let mut jira = issue("WRIG-2", category);
+            if let Some(value) = value {
+                jira.fields
+                    .custom_fields
+                    .insert("customfield_10016".to_string(), value);
+            }
+            node(jira, vec![])
+        };
+        let stories = vec![
+            points("done", Some(serde_json::json!(3))),
+            points("indeterminate", Some(serde_json::json!(" 2.5 "))),
+            points("done", Some(serde_json::json!("many"))),
+            points("new", None),
+            points("done", Some(Value::Null)),
+        ];
+        let row = OverviewRow::new(&issue("WRIG-1", "indeterminate"), &stories, &ctx);
+        assert_eq!((row.points_done, row.points_total), (3.0, 5.5));
+        assert_eq!(row.points, "3 of 5.5");
+
+        let empty = OverviewRow::new(&issue("WRIG-9", "new"), &[], &ctx);
+        assert_eq!(empty.points, "0 of 0");
+        assert_eq!(format_points(5.0), "5");
+        assert_eq!(format_points(2.5), "2.5");
+    }
+
+    #[test]
+    fn overview_updated_and_total() {
+        let subtask = node(updated(issue("WRIG-4", "done"), "2024-05-09"), vec![]);
+        let story = node(
+            updated(issue("WRIG-3", "done"), "2024-05-02"),
+            vec![subtask],
+        );
+        let first = OverviewRow::new(
+            &updated(issue("WRIG-1", "indeterminate"), "2024-05-01"),
+            &[story],
+            &ctx(),
+        );
+        // the subtask was updated last
+        assert_eq!(first.updated, "2024-05-09");
+        let second = OverviewRow::new(
+            &updated(issue("WRIG-5", "new"), "2024-05-03"),
+            &[
+                node(issue("WRIG-6", "new"), vec![]),
+                node(issue("WRIG-7", "new"), vec![]),
+            ],
+            &ctx(),
+        );
+        assert_eq!(second.updated, "2024-05-03");
+
+        let total = OverviewRow::total(&[first, second], true);
+        assert_eq!(total.key, "Total");
+        assert_eq!(total.summary, "2 epics");
+        assert_eq!(
+            (total.stories, total.to_do, total.done, total.percent),
+            (3, 2, 1, 33)
+        );
+        assert_eq!(total.updated, "2024-05-09");
+        assert_eq!(total.points, "0 of 0");
+        assert_eq!(OverviewRow::total(&[], false).percent, 0);
+    }
+
+    /// changelog from the histories as jira returns them
+    fn changelog(histories: Value) -> Option<Changelog> {
+        Some(serde_json::from_value(serde_json::json!({ "histories": histories })).unwrap())
+    }
+
+    fn change(view: &ChangeView) -> (&str, &str, &str, &str, &str, &str) {
+        (
+            view.date.as_str(),
+            view.key.as_str(),
+            view.field.as_str(),
+            view.from.as_str(),
+            view.to.as_str(),
+            view.author.as_str(),
+        )
+    }
+
+    #[test]
+    fn changes_in_the_window() {
+        let mut epic = issue("WRIG-1", "indeterminate");
+        epic.changelog = changelog(serde_json::json!([
+            {
+                "id": "1",
+                "author": { "name": "alice", "displayName": "Alice Smith" },
+                "created": "2024-04-20T09:00:00.000+0000",
+                "items": [{ "field": "status", "fromString": "New", "toString": "To Do" }]
+            },
+            {
+                "id": "2",
+                "author": { "name": "alice", "displayName": "Alice Smith" },
+                "created": "2024-05-05T09:00:00.000+0000",
+                "items": [
+                    { "field": "Status", "fromString": "To Do", "toString": "In Progress" },
+                    { "field": "summary", "fromString": "old", "toString": "new" },
+                    { "field": "Assignee", "toString": "bob" }
+                ]
+            },
+            {
+                "id": "3",
+                "author": { "name": "alice", "displayName": "Alice Smith" },
+                "created": "2024-05-20T09:00:00.000+0000",
+                "items": [{ "field": "resolution", "toString": "Done" }]
+            }
+        ]));
+        let mut story = issue("WRIG-2", "done");
+        story.changelog = changelog(serde_json::json!([
+            {
+                "id": "4",
+                "author": { "name": "jenkins", "displayName": "" },
+                "created": "2024-05-03T08:00:00.000+0000",
+                "items": [
+                    { "field": "RESOLUTION", "toString": "Done" },
+                    { "field": "Story Points", "fromString": "3", "toString": "5" }
+                ]
+            }
+        ]));
+        let mut subtask = issue("WRIG-3", "done");
+        subtask.changelog = changelog(serde_json::json!([
+            {
+                "id": "5",
+                "created": "2024-05-04T12:30:00.000+0000",
+                "items": [{ "field": "status", "fromString": "To Do", "toString": "Done" }]
+            },
+            {
+                "id": "6",
+                "created": "not a date",
+                "items": [{ "field": "status", "toString": "Done" }]
+            }
+        ]));
+        let stories = vec![node(story, vec![node(subtask, vec![])])];
+
+        let view = EpicView::new(epic, stories, &ctx());
+        let changes = view.changes.iter().map(change).collect::<Vec<_>>();
+        // sorted across the epic, its stories and subtasks, untracked fields are left out
+        assert_eq!(
+            changes,
+            [
+                (
+                    "2024-05-03 08:00",
+                    "WRIG-2",
+                    "resolution",
+                    "",
+                    "Done",
+                    "jenkins"
+                ),
+                ("2024-05-04 12:30", "WRIG-3", "status", "To Do", "Done", ""),
+                (
+                    "2024-05-05 09:00",
+                    "WRIG-1",
+                    "status",
+                    "To Do",
+                    "In Progress",
+                    "Alice Smith"
+                ),
+                (
+                    "2024-05-05 09:00",
+                    "WRIG-1",
+                    "assignee",
+                    "",
+                    "bob",
+                    "Alice Smith"
+                ),
+            ]
+        );
+        assert_eq!(
+            view.changes[0].url,
+            "https://jira.example.com/browse/WRIG-2"
+        );
+    }
+
+    #[test]
+    fn changes_without_changelog() {
+        assert!(collect_changes(&issue("WRIG-1", "new"), &ctx()).is_empty());
+    }
+}
@@ -0,0 +1,192 @@
+use crate::config::load::Parameters;
+use crate::error::jira::JiraServiceError;
+use custom_logger as log;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// attempts per request when retry_max_attempts is not set
+const DEFAULT_MAX_ATTEMPTS: u32 = 4;
+
+/// seconds a request may spend retrying when retry_deadline_secs is not set
+const DEFAULT_DEADLINE_SECS: u64 = 60;
+
+/// delay before the first retry, doubled on every attempt
+const BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// upper bound of the backoff delay (Retry-After is honoured up to the deadline)
+const MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// retry policy for transient jira failures (429, 5xx, connect errors and timeouts)
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub deadline: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            deadline: Duration::from_secs(DEFAULT_DEADLINE_SECS),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(params: &Parameters) -> Self {
+        RetryPolicy {
+            max_attempts: params
+                .retry_max_attempts
+                .unwrap_or(DEFAULT_MAX_ATTEMPTS)
+                .max(1),
+            deadline: Duration::from_secs(
+                params.retry_deadline_secs.unwrap_or(DEFAULT_DEADLINE_SECS),
+            ),
+        }
+    }
+
+    /// delay before the next attempt, the error to report when it is final or the attempts or
+    /// the deadline are used up (attempt counts from 1, elapsed is the time since the first
+    /// attempt)
+    ///
+    /// a Retry-After wait past the deadline is logged, the error (a 429 names the wait) is
+    /// returned as it is
+    pub fn next_delay(
+        &self,
+        err: JiraServiceError,
+        attempt: u32,
+        elapsed: Duration,
+    ) -> Result<Duration, JiraServiceError> {
+        if attempt >= self.max_attempts || !is_transient(&err) {
+            return Err(err);
+        }
+        let retry_after = match &err {
+            JiraServiceError::RateLimited {
+                retry_after: Some(secs),
+                ..
+            }
+            | JiraServiceError::Server {
+                retry_after: Some(secs),
+                ..
+            } => Some(*secs),
+            _ => None,
+        };
+        let delay = retry_after
+            .map(Duration::from_secs)
+            .unwrap_or_else(|| backoff(attempt));
+        if elapsed + delay <= self.deadline {
+            return Ok(delay);
+        }
+        if let Some(secs) = retry_after {
+            log::warn!(
+                "[next_delay] jira asked to retry after {}s, past the retry deadline of {}s : {}",
+                secs,
+                self.deadline.as_secs(),
+                err
+            );
+        }
+        Err(err)
+    }
+}
+
+/// 429, 5xx and network errors (the client only reports connect errors and timeouts as
+/// network errors worth retrying)
+fn is_transient(err: &JiraServiceError) -> bool {
+    matches!(
+        err,
+        JiraServiceError::RateLimited { .. }
+            | JiraServiceError::Server { .. }
+            | JiraServiceError::Network { .. }
+    )
+}
+
+/// exponential backoff with equal jitter, half of the delay is fixed and half is random
+fn backoff(attempt: u32) -> Duration {
+    let exp = BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt - 1))
+        .min(MAX_DELAY);
+    let half = exp / 2;
+    half + half.mul_f64(jitter())
+}
+
+/// pseudo random fraction in [0, 1) from the clock, good enough to spread retries
+fn jitter() -> f64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or_default();
+    // xorshift to spread consecutive nanosecond values
+    let mut x = nanos as u64 | 1;
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    (x % 10_000) as f64 / 10_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 4,
+            deadline: Duration::from_secs(60),
+        }
+    }
+
+    fn unavailable(retry_after: Option<u64>) -> JiraServiceError {
+        JiraServiceError::Server {
+            status: 503,
+            key: Some("WRIG-1".to_string()),
+            url: "https://jira.example.com/rest/api/2/issue/WRIG-1".to_string(),
+            message: "unavailable".to_string(),
+            retry_after,
+        }
+    }
+
+    #[test]
+    fn retry_after_is_the_delay() {
+        let delay = policy().next_delay(unavailable(Some(5)), 1, Duration::from_secs(10));
+        assert_eq!(delay.unwrap(), Duration::from_secs(5));
+        let delay = policy().next_delay(unavailable(None), 1, Duration::ZERO);
+        assert!(delay.unwrap() <= BASE_DELAY);
+    }
+
+    #[test]
+    fn retry_after_past_the_deadline_keeps_the_error() {
+        let err = policy()
+            .next_delay(unavailable(Some(120)), 1, Duration::ZERO)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            JiraServiceError::Server {
+                status: 503,
+                retry_after: Some(120),
+                ..
+            }
+        ));
+        assert_eq!(err.exit_code(), 7);
+
+        let rate_limited = JiraServiceError::RateLimited {
+            key: None,
+            url: "https://jira.example.com/rest/api/2/search".to_string(),
+            retry_after: Some(120),
+        };
+        let err = policy()
+            .next_delay(rate_limited, 1, Duration::ZERO)
+            .unwrap_err();
+        assert_eq!(err.exit_code(), 6);
+        assert!(err.to_string().contains("retry after 120s"));
+    }
+
+    #[test]
+    fn final_errors_are_returned() {
+        let err = policy()
+            .next_delay(unavailable(None), 1, Duration::from_secs(60))
+            .unwrap_err();
+        assert!(matches!(err, JiraServiceError::Server { .. }));
+        let err = policy()
+            .next_delay(unavailable(Some(1)), 4, Duration::ZERO)
+            .unwrap_err();
+        assert!(matches!(err, JiraServiceError::Server { .. }));
+    }
+}
@@ -0,0 +1,523 @@
+// module server
+//
+// http mode, exposes report generation as a small rest api
+//
+// GET  /issues/{key}   issue json (served through the issue cache)
+// POST /reports        generate a report, body {"issues": [..]} or {"jql": ".."} plus "format"
+// GET  /reports/{id}   rendered report document
+
+use crate::config::load::Parameters;
+use crate::error::jira::JiraServiceError;
+use crate::handlers::common::shutdown_signal;
+use crate::handlers::render::{ReportFormat, get_renderer};
+use crate::handlers::report::ReportWindow;
+use crate::handlers::service::{
+    ReportOptions, ReportQuery, Service, ServiceInterface, default_format,
+};
+use custom_logger as log;
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
+use hyper::body::{Body, Bytes};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+
+/// listen address when server_address is not set
+const DEFAULT_SERVER_ADDRESS: &str = "127.0.0.1:8090";
+
+/// largest accepted request body
+const MAX_BODY_BYTES: usize = 64 * 1024;
+
+/// generated reports kept in memory, the oldest is dropped first
+const MAX_STORED_REPORTS: usize = 100;
+
+/// pause after a failed accept (too many open files ...) before the next one
+const ACCEPT_RETRY_MILLIS: u64 = 100;
+
+#[derive(Debug, Clone, Deserialize)]
+struct ReportRequest {
+    #[serde(default)]
+    issues: Vec<String>,
+    #[serde(default)]
+    jql: Option<String>,
+    /// falls back to the first config format (markdown when not set)
+    #[serde(default)]
+    format: Option<ReportFormat>,
+    #[serde(default)]
+    since: Option<String>,
+    #[serde(default)]
+    until: Option<String>,
+    #[serde(default)]
+    refresh: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ReportCreated {
+    id: String,
+    url: String,
+    format: ReportFormat,
+}
+
+#[derive(Debug, Clone)]
+struct StoredReport {
+    content_type: &'static str,
+    document: String,
+}
+
+struct ServerState {
+    params: Parameters,
+    reports: Mutex<BTreeMap<u64, StoredReport>>,
+    next_id: Mutex<u64>,
+    /// every request that reaches jira opens the issue cache tree for its whole run
+    /// (create_client) and a tree must not be open twice at once, so jira work is done one
+    /// request at a time and a long report delays issue lookups
+    jira: Mutex<()>,
+}
+
+impl ServerState {
+    fn new(params: Parameters) -> Self {
+        ServerState {
+            params,
+            reports: Mutex::new(BTreeMap::new()),
+            next_id: Mutex::new(1),
+            jira: Mutex::new(()),
+        }
+    }
+}
+
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        ApiError {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+/// serves the api until ctrl-c or sigterm is received
+pub async fn serve(params: Parameters) -> Result<(), Box<dyn std::error::Error>> {
+    let address = params
+        .server_address
+        .clone()
+        .unwrap_or(DEFAULT_SERVER_ADDRESS.to_string());
+    let listener = TcpListener::bind(&address)
+        .await
+        .map_err(|e| JiraServiceError::io(&address, e))?;
+    log::info!("listening on {}", address);
+    let state = Arc::new(ServerState::new(params));
+    // one signal future for the whole loop, a signal between two accepts is not lost
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    loop {
+        let (stream, peer) = tokio::select! {
+            res = listener.accept() => match res {
+                Ok(conn) => conn,
+                // the listener stays usable, one failed connection must not stop the server
+                Err(e) => {
+                    log::warn!("[serve] accept : {}", e);
+                    tokio::time::sleep(Duration::from_millis(ACCEPT_RETRY_MILLIS)).await;
+                    continue;
+                }
+            },
+            _ = &mut shutdown => {
+                log::info!("shutting down");
+                break;
+            }
+        };
+        let state = state.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |req| handle(state.clone(), req));
+            if let Err(e) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                log::warn!("[serve] connection {} : {}", peer, e);
+            }
+        });
+    }
+    Ok(())
+}
+
+async fn handle<B>(
+    state: Arc<ServerState>,
+    req: Request<B>,
+) -> Result<Response<Full<Bytes>>, Infallible>
+where
+    B: Body<Data = Bytes>,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let method = req.method().clone();
+    let path = req.uri().path().trim_matches('/').to_string();
+    log::debug!("[handle] {} /{}", method, path);
+    let segments = path.split('/').collect::<Vec<&str>>();
+    let res = match (&method, segments.as_slice()) {
+        (&Method::GET, ["issues", key]) => get_issue(&state, key).await,
+        (&Method::POST, ["reports"]) => create_report(&state, req).await,
+        (&Method::GET, ["reports", id]) => get_report(&state, id).await,
+        (_, ["issues", _]) | (_, ["reports"]) | (_, ["reports", _]) => Err(ApiError::new(
+            StatusCode::METHOD_NOT_ALLOWED,
+            format!("method {} not allowed", method),
+        )),
+        _ => Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            format!("/{} not found", path),
+        )),
+    };
+    Ok(res.unwrap_or_else(|err| {
+        log::warn!("[handle] {} /{} : {}", method, path, err.message);
+        let body = serde_json::json!({ "error": err.message }).to_string();
+        response(err.status, "application/json", body)
+    }))
+}
+
+async fn get_issue(state: &ServerState, key: &str) -> Result<Response<Full<Bytes>>, ApiError> {
+    let _guard = state.jira.lock().await;
+    let jira = Service::get_issue(&state.params, key, false)
+        .await
+        .map_err(jira_error)?;
+    let body = serde_json::to_string(&jira)
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(response(StatusCode::OK, "application/json", body))
+}
+
+async fn create_report<B>(
+    state: &ServerState,
+    req: Request<B>,
+) -> Result<Response<Full<Bytes>>, ApiError>
+where
+    B: Body<Data = Bytes>,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let body = Limited::new(req.into_body(), MAX_BODY_BYTES)
+        .collect()
+        .await
+        .map_err(|e| match e.downcast_ref::<LengthLimitError>() {
+            Some(_) => ApiError::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("request body is larger than {} bytes", MAX_BODY_BYTES),
+            ),
+            None => ApiError::new(StatusCode::BAD_REQUEST, e.to_string()),
+        })?
+        .to_bytes();
+    let request: ReportRequest = serde_json::from_slice(&body)
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, format!("request body : {}", e)))?;
+    let query = match (request.jql, request.issues.is_empty()) {
+        (Some(jql), true) => ReportQuery::Jql(jql),
+        (None, false) => ReportQuery::Issues(request.issues),
+        _ => {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "exactly one of issues or jql is required",
+            ));
+        }
+    };
+    let window = ReportWindow::parse(request.since.as_deref(), request.until.as_deref())
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e.to_string()))?;
+    let format = request.format.unwrap_or(default_format(&state.params));
+    let options = ReportOptions {
+        query,
+        refresh: request.refresh,
+        formats: vec![format],
+        window,
+        comment_epics: false,
+        dry_run: false,
+    };
+    let stored = {
+        let _guard = state.jira.lock().await;
+        render_report(&state.params, &options, format).await?
+    };
+    let id = {
+        let mut next_id = state.next_id.lock().await;
+        let id = *next_id;
+        *next_id += 1;
+        id
+    };
+    let mut reports = state.reports.lock().await;
+    reports.insert(id, stored);
+    while reports.len() > MAX_STORED_REPORTS {
+        reports.pop_first();
+    }
+    log::info!("report {} created ({:?})", id, format);
+    let created = ReportCreated {
+        id: id.to_string(),
+        url: format!("/reports/{}", id),
+        format,
+    };
+    let body = serde_json::to_string(&created)
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(response(StatusCode::CREATED, "application/json", body))
+}
+
+/// errors are mapped before the next await, the boxed errors are not Send
+async fn render_report(
+    params: &Parameters,
+    options: &ReportOptions,
+    format: ReportFormat,
+) -> Result<StoredReport, ApiError> {
+    let report = Service::build_report(params, options)
+        .await
+        .map_err(jira_error)?;
+    let internal = |e: Box<dyn std::error::Error>| {
+        ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    };
+    let renderer = get_renderer(format, params).map_err(internal)?;
+    let document = renderer.render(&report).map_err(internal)?;
+    Ok(StoredReport {
+        content_type: renderer.content_type(),
+        document,
+    })
+}
+
+async fn get_report(state: &ServerState, id: &str) -> Result<Response<Full<Bytes>>, ApiError> {
+    let not_found = || ApiError::new(StatusCode::NOT_FOUND, format!("report {} not found", id));
+    let id = id.parse::<u64>().map_err(|_| not_found())?;
+    let reports = state.reports.lock().await;
+    let stored = reports.get(&id).ok_or_else(not_found)?;
+    Ok(response(
+        StatusCode::OK,
+        stored.content_type,
+        stored.document.clone(),
+    ))
+}
+
+/// unknown issues and rejected requests (bad jql) are the caller's, failures of jira or of
+/// the connection to it are a bad gateway
+fn jira_error(err: Box<dyn std::error::Error>) -> ApiError {
+    let err = JiraServiceError::from_boxed(err);
+    let status = match err {
+        JiraServiceError::NotFound { .. } => StatusCode::NOT_FOUND,
+        JiraServiceError::Request { .. } => StatusCode::BAD_REQUEST,
+        JiraServiceError::Auth { .. }
+        | JiraServiceError::RateLimited { .. }
+        | JiraServiceError::Server { .. }
+        | JiraServiceError::Network { .. }
+        | JiraServiceError::Parse { .. } => StatusCode::BAD_GATEWAY,
+        JiraServiceError::Config { .. }
+        | JiraServiceError::Io { .. }
+        | JiraServiceError::Other(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    ApiError::new(status, err.to_string())
+}
+
+fn response(status: StatusCode, content_type: &str, body: String) -> Response<Full<Bytes>> {
+    let mut res = Response::new(Full::new(Bytes::from(body)));
+    *res.status_mut() = status;
+    if let Ok(value) = content_type.parse() {
+        res.headers_mut().insert(hyper::header::CONTENT_TYPE, value);
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::cache::IssueCache;
+    use serde_json::{Value, json};
+
+    /// test mode over docs/fixtures, the cache is written to a scratch directory
+    fn state(name: &str) -> Arc<ServerState> {
+        let working_dir =
+            std::env::temp_dir().join(format!("jira-service-{}-{}", name, std::process::id()));
+        let params: Parameters = serde_json::from_value(json!({
+            "test": true,
+            "fixtures_dir": concat!(env!("CARGO_MANIFEST_DIR"), "/docs/fixtures"),
+            "working_dir": working_dir.to_string_lossy(),
+            "base_url": "https://jira.example.com/rest/api/2/issue/",
+            "story_link_types": ["Relates"],
+            "story_points_field": "customfield_12310243",
+        }))
+        .unwrap();
+        Arc::new(ServerState::new(params))
+    }
+
+    async fn call(
+        state: &Arc<ServerState>,
+        method: Method,
+        uri: &str,
+        body: impl Into<Bytes>,
+    ) -> (StatusCode, String, String) {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Full::new(body.into()))
+            .unwrap();
+        let res = handle(state.clone(), req).await.unwrap();
+        let status = res.status();
+        let content_type = res
+            .headers()
+            .get(hyper::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        (
+            status,
+            content_type,
+            String::from_utf8_lossy(&body).to_string(),
+        )
+    }
+
+    fn cleanup(state: &ServerState) {
+        let _ = std::fs::remove_dir_all(&state.params.working_dir);
+    }
+
+    #[tokio::test]
+    async fn routing() {
+        let state = state("server-routing");
+        let (status, _, body) = call(&state, Method::GET, "/nothing/here", "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(body.contains("/nothing/here not found"));
+        for (method, uri) in [
+            (Method::DELETE, "/reports"),
+            (Method::PUT, "/reports/1"),
+            (Method::POST, "/issues/WRIG-1"),
+        ] {
+            let (status, _, _) = call(&state, method, uri, "").await;
+            assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED, "{}", uri);
+        }
+        let (status, _, _) = call(&state, Method::GET, "/reports/42", "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        cleanup(&state);
+    }
+
+    #[tokio::test]
+    async fn report_requests_are_checked() {
+        let state = state("server-requests");
+        for body in [
+            json!({}),
+            json!({ "issues": ["WRIG-1"], "jql": "project = WRIG" }),
+        ] {
+            let (status, _, body) = call(&state, Method::POST, "/reports", body.to_string()).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert!(body.contains("exactly one of issues or jql"));
+        }
+        let (status, _, _) = call(&state, Method::POST, "/reports", "{").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let oversized = json!({ "jql": "x".repeat(MAX_BODY_BYTES) }).to_string();
+        let (status, _, _) = call(&state, Method::POST, "/reports", oversized).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        cleanup(&state);
+    }
+
+    #[tokio::test]
+    async fn created_reports_are_served() {
+        let state = state("server-reports");
+        let request = json!({ "issues": ["WRIG-1"], "format": "html" }).to_string();
+        let (status, _, body) = call(&state, Method::POST, "/reports", request).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let created: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(created["format"], "html");
+        let url = created["url"].as_str().unwrap();
+        assert_eq!(url, format!("/reports/{}", created["id"].as_str().unwrap()));
+
+        let (status, content_type, document) = call(&state, Method::GET, url, "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(content_type.starts_with("text/html"));
+        assert!(document.contains("WRIG-1"));
+        cleanup(&state);
+    }
+
+    #[tokio::test]
+    async fn issues_are_served_and_missing_ones_are_not_found() {
+        let state = state("server-issues");
+        let (status, content_type, body) = call(&state, Method::GET, "/issues/WRIG-2", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type, "application/json");
+        let jira: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(jira["key"], "WRIG-2");
+        let (status, _, body) = call(&state, Method::GET, "/issues/WRIG-99", "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(body.contains("WRIG-99"));
+        cleanup(&state);
+    }
+
+    #[tokio::test]
+    async fn reports_of_unknown_issues_are_not_found() {
+        let state = state("server-unknown");
+        let request = json!({ "issues": ["WRIG-99"] }).to_string();
+        let (status, _, body) = call(&state, Method::POST, "/reports", request).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(body.contains("WRIG-99"));
+        cleanup(&state);
+    }
+
+    #[test]
+    fn jira_error_statuses() {
+        let url = "https://jira.example.com/rest/api/2/search".to_string();
+        for (err, status) in [
+            (
+                JiraServiceError::from_status(404, Some("WRIG-99"), &url, b"", None),
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                JiraServiceError::from_status(400, None, &url, b"bad jql", None),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                JiraServiceError::from_status(401, None, &url, b"", None),
+                StatusCode::BAD_GATEWAY,
+            ),
+            (
+                JiraServiceError::from_status(429, None, &url, b"", Some(5)),
+                StatusCode::BAD_GATEWAY,
+            ),
+            (
+                JiraServiceError::from_status(503, None, &url, b"", None),
+                StatusCode::BAD_GATEWAY,
+            ),
+            (
+                JiraServiceError::network(None, &url, "connection refused"),
+                StatusCode::BAD_GATEWAY,
+            ),
+            (
+                JiraServiceError::io("cache", "disk full"),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+        ] {
+            let message = err.to_string();
+            let api = jira_error(Box::new(err));
+            assert_eq!(api.status, status, "{}", message);
+            assert_eq!(api.message, message);
+        }
+        let api = jira_error(Box::from("no epics"));
+        assert_eq!(api.status, StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[tokio::test]
+    async fn concurrent_requests_share_the_cache() {
+        let state = state("server-concurrent");
+        let request = json!({ "issues": ["WRIG-1"] }).to_string();
+        // the cache tree is opened by one request at a time, no write is lost
+        let (report, first, second) = tokio::join!(
+            call(&state, Method::POST, "/reports", request),
+            call(&state, Method::GET, "/issues/WRIG-5", ""),
+            call(&state, Method::GET, "/issues/WRIG-7", ""),
+        );
+        assert_eq!(report.0, StatusCode::CREATED);
+        assert_eq!((first.0, second.0), (StatusCode::OK, StatusCode::OK));
+
+        let cache = IssueCache::open_for(&state.params, false).unwrap();
+        let entries = cache.list().await.unwrap();
+        cache.close().await.unwrap();
+        let keys = entries
+            .iter()
+            .map(|entry| entry.key.as_str())
+            .collect::<Vec<&str>>();
+        for key in ["WRIG-1", "WRIG-5", "WRIG-7"] {
+            assert!(keys.contains(&key), "{} missing from {:?}", key, keys);
+        }
+        cleanup(&state);
+    }
+}
@@ -1,14 +1,28 @@
 use crate::config::load::Parameters;
+use crate::error::jira::JiraServiceError;
+use crate::handlers::adf;
+use crate::handlers::auth::Credentials;
+use crate::handlers::browse::BrowseLinks;
+use crate::handlers::cache::{CachingJiraClient, IssueCache};
+use crate::handlers::client::{FakeJiraClient, JiraClient, RestJiraClient};
+use crate::handlers::common::url_api_version;
+use crate::handlers::markup;
+use crate::handlers::render::{ReportFormat, epic_comment, get_renderer};
+use crate::handlers::report::{Report, ReportContext, ReportWindow, StoryNode};
+use crate::handlers::retry::RetryPolicy;
+use crate::handlers::spec::{EpicSpec, StorySpec};
 use chrono::prelude::*;
 use custom_logger as log;
 use serde_derive::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Value, json};
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::fs::OpenOptions;
-use std::io::Write;
 use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
+use std::sync::Arc;
 use std::time::SystemTime;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -19,6 +33,9 @@ pub struct JiraResponse {
     pub self_field: String,
     pub key: String,
     pub fields: Fields,
+    /// only present when the issue is requested with expand=changelog
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changelog: Option<Changelog>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -26,31 +43,93 @@ pub struct JiraResponse {
 pub struct Fields {
     pub priority: Priority,
     pub labels: Vec<String>,
-    pub timeestimate: Value,
-    pub versions: Vec<Value>,
+    /// remaining estimate in seconds
+    #[serde(default)]
+    pub timeestimate: Option<i64>,
+    /// affects versions
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub versions: Vec<Version>,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub fix_versions: Vec<Version>,
     pub issuelinks: Vec<IssueLink>,
-    pub assignee: Value,
+    #[serde(default)]
+    pub assignee: Option<User>,
+    #[serde(default)]
+    pub reporter: Option<User>,
     pub status: Status,
-    pub components: Vec<Value>,
-    pub creator: Creator,
-    pub subtasks: Vec<Value>,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub components: Vec<Component>,
+    pub creator: User,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub subtasks: Vec<Subtask>,
+    /// parent issue of a subtask (or of a story in a parent based hierarchy)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<LinkedIssue>,
     pub aggregateprogress: Aggregateprogress,
     pub progress: Progress,
     pub issuetype: Issuetype,
-    pub timespent: Value,
+    /// time logged in seconds
+    #[serde(default)]
+    pub timespent: Option<i64>,
     pub project: Project,
-    pub aggregatetimespent: Value,
-    pub resolutiondate: Value,
+    /// time logged on the issue and its subtasks in seconds
+    #[serde(default)]
+    pub aggregatetimespent: Option<i64>,
+    #[serde(default)]
+    pub resolutiondate: Option<String>,
     pub created: String,
     pub updated: String,
-    pub timeoriginalestimate: Value,
-    pub description: Option<String>,
+    /// original estimate in seconds
+    #[serde(default)]
+    pub timeoriginalestimate: Option<i64>,
+    pub description: Option<RichText>,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub timetracking: Timetracking,
-    pub attachment: Vec<Value>,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub attachment: Vec<Attachment>,
     pub summary: String,
-    pub environment: Value,
-    pub duedate: Value,
+    #[serde(default)]
+    pub environment: Option<RichText>,
+    /// YYYY-MM-DD
+    #[serde(default)]
+    pub duedate: Option<String>,
     pub comment: Comment,
+    /// fields without a model (customfield_NNNNN, plugin fields ...) are kept as they are
+    #[serde(flatten)]
+    pub custom_fields: HashMap<String, Value>,
+}
+
+/// jira sends null for unset lists and blocks on some instances, they read as empty
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::Deserialize<'de> + Default,
+{
+    let value = <Option<T> as serde::Deserialize>::deserialize(deserializer)?;
+    Ok(value.unwrap_or_default())
+}
+
+/// description and comment body, wiki markup on the v2 api and an atlassian document on v3
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RichText {
+    Wiki(String),
+    Adf(Value),
+}
+
+impl Default for RichText {
+    fn default() -> Self {
+        RichText::Wiki(String::new())
+    }
+}
+
+impl RichText {
+    pub fn to_markdown(&self) -> String {
+        match self {
+            RichText::Wiki(text) => markup::to_markdown(text),
+            RichText::Adf(doc) => adf::to_markdown(doc),
+        }
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -88,15 +167,91 @@ pub struct StatusCategory {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct Creator {
-    #[serde(rename = "self")]
+pub struct User {
+    #[serde(rename = "self", default)]
     pub self_field: String,
+    /// username on jira server, empty on jira cloud
+    #[serde(default)]
     pub name: String,
+    #[serde(default)]
     pub key: String,
-    pub email_address: String,
+    /// user id on jira cloud
+    #[serde(default)]
+    pub account_id: Option<String>,
+    /// hidden by the privacy settings of some instances
+    #[serde(default)]
+    pub email_address: Option<String>,
+    #[serde(default)]
     pub display_name: String,
+    #[serde(default)]
     pub active: bool,
-    pub time_zone: String,
+    #[serde(default)]
+    pub time_zone: Option<String>,
+}
+
+impl User {
+    /// username on jira server, the display name on jira cloud where users have no name
+    pub fn name_or_display(&self) -> String {
+        match self.name.is_empty() {
+            true => self.display_name.clone(),
+            false => self.name.clone(),
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Version {
+    #[serde(rename = "self", default)]
+    pub self_field: String,
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub archived: bool,
+    #[serde(default)]
+    pub released: bool,
+    /// YYYY-MM-DD
+    #[serde(default)]
+    pub release_date: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Component {
+    #[serde(rename = "self", default)]
+    pub self_field: String,
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    #[serde(rename = "self", default)]
+    pub self_field: String,
+    #[serde(default)]
+    pub id: String,
+    pub filename: String,
+    #[serde(default)]
+    pub author: Option<User>,
+    #[serde(default)]
+    pub created: String,
+    /// bytes
+    #[serde(default)]
+    pub size: i64,
+    #[serde(default)]
+    pub mime_type: Option<String>,
+    /// download url
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub thumbnail: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -169,7 +324,21 @@ pub struct Watches {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct Timetracking {}
+pub struct Timetracking {
+    /// jira duration format (1w 2d 4h)
+    #[serde(default)]
+    pub original_estimate: Option<String>,
+    #[serde(default)]
+    pub remaining_estimate: Option<String>,
+    #[serde(default)]
+    pub time_spent: Option<String>,
+    #[serde(default)]
+    pub original_estimate_seconds: Option<i64>,
+    #[serde(default)]
+    pub remaining_estimate_seconds: Option<i64>,
+    #[serde(default)]
+    pub time_spent_seconds: Option<i64>,
+}
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -186,39 +355,296 @@ pub struct CommentData {
     #[serde(rename = "self")]
     pub self_field: String,
     pub id: String,
-    pub author: Author,
-    pub body: String,
+    pub author: User,
+    pub body: RichText,
     pub created: String,
     pub updated: String,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct Author {
-    #[serde(rename = "self")]
-    pub self_field: String,
+pub struct IssueLink {
+    pub id: String,
+    #[serde(rename = "type", default)]
+    pub link_type: IssueLinkType,
+    /// set when this issue is the inward side of the link
+    #[serde(default)]
+    pub outward_issue: Option<LinkedIssue>,
+    /// set when this issue is the outward side of the link
+    #[serde(default)]
+    pub inward_issue: Option<LinkedIssue>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IssueLinkType {
+    #[serde(default)]
     pub name: String,
+    /// description seen from the outward issue (is blocked by)
+    #[serde(default)]
+    pub inward: String,
+    /// description seen from the inward issue (blocks)
+    #[serde(default)]
+    pub outward: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkedIssue {
     pub key: String,
-    pub email_address: String,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct IssueLink {
+pub struct Subtask {
+    #[serde(default)]
+    pub id: String,
+    pub key: String,
+    #[serde(default)]
+    pub fields: SubtaskFields,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubtaskFields {
+    #[serde(default)]
+    pub summary: String,
+}
+
+impl IssueLink {
+    /// key of the other issue and the link as read from this issue (relates to, is parent of)
+    pub fn target(&self) -> Option<(&str, &str)> {
+        match (&self.outward_issue, &self.inward_issue) {
+            (Some(issue), _) => Some((
+                &issue.key,
+                relation(&self.link_type.outward, &self.link_type),
+            )),
+            (None, Some(issue)) => Some((
+                &issue.key,
+                relation(&self.link_type.inward, &self.link_type),
+            )),
+            (None, None) => None,
+        }
+    }
+
+    /// a link selects a story when its type name or its description from this issue is one
+    /// of the configured story link types, every outward link counts when none are configured
+    pub fn is_story_link(&self, link_types: &[String]) -> bool {
+        match self.target() {
+            Some((_, relation)) if !link_types.is_empty() => link_types.iter().any(|link_type| {
+                link_type.eq_ignore_ascii_case(&self.link_type.name)
+                    || link_type.eq_ignore_ascii_case(relation)
+            }),
+            Some(_) => self.outward_issue.is_some(),
+            None => false,
+        }
+    }
+}
+
+/// falls back to the type name when jira omits the description
+fn relation<'a>(description: &'a str, link_type: &'a IssueLinkType) -> &'a str {
+    match description.trim().is_empty() {
+        true => &link_type.name,
+        false => description,
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Changelog {
+    #[serde(default)]
+    pub start_at: i64,
+    #[serde(default)]
+    pub max_results: i64,
+    #[serde(default)]
+    pub total: i64,
+    pub histories: Vec<History>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct History {
+    pub id: String,
+    #[serde(default)]
+    pub author: Option<HistoryAuthor>,
+    pub created: String,
+    pub items: Vec<HistoryItem>,
+}
+
+/// changelog authors can be anonymous or automation users without an email
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryAuthor {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub display_name: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryItem {
+    pub field: String,
+    #[serde(default)]
+    pub fieldtype: String,
+    #[serde(default, rename = "fromString")]
+    pub from_value: Option<String>,
+    #[serde(default, rename = "toString")]
+    pub to_value: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResponse {
+    #[serde(default)]
+    pub start_at: i64,
+    #[serde(default)]
+    pub max_results: i64,
+    /// not returned by the v3 search, which pages with next_page_token
+    #[serde(default)]
+    pub total: i64,
+    pub issues: Vec<JiraResponse>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_page_token: Option<String>,
+    /// json of each issue as jira returned it, filled by the rest client for the issue cache
+    #[serde(skip)]
+    pub issues_json: Vec<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransitionsResponse {
+    pub transitions: Vec<Transition>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transition {
     pub id: String,
-    pub outward_issue: Option<OutwardIssue>,
+    pub name: String,
+    /// target status
+    #[serde(default)]
+    pub to: Option<TransitionTarget>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransitionTarget {
+    pub name: String,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct OutwardIssue {
+pub struct BulkCreateResponse {
+    #[serde(default)]
+    pub issues: Vec<CreatedIssue>,
+    #[serde(default)]
+    pub errors: Vec<BulkCreateError>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreatedIssue {
+    #[serde(default)]
+    pub id: String,
     pub key: String,
+    #[serde(rename = "self", default)]
+    pub self_field: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkCreateError {
+    #[serde(default)]
+    pub status: i64,
+    #[serde(default)]
+    pub element_errors: Value,
+    /// index of the failed issue in the request
+    #[serde(default)]
+    pub failed_element_number: i64,
 }
 
+/// options for a single report run
+#[derive(Debug, Clone)]
+pub struct ReportOptions {
+    pub query: ReportQuery,
+    /// bypass the issue cache (entries are still rewritten)
+    pub refresh: bool,
+    /// output formats, falls back to the config formats (markdown when neither is set)
+    pub formats: Vec<ReportFormat>,
+    /// reporting window used for the "what changed" sections
+    pub window: ReportWindow,
+    /// post the status summary of each epic as a comment on the epic
+    pub comment_epics: bool,
+    /// print jira write requests instead of sending them
+    pub dry_run: bool,
+}
+
+/// selects the epics a report is built from
+#[derive(Debug, Clone)]
+pub enum ReportQuery {
+    Issues(Vec<String>),
+    Jql(String),
+}
+
+/// page size used when following search results
+const SEARCH_PAGE_SIZE: i64 = 50;
+
+/// concurrent requests allowed when max_concurrency is not set
+const DEFAULT_MAX_CONCURRENCY: usize = 4;
+
+/// report title used when report_title is not set
+const DEFAULT_REPORT_TITLE: &str = "WRIG ET Bi-Weekly Status Report";
+
+/// rest api version when neither api_version nor base_url name one
+const DEFAULT_API_VERSION: u32 = 2;
+
+/// levels of subtasks walked below a story when subtask_depth is not set
+const DEFAULT_SUBTASK_DEPTH: usize = 2;
+
+/// jql finding the children of an epic when child_jql is not set
+const DEFAULT_CHILD_JQL: &str = "\"Epic Link\" = {key} OR parent = {key} ORDER BY key ASC";
+
+/// jql retried when the child jql is rejected, a jira without the epic link field (cloud
+/// team-managed projects) fails the whole query but still knows the parent field
+const PARENT_CHILD_JQL: &str = "parent = {key} ORDER BY key ASC";
+
 pub trait ServiceInterface {
     async fn execute(
         params: Parameters,
-        issue: String,
+        options: ReportOptions,
+    ) -> Result<String, Box<dyn std::error::Error>>;
+    async fn build_report(
+        params: &Parameters,
+        options: &ReportOptions,
+    ) -> Result<Report, Box<dyn std::error::Error>>;
+    async fn get_issue(
+        params: &Parameters,
+        key: &str,
+        refresh: bool,
+    ) -> Result<JiraResponse, Box<dyn std::error::Error>>;
+    async fn search(
+        params: &Parameters,
+        jql: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<JiraResponse>, Box<dyn std::error::Error>>;
+    async fn comment(
+        params: &Parameters,
+        key: &str,
+        body: &str,
+        dry_run: bool,
+    ) -> Result<String, Box<dyn std::error::Error>>;
+    async fn list_transitions(
+        params: &Parameters,
+        key: &str,
+    ) -> Result<String, Box<dyn std::error::Error>>;
+    async fn transition(
+        params: &Parameters,
+        key: &str,
+        transition: &str,
+        comment: Option<&str>,
+        dry_run: bool,
+    ) -> Result<String, Box<dyn std::error::Error>>;
+    async fn create(
+        params: &Parameters,
+        spec_path: &str,
+        dry_run: bool,
     ) -> Result<String, Box<dyn std::error::Error>>;
 }
 
@@ -227,187 +653,1202 @@ pub struct Service {}
 impl ServiceInterface for Service {
     async fn execute(
         params: Parameters,
-        issues: String,
+        options: ReportOptions,
     ) -> Result<String, Box<dyn std::error::Error>> {
-        let file_name = format!("{}/staging/{}", params.working_dir, params.document_name);
-        if Path::new(&file_name).exists() {
-            fs::remove_file(file_name.clone())?;
+        // renderers are created up front so a broken template fails before any request
+        let mut renderers = vec![];
+        for format in resolve_formats(&params, &options.formats) {
+            renderers.push(get_renderer(format, &params)?);
         }
-        let curr_time = SystemTime::now();
-        let dt: DateTime<Utc> = curr_time.clone().into();
-        let header = format!(
-            "# [{}] WRIG ET Bi-Weekly Status Report\n\n\n",
-            dt.format("%Y-%m-%d")
-        );
-        fs::write(file_name.clone(), header)?;
-        fs::set_permissions(file_name.clone(), fs::Permissions::from_mode(0o777))?;
-        let api_key = fs::read_to_string(params.api_key_path)?;
-        match params.test {
-            true => {
-                log::info!("mode        : testing");
-                let data_result = fs::read_to_string("docs/test-example.json")?;
-                let jira: JiraResponse = serde_json::from_str(&data_result)?;
-                log::info!("response from test {:?}", jira);
+        let report = Self::build_report(&params, &options).await?;
+        for renderer in renderers.iter() {
+            let file_name = document_path(&params, renderer.extension());
+            if Path::new(&file_name).exists() {
+                fs::remove_file(&file_name).map_err(|e| JiraServiceError::io(&file_name, e))?;
             }
-            false => {
-                log::info!("mode        : executing");
-                let vec_issues: Vec<&str> = issues.split(",").collect();
-                for issue in vec_issues.iter() {
-                    let url = format!("{}{}", params.base_url, issue);
-                    log::debug!("[execute] url {}", url);
-                    let client = reqwest::Client::new();
-                    let res = client.get(url).bearer_auth(api_key.trim()).send().await;
-                    match res {
-                        Ok(data) => {
-                            // epics
-                            let data_result = data.bytes().await?;
-                            log::trace!(
-                                "jira raw response {}",
-                                String::from_utf8(data_result.to_vec()).unwrap()
-                            );
-                            let jira: JiraResponse = serde_json::from_slice(&data_result)?;
-                            log::trace!("jira response {:?}", jira);
-                            save_data(jira.clone(), "EPIC".to_string(), file_name.clone())?;
-                            // linked user stories
-                            for link in jira.fields.issuelinks.iter() {
-                                // this will fail if there is no issuelink
-                                let story_url = format!(
-                                    "{}{}",
-                                    params.base_url,
-                                    link.outward_issue.as_ref().unwrap().key
-                                );
-                                let res_story = client
-                                    .get(story_url)
-                                    .bearer_auth(api_key.trim())
-                                    .send()
-                                    .await;
-                                match res_story {
-                                    Ok(story) => {
-                                        let data_story = story.bytes().await?;
-                                        let jira: JiraResponse =
-                                            serde_json::from_slice(&data_story)?;
-                                        save_data(
-                                            jira.clone(),
-                                            "STORY".to_string(),
-                                            file_name.clone(),
-                                        )?;
-                                    }
-                                    Err(err) => {
-                                        return Err(Box::from(err.to_string()));
-                                    }
-                                }
-                            }
-                        }
-                        Err(err) => {
-                            return Err(Box::from(err.to_string()));
-                        }
-                    }
+            let document = renderer.render(&report)?;
+            log::info!("writing report to {}", file_name);
+            fs::write(&file_name, document).map_err(|e| JiraServiceError::io(&file_name, e))?;
+            fs::set_permissions(&file_name, fs::Permissions::from_mode(0o777))
+                .map_err(|e| JiraServiceError::io(&file_name, e))?;
+        }
+        if options.comment_epics {
+            let (client, cache) = create_client(&params, false, options.dry_run)?;
+            for epic in report.epics.iter() {
+                log::info!("posting status summary on {}", epic.key);
+                let res = client
+                    .add_comment(&epic.key, &epic_comment(&report, epic))
+                    .await;
+                if let Err(e) = res {
+                    cache.close().await?;
+                    return Err(e);
                 }
             }
+            cache.close().await?;
         }
         Ok("exit => 0".to_string())
     }
+
+    async fn build_report(
+        params: &Parameters,
+        options: &ReportOptions,
+    ) -> Result<Report, Box<dyn std::error::Error>> {
+        let (client, cache) = create_client(params, options.refresh, options.dry_run)?;
+        let semaphore = Arc::new(Semaphore::new(
+            params
+                .max_concurrency
+                .unwrap_or(DEFAULT_MAX_CONCURRENCY)
+                .max(1),
+        ));
+        let res = async {
+            let epics = match &options.query {
+                ReportQuery::Issues(issues) => {
+                    fetch_issues(client.clone(), semaphore.clone(), issues.clone()).await?
+                }
+                ReportQuery::Jql(jql) => search_all(client.as_ref(), jql, None).await?,
+            };
+            let stories = collect_stories(client.clone(), semaphore, params, &epics).await?;
+            Ok::<_, Box<dyn std::error::Error>>((epics, stories))
+        }
+        .await
+        .map_err(JiraServiceError::from_boxed);
+        // closed on failures too, the worker opens the cache again for its next job
+        cache.close().await?;
+        let (epics, stories) = res?;
+        let mut keys = epics
+            .iter()
+            .map(|epic| epic.key.as_str())
+            .collect::<Vec<&str>>();
+        for node in stories.iter().flatten() {
+            node.keys(&mut keys);
+        }
+        let links =
+            BrowseLinks::new(&params.base_url, params.browse_url.as_deref()).with_projects(keys);
+        let epics = epics.into_iter().zip(stories).collect();
+
+        let curr_time = SystemTime::now();
+        let dt: DateTime<Utc> = curr_time.into();
+        Ok(Report::new(
+            params
+                .report_title
+                .clone()
+                .unwrap_or(DEFAULT_REPORT_TITLE.to_string()),
+            dt.format("%Y-%m-%d").to_string(),
+            &ReportContext {
+                window: options.window.clone(),
+                comments: params.comments.clone(),
+                links,
+                time_tracking: params.time_tracking.clone(),
+                story_points_field: params.story_points_field.clone(),
+            },
+            epics,
+        ))
+    }
+
+    async fn get_issue(
+        params: &Parameters,
+        key: &str,
+        refresh: bool,
+    ) -> Result<JiraResponse, Box<dyn std::error::Error>> {
+        let (client, cache) = create_client(params, refresh, false)?;
+        // the error is kept as a string so the future stays Send across the close
+        let res = client
+            .get_issue(key)
+            .await
+            .map_err(JiraServiceError::from_boxed);
+        cache.close().await?;
+        Ok(res?)
+    }
+
+    async fn search(
+        params: &Parameters,
+        jql: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<JiraResponse>, Box<dyn std::error::Error>> {
+        let (client, cache) = create_client(params, false, false)?;
+        let res = search_all(client.as_ref(), jql, limit)
+            .await
+            .map_err(JiraServiceError::from_boxed);
+        cache.close().await?;
+        Ok(res?)
+    }
+
+    async fn comment(
+        params: &Parameters,
+        key: &str,
+        body: &str,
+        dry_run: bool,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        if body.trim().is_empty() {
+            return Err(Box::from("comment body is empty"));
+        }
+        let (client, cache) = create_client(params, false, dry_run)?;
+        let res = client.add_comment(key, body).await;
+        cache.close().await?;
+        res?;
+        match dry_run {
+            true => Ok(format!("dry run, comment not sent to {}", key)),
+            false => Ok(format!("comment added to {}", key)),
+        }
+    }
+
+    async fn list_transitions(
+        params: &Parameters,
+        key: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let (client, cache) = create_client(params, false, false)?;
+        let res = client.get_transitions(key).await;
+        cache.close().await?;
+        let transitions = res?;
+        let mut table = format!("{:<8} {:<30} {}\n", "ID", "TRANSITION", "TO STATUS");
+        for transition in transitions.iter() {
+            table.push_str(&format!(
+                "{:<8} {:<30} {}\n",
+                transition.id,
+                transition.name,
+                transition
+                    .to
+                    .as_ref()
+                    .map(|to| to.name.as_str())
+                    .unwrap_or_default()
+            ));
+        }
+        Ok(table.trim_end().to_string())
+    }
+
+    async fn transition(
+        params: &Parameters,
+        key: &str,
+        transition: &str,
+        comment: Option<&str>,
+        dry_run: bool,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let (client, cache) = create_client(params, false, dry_run)?;
+        let res = transition_by_name(client.as_ref(), key, transition, comment).await;
+        cache.close().await?;
+        let name = res?;
+        match dry_run {
+            true => Ok(format!("dry run, {} not transitioned with {}", key, name)),
+            false => Ok(format!("{} transitioned with {}", key, name)),
+        }
+    }
+
+    async fn create(
+        params: &Parameters,
+        spec_path: &str,
+        dry_run: bool,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let spec = EpicSpec::read(spec_path)?;
+        let (client, cache) = create_client(params, false, dry_run)?;
+        let semaphore = Arc::new(Semaphore::new(
+            params
+                .max_concurrency
+                .unwrap_or(DEFAULT_MAX_CONCURRENCY)
+                .max(1),
+        ));
+        let res = create_stories(client, semaphore, &spec, resolve_api_version(params))
+            .await
+            .map_err(JiraServiceError::from_boxed);
+        cache.close().await?;
+        Ok(res?)
+    }
 }
 
-fn save_data(
+/// keys of the stories linked to the epic with the link as read from the epic
+fn story_links_of(jira: &JiraResponse, link_types: &[String]) -> Vec<(String, String)> {
+    jira.fields
+        .issuelinks
+        .iter()
+        .filter(|link| link.is_story_link(link_types))
+        .filter_map(|link| link.target())
+        .map(|(key, relation)| (key.to_string(), relation.to_string()))
+        .collect()
+}
+
+/// issue found while walking the hierarchy of an epic
+struct FoundIssue {
+    epic: usize,
+    /// index of the parent issue, None for the stories of the epic
+    parent: Option<usize>,
+    link: String,
     jira: JiraResponse,
-    jira_type: String,
-    file_name: String,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut file = OpenOptions::new()
-        .write(true)
-        .append(true)
-        .open(file_name.clone())?;
-
-    log::debug!("appending data to {}",file_name);
-
-    let mut data = String::new();
-    match jira_type.as_str() {
-        "EPIC" => {
-            data.push_str(&format!(
-                "## [EPIC] [{}](https://issues.redhat.com/browse/{}) {}",
-                jira.key,
-                jira.key,
-                jira.fields.summary.trim(),
-            ));
-            data.push_str(&format!(
-                "\n\n### Status : {}",
-                jira.fields.status.status_category.name.trim()
-            ));
-            data.push_str("\n\n### Description\n\n");
-            if jira.fields.description.is_some() {
-                let lines = jira
-                    .fields
-                    .description
-                    .as_ref()
-                    .unwrap()
-                    .split("\n")
-                    .collect::<Vec<&str>>();
-                for line in lines.iter() {
-                    data.push_str(&format!(
-                        "{}\n",
-                        line.replace("\r", "").replace("* ", "- ").trim()
-                    ));
+}
+
+/// stories of each epic (linked issues and children found with the child jql) with their
+/// subtasks walked to the configured depth, every level is fetched in one batch for all epics
+async fn collect_stories(
+    client: Arc<dyn JiraClient>,
+    semaphore: Arc<Semaphore>,
+    params: &Parameters,
+    epics: &[JiraResponse],
+) -> Result<Vec<Vec<StoryNode>>, Box<dyn std::error::Error>> {
+    let depth = params
+        .hierarchy
+        .subtask_depth
+        .unwrap_or(DEFAULT_SUBTASK_DEPTH);
+    let child_jql = params
+        .hierarchy
+        .child_jql
+        .clone()
+        .unwrap_or(DEFAULT_CHILD_JQL.to_string());
+    // keys already in the tree of each epic, a key seen again is a duplicate or a cycle
+    let mut visited = epics
+        .iter()
+        .map(|jira| HashSet::from([jira.key.clone()]))
+        .collect::<Vec<HashSet<String>>>();
+    let mut found: Vec<FoundIssue> = vec![];
+
+    let mut pending = vec![];
+    for (index, jira) in epics.iter().enumerate() {
+        for (key, link) in story_links_of(jira, &params.story_link_types) {
+            if visited[index].insert(key.clone()) {
+                pending.push((index, link, key));
+            }
+        }
+    }
+    let keys = pending.iter().map(|(_, _, key)| key.clone()).collect();
+    let issues = fetch_issues(client.clone(), semaphore.clone(), keys).await?;
+    for ((epic, link, _), jira) in pending.into_iter().zip(issues) {
+        found.push(FoundIssue {
+            epic,
+            parent: None,
+            link,
+            jira,
+        });
+    }
+
+    if !child_jql.trim().is_empty() {
+        for (index, epic) in epics.iter().enumerate() {
+            for jira in search_children(client.as_ref(), &child_jql, &epic.key).await {
+                if !visited[index].insert(jira.key.clone()) {
+                    continue;
+                }
+                let link = match &jira.fields.parent {
+                    Some(parent) if parent.key == epic.key => "is parent of",
+                    _ => "is epic of",
+                };
+                found.push(FoundIssue {
+                    epic: index,
+                    parent: None,
+                    link: link.to_string(),
+                    jira,
+                });
+            }
+        }
+    }
+
+    let mut frontier = (0..found.len()).collect::<Vec<usize>>();
+    for _ in 0..depth {
+        let mut pending = vec![];
+        for index in frontier {
+            let issue = &found[index];
+            for subtask in issue.jira.fields.subtasks.iter() {
+                if visited[issue.epic].insert(subtask.key.clone()) {
+                    pending.push((issue.epic, index, subtask.key.clone()));
+                } else {
+                    log::warn!(
+                        "{} is already in the tree of {} (cycle), not walked again",
+                        subtask.key,
+                        epics[issue.epic].key
+                    );
                 }
             }
-            data.push_str("\n\n### Stories\n\n");
-        }
-        "STORY" => {
-            data.push_str(&format!(
-                "\n**[{}](https://issues.redhat.com/browse/{}) {}**\n",
-                jira.key,
-                jira.key,
-                jira.fields.summary.trim(),
+        }
+        if pending.is_empty() {
+            break;
+        }
+        let keys = pending.iter().map(|(_, _, key)| key.clone()).collect();
+        let issues = fetch_issues(client.clone(), semaphore.clone(), keys).await?;
+        frontier = vec![];
+        for ((epic, parent, _), jira) in pending.into_iter().zip(issues) {
+            let link = format!("subtask of {}", found[parent].jira.key);
+            frontier.push(found.len());
+            found.push(FoundIssue {
+                epic,
+                parent: Some(parent),
+                link,
+                jira,
+            });
+        }
+    }
+
+    // parents always come before their subtasks, so the trees are assembled from the end
+    let mut stories: Vec<Vec<StoryNode>> = vec![vec![]; epics.len()];
+    let mut nodes = found
+        .into_iter()
+        .map(|issue| {
+            let node = StoryNode {
+                link: issue.link,
+                jira: issue.jira,
+                subtasks: vec![],
+            };
+            (issue.epic, issue.parent, node)
+        })
+        .collect::<Vec<(usize, Option<usize>, StoryNode)>>();
+    while let Some((epic, parent, mut node)) = nodes.pop() {
+        node.subtasks.reverse();
+        match parent {
+            Some(parent) => nodes[parent].2.subtasks.push(node),
+            None => stories[epic].push(node),
+        }
+    }
+    for epic in stories.iter_mut() {
+        epic.reverse();
+    }
+    Ok(stories)
+}
+
+/// children of the epic found with the child jql, retried with the parent clause alone when
+/// jira rejects the query, the linked stories still apply when both searches fail
+async fn search_children(client: &dyn JiraClient, child_jql: &str, key: &str) -> Vec<JiraResponse> {
+    let jql = child_jql.replace("{key}", key);
+    let err = match search_all(client, &jql, None).await {
+        Ok(children) => return children,
+        Err(e) => e,
+    };
+    if child_jql == PARENT_CHILD_JQL {
+        log::warn!("children of {} not searched : {}", key, err);
+        return vec![];
+    }
+    log::warn!(
+        "children of {} not searched with {} : {}, retrying with the parent field",
+        key,
+        jql,
+        err
+    );
+    match search_all(client, &PARENT_CHILD_JQL.replace("{key}", key), None).await {
+        Ok(children) => children,
+        Err(e) => {
+            log::warn!("children of {} not searched : {}", key, e);
+            vec![]
+        }
+    }
+}
+
+/// creates the stories of the spec that do not exist yet (matched by summary) and links them
+/// to the epic
+///
+/// stories are matched against the issues linked to the epic and against the issues of the
+/// project found with a summary search, an existing story that is not linked yet (a run that
+/// stopped between the create and the link requests) is linked instead of created again
+async fn create_stories(
+    client: Arc<dyn JiraClient>,
+    semaphore: Arc<Semaphore>,
+    spec: &EpicSpec,
+    api_version: u32,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let epic = client.get_issue(&spec.epic).await?;
+    // any linked issue counts here so a rerun never duplicates a story
+    let linked_keys = epic
+        .fields
+        .issuelinks
+        .iter()
+        .filter_map(|link| link.target())
+        .map(|(key, _)| key.to_string())
+        .collect::<Vec<String>>();
+    // summary -> key and whether the issue is linked to the epic
+    let mut existing: HashMap<String, (String, bool)> = HashMap::new();
+    for story in fetch_issues(client.clone(), semaphore, linked_keys).await? {
+        existing.insert(summary_key(&story.fields.summary), (story.key, true));
+    }
+    for story in spec.stories.iter() {
+        let summary = summary_key(&story.summary);
+        if existing.contains_key(&summary) {
+            continue;
+        }
+        let Some(jql) = summary_jql(&spec.project(), &story.summary) else {
+            continue;
+        };
+        // the text search is fuzzy, only exact summaries count
+        for issue in search_all(client.as_ref(), &jql, None).await? {
+            if summary_key(&issue.fields.summary) == summary && issue.key != spec.epic {
+                existing
+                    .entry(summary.clone())
+                    .or_insert((issue.key, false));
+            }
+        }
+    }
+    let mut lines = vec![];
+    let mut pending = vec![];
+    for story in spec.stories.iter() {
+        let summary = summary_key(&story.summary);
+        match existing.get_mut(&summary) {
+            Some((key, true)) => lines.push(format!("skipped {} {} (exists)", key, story.summary)),
+            Some((key, linked)) => {
+                client
+                    .link_issues(&spec.link_type(), &spec.epic, key)
+                    .await?;
+                lines.push(format!("linked  {} {} (exists)", key, story.summary));
+                *linked = true;
+            }
+            None => {
+                // the placeholder also skips a repeated summary inside the spec
+                existing.insert(summary, ("-".to_string(), true));
+                pending.push(story);
+            }
+        }
+    }
+    if pending.is_empty() {
+        lines.push(format!("nothing to create for {}", spec.epic));
+        return Ok(lines.join("\n"));
+    }
+    let issues = pending
+        .iter()
+        .map(|story| story_fields(spec, story, api_version))
+        .collect::<Vec<Value>>();
+    let res = client.create_issues(issues).await?;
+    // created issues come back in request order without the failed elements
+    let failed = res
+        .errors
+        .iter()
+        .map(|error| (error.failed_element_number as usize, error))
+        .collect::<HashMap<usize, &BulkCreateError>>();
+    let mut created = res.issues.iter();
+    let mut has_errors = false;
+    for (index, story) in pending.iter().enumerate() {
+        if let Some(error) = failed.get(&index) {
+            has_errors = true;
+            lines.push(format!(
+                "failed  {} : {} {}",
+                story.summary, error.status, error.element_errors
             ));
-            data.push_str(&format!(
-                "\n- **Status : {}**\n",
-                jira.fields.status.status_category.name.trim()
+            continue;
+        }
+        let Some(issue) = created.next() else {
+            has_errors = true;
+            lines.push(format!(
+                "failed  {} : missing from the response",
+                story.summary
             ));
-            if jira
-                .fields
-                .status
-                .status_category
-                .name
-                .contains("In Progress")
-            {
-                data.push_str("\n- **Description**\n\n");
-                if jira.fields.description.is_some() {
-                    let lines = jira
-                        .fields
-                        .description
-                        .as_ref()
-                        .unwrap()
-                        .split("\n")
-                        .collect::<Vec<&str>>();
-                    for line in lines.iter() {
-                        data.push_str(&format!(
-                            "\t{}\n",
-                            line.replace("\r", "").replace("* ", "- ").trim()
-                        ));
-                    }
-                }
-                data.push_str("\n- **Comments**\n");
-                for comment in jira.fields.comment.comments {
-                    data.push_str(&format!(
-                        "\n\t- {} {}",
-                        comment.author.name, comment.created,
-                    ));
-                    let lines = comment.body.split("\n").collect::<Vec<&str>>();
-                    for line in lines.iter() {
-                        data.push_str(&format!(
-                            "\t{}\n",
-                            line.replace("\r", "")
-                                .replace("* ", "- ")
-                                .replace("{code:java}", "```bash")
-                                .replace("{code}", "```")
-                        ));
-                    }
-                }
-                data.push_str("\n");
+            continue;
+        };
+        client
+            .link_issues(&spec.link_type(), &spec.epic, &issue.key)
+            .await?;
+        lines.push(format!("created {} {}", issue.key, story.summary));
+    }
+    match has_errors {
+        true => Err(Box::from(lines.join("\n"))),
+        false => Ok(lines.join("\n")),
+    }
+}
+
+/// summaries are matched trimmed and case insensitive
+fn summary_key(summary: &str) -> String {
+    summary.trim().to_lowercase()
+}
+
+/// jql finding the issues of the project whose summary contains the summary as a phrase,
+/// None when the summary has no searchable words
+fn summary_jql(project: &str, summary: &str) -> Option<String> {
+    // lucene operators and quotes would break the text search and are not indexed anyway
+    let words = summary
+        .chars()
+        .map(|c| match "+-&|!(){}[]^~*?\\:\"/".contains(c) {
+            true => ' ',
+            false => c,
+        })
+        .collect::<String>();
+    let words = words.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if words.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "project = \"{}\" AND summary ~ \"\\\"{}\\\"\"",
+        project.replace(['"', '\\'], ""),
+        words
+    ))
+}
+
+/// bulk create entry ({"fields": {..}}) of a story, users are named on v2 and referenced by
+/// account id on v3 (jira cloud has no user names)
+fn story_fields(spec: &EpicSpec, story: &StorySpec, api_version: u32) -> Value {
+    let mut fields = json!({
+        "project": { "key": spec.project() },
+        "issuetype": { "name": spec.issue_type() },
+        "summary": story.summary.trim(),
+    });
+    if let Some(description) = &story.description {
+        fields["description"] = json!(description);
+    }
+    if !story.labels.is_empty() {
+        fields["labels"] = json!(story.labels);
+    }
+    if !story.components.is_empty() {
+        fields["components"] = story
+            .components
+            .iter()
+            .map(|name| json!({ "name": name }))
+            .collect();
+    }
+    if let Some(assignee) = &story.assignee {
+        fields["assignee"] = match api_version {
+            3 => json!({ "accountId": assignee }),
+            _ => json!({ "name": assignee }),
+        };
+    }
+    json!({ "fields": fields })
+}
+
+/// matches the transition by id or (case insensitive) name, or by the name of its target status
+async fn transition_by_name(
+    client: &dyn JiraClient,
+    key: &str,
+    transition: &str,
+    comment: Option<&str>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let transitions = client.get_transitions(key).await?;
+    let found = transitions.iter().find(|t| {
+        t.id == transition
+            || t.name.eq_ignore_ascii_case(transition)
+            || t.to
+                .as_ref()
+                .is_some_and(|to| to.name.eq_ignore_ascii_case(transition))
+    });
+    let Some(found) = found else {
+        let names = transitions
+            .iter()
+            .map(|t| t.name.clone())
+            .collect::<Vec<String>>();
+        return Err(Box::from(format!(
+            "transition {} is not available for {} (available : {})",
+            transition,
+            key,
+            names.join(", ")
+        )));
+    };
+    client.transition_issue(key, &found.id, comment).await?;
+    Ok(found.name.clone())
+}
+
+/// requested formats, falling back to the config formats (markdown when neither is set or
+/// the config lists none)
+pub fn resolve_formats(params: &Parameters, formats: &[ReportFormat]) -> Vec<ReportFormat> {
+    match (formats.is_empty(), &params.formats) {
+        (false, _) => formats.to_vec(),
+        (true, Some(configured)) if !configured.is_empty() => configured.clone(),
+        (true, _) => vec![ReportFormat::Markdown],
+    }
+}
+
+/// format of a single document when the request names none
+pub fn default_format(params: &Parameters) -> ReportFormat {
+    resolve_formats(params, &[])
+        .first()
+        .copied()
+        .unwrap_or(ReportFormat::Markdown)
+}
+
+/// api_version, else the version in base_url, else DEFAULT_API_VERSION
+pub fn resolve_api_version(params: &Parameters) -> u32 {
+    params
+        .api_version
+        .or(url_api_version(&params.base_url))
+        .unwrap_or(DEFAULT_API_VERSION)
+}
+
+/// caching client and the cache it writes to (closed once the requests are done)
+type CachedClient = (Arc<dyn JiraClient>, Arc<IssueCache>);
+
+/// jira client (fixtures in test mode) wrapped by the issue cache
+fn create_client(
+    params: &Parameters,
+    refresh: bool,
+    dry_run: bool,
+) -> Result<CachedClient, Box<dyn std::error::Error>> {
+    let client: Arc<dyn JiraClient> = match params.test {
+        true => {
+            log::info!("mode        : testing");
+            let fixtures_dir = params
+                .fixtures_dir
+                .clone()
+                .unwrap_or("docs/fixtures".to_string());
+            Arc::new(FakeJiraClient::new(fixtures_dir))
+        }
+        false => {
+            log::info!("mode        : executing");
+            if params.base_url.is_empty() {
+                return Err(Box::new(JiraServiceError::Config {
+                    path: "base_url".to_string(),
+                    message: "not set (required unless test is set)".to_string(),
+                }));
             }
+            let credentials = Credentials::resolve(params)?;
+            log::info!("auth        : {}", credentials.describe());
+            let api_version = resolve_api_version(params);
+            if !(2..=3).contains(&api_version) {
+                return Err(Box::new(JiraServiceError::Config {
+                    path: "api_version".to_string(),
+                    message: format!("unsupported version {} (expected 2 or 3)", api_version),
+                }));
+            }
+            log::info!("api version : {}", api_version);
+            Arc::new(
+                RestJiraClient::new(params.base_url.clone(), credentials)
+                    .with_dry_run(dry_run)
+                    .with_retry(RetryPolicy::new(params))
+                    .with_api_version(api_version),
+            )
+        }
+    };
+    let cache = Arc::new(IssueCache::open_for(params, refresh)?);
+    let client: Arc<dyn JiraClient> = Arc::new(CachingJiraClient::new(client, cache.clone()));
+    Ok((client, cache))
+}
+
+/// {working_dir}/staging/{document_name} with the extension of the rendered format
+fn document_path(params: &Parameters, extension: &str) -> String {
+    let path = Path::new(&params.document_name);
+    let name = match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if ext == extension => params.document_name.clone(),
+        _ => path.with_extension(extension).to_string_lossy().to_string(),
+    };
+    format!("{}/staging/{}", params.working_dir, name)
+}
+
+/// fetches issues concurrently (bounded by the semaphore), the result keeps the order of keys
+async fn fetch_issues(
+    client: Arc<dyn JiraClient>,
+    semaphore: Arc<Semaphore>,
+    keys: Vec<String>,
+) -> Result<Vec<JiraResponse>, Box<dyn std::error::Error>> {
+    let mut tasks = JoinSet::new();
+    for (index, key) in keys.iter().enumerate() {
+        let client = client.clone();
+        let semaphore = semaphore.clone();
+        let key = key.clone();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            log::debug!("[fetch_issues] fetching {}", key);
+            let res = client
+                .get_issue(&key)
+                .await
+                .map_err(JiraServiceError::from_boxed);
+            (index, res)
+        });
+    }
+    let mut results: Vec<Option<JiraResponse>> = vec![None; keys.len()];
+    while let Some(task) = tasks.join_next().await {
+        let (index, res) = task?;
+        results[index] = Some(res?);
+    }
+    Ok(results.into_iter().flatten().collect())
+}
+
+/// follows startAt/maxResults/total until every page of the search is read (or limit issues)
+async fn search_all(
+    client: &dyn JiraClient,
+    jql: &str,
+    limit: Option<usize>,
+) -> Result<Vec<JiraResponse>, Box<dyn std::error::Error>> {
+    let mut issues = vec![];
+    let mut start_at = 0;
+    let mut page_token: Option<String> = None;
+    loop {
+        log::debug!("[search_all] jql {} start_at {}", jql, start_at);
+        let page_size = match limit {
+            Some(limit) => SEARCH_PAGE_SIZE.min((limit - issues.len()) as i64),
+            None => SEARCH_PAGE_SIZE,
+        };
+        let page = client
+            .search(jql, start_at, page_token.as_deref(), page_size)
+            .await?;
+        log::debug!(
+            "[search_all] page start_at {} returned {} of {}",
+            page.start_at,
+            page.issues.len(),
+            page.total
+        );
+        let count = page.issues.len() as i64;
+        issues.extend(page.issues);
+        start_at += count;
+        if let Some(limit) = limit
+            && issues.len() >= limit
+        {
+            issues.truncate(limit);
+            break;
+        }
+        // the v3 search pages with a token and returns no total
+        if let Some(token) = page.next_page_token
+            && count > 0
+        {
+            page_token = Some(token);
+            continue;
         }
-        _ => {}
+        // an empty page guards against a total that changes while paging
+        if count == 0 || start_at >= page.total {
+            break;
+        }
+    }
+    Ok(issues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::jira::exit_code;
+    use crate::handlers::report::OverviewRow;
+
+    /// test mode over docs/fixtures, the cache is written to a scratch directory
+    fn fixture_params(name: &str) -> Parameters {
+        let working_dir =
+            std::env::temp_dir().join(format!("jira-service-{}-{}", name, std::process::id()));
+        serde_json::from_value(json!({
+            "test": true,
+            "fixtures_dir": concat!(env!("CARGO_MANIFEST_DIR"), "/docs/fixtures"),
+            "working_dir": working_dir.to_string_lossy(),
+            "base_url": "https://jira.example.com/rest/api/2/issue/",
+            "story_link_types": ["Relates"],
+            "story_points_field": "customfield_12310243",
+        }))
+        .unwrap()
+    }
+
+    fn report_options(keys: &[&str]) -> ReportOptions {
+        ReportOptions {
+            query: ReportQuery::Issues(keys.iter().map(|key| key.to_string()).collect()),
+            refresh: false,
+            formats: vec![],
+            window: ReportWindow::new(None, None),
+            comment_epics: false,
+            dry_run: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn report_from_fixtures() {
+        let params = fixture_params("report");
+        let report = Service::build_report(&params, &report_options(&["WRIG-1", "WRIG-4"]))
+            .await
+            .unwrap();
+        let _ = fs::remove_dir_all(&params.working_dir);
+
+        let epics = report
+            .epics
+            .iter()
+            .map(|epic| epic.key.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(epics, ["WRIG-1", "WRIG-4"]);
+        let stories = |index: usize| {
+            report.epics[index]
+                .stories
+                .iter()
+                .map(|story| (story.key.as_str(), story.link.as_str()))
+                .collect::<Vec<(&str, &str)>>()
+        };
+        // linked stories first, then the children found with the child jql
+        assert_eq!(
+            stories(0),
+            [("WRIG-2", "relates to"), ("WRIG-3", "relates to")]
+        );
+        assert_eq!(
+            stories(1),
+            [("WRIG-5", "relates to"), ("WRIG-6", "is parent of")]
+        );
+        let subtasks = &report.epics[1].stories[1].subtasks;
+        assert_eq!(subtasks.len(), 1);
+        assert_eq!((subtasks[0].key.as_str(), subtasks[0].depth), ("WRIG-7", 1));
+        assert_eq!(subtasks[0].indent, "\t");
+
+        let counts = |row: &OverviewRow| {
+            (
+                row.stories,
+                row.to_do,
+                row.in_progress,
+                row.done,
+                row.percent,
+            )
+        };
+        assert_eq!(counts(&report.overview[0]), (2, 0, 1, 1, 50));
+        assert_eq!(counts(&report.overview[1]), (2, 1, 1, 0, 0));
+        assert_eq!(counts(&report.total), (4, 1, 2, 1, 25));
+        assert_eq!(report.overview[0].points, "3 of 8");
+        assert_eq!(report.total.summary, "2 epics");
+
+        let document = get_renderer(ReportFormat::Markdown, &params)
+            .unwrap()
+            .render(&report)
+            .unwrap();
+        for key in [
+            "WRIG-1", "WRIG-2", "WRIG-3", "WRIG-4", "WRIG-5", "WRIG-6", "WRIG-7",
+        ] {
+            assert!(
+                document.contains(&format!(
+                    "[{}](https://jira.example.com/browse/{})",
+                    key, key
+                )),
+                "{} missing from the report",
+                key
+            );
+        }
+        // subtasks are nested below the story status and link items
+        assert!(document.contains("- Link : is parent of\n\t- [WRIG-7]("));
+    }
+
+    #[test]
+    fn empty_config_formats_fall_back_to_markdown() {
+        let mut params = fixture_params("formats");
+        params.formats = Some(vec![]);
+        assert_eq!(resolve_formats(&params, &[]), [ReportFormat::Markdown]);
+        assert_eq!(default_format(&params), ReportFormat::Markdown);
+        params.formats = Some(vec![ReportFormat::Html, ReportFormat::Csv]);
+        assert_eq!(default_format(&params), ReportFormat::Html);
+        assert_eq!(
+            resolve_formats(&params, &[ReportFormat::Json]),
+            [ReportFormat::Json]
+        );
+    }
+
+    #[tokio::test]
+    async fn create_stories_skips_and_links_existing_stories() {
+        let client: Arc<dyn JiraClient> = Arc::new(FakeJiraClient::new(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/docs/fixtures").to_string(),
+        ));
+        let spec = |epic: &str, summaries: &[&str]| EpicSpec {
+            epic: epic.to_string(),
+            stories: summaries
+                .iter()
+                .map(|summary| StorySpec {
+                    summary: summary.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let run = async |spec: EpicSpec| {
+            create_stories(client.clone(), Arc::new(Semaphore::new(2)), &spec, 2)
+                .await
+                .unwrap()
+        };
+        // linked to the epic
+        let lines = run(spec("WRIG-1", &["sign release artifacts ", "Publish SBOM"])).await;
+        assert_eq!(
+            lines.lines().collect::<Vec<&str>>(),
+            [
+                "skipped WRIG-2 sign release artifacts  (exists)",
+                "created WRIG-1001 Publish SBOM",
+            ]
+        );
+        // found by the summary search but not linked yet, a repeated summary is skipped
+        let lines = run(spec("WRIG-2", &["observability", "Observability"])).await;
+        assert_eq!(
+            lines.lines().collect::<Vec<&str>>(),
+            [
+                "linked  WRIG-4 observability (exists)",
+                "skipped WRIG-4 Observability (exists)",
+                "nothing to create for WRIG-2",
+            ]
+        );
+    }
+
+    #[test]
+    fn summary_search_and_assignee() {
+        assert_eq!(
+            summary_jql("WRIG", "Fix \"login\" (SSO) - part 2?").as_deref(),
+            Some("project = \"WRIG\" AND summary ~ \"\\\"Fix login SSO part 2\\\"\"")
+        );
+        assert_eq!(summary_jql("WRIG", " ?? "), None);
+        let spec = EpicSpec {
+            epic: "WRIG-1".to_string(),
+            ..Default::default()
+        };
+        let story = StorySpec {
+            summary: "Story".to_string(),
+            assignee: Some("jdoe".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            story_fields(&spec, &story, 2)["fields"]["assignee"],
+            json!({ "name": "jdoe" })
+        );
+        assert_eq!(
+            story_fields(&spec, &story, 3)["fields"]["assignee"],
+            json!({ "accountId": "jdoe" })
+        );
+    }
+
+    #[tokio::test]
+    async fn children_found_by_parent_without_epic_link() {
+        let client: Arc<dyn JiraClient> = Arc::new(
+            FakeJiraClient::new(concat!(env!("CARGO_MANIFEST_DIR"), "/docs/fixtures").to_string())
+                .without_epic_link(),
+        );
+        let params = fixture_params("epic-link");
+        let epic = client.get_issue("WRIG-4").await.unwrap();
+        let stories = collect_stories(client, Arc::new(Semaphore::new(2)), &params, &[epic])
+            .await
+            .unwrap();
+        let stories = stories[0]
+            .iter()
+            .map(|story| (story.jira.key.as_str(), story.link.as_str()))
+            .collect::<Vec<(&str, &str)>>();
+        assert_eq!(
+            stories,
+            [("WRIG-5", "relates to"), ("WRIG-6", "is parent of")]
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_fixture_is_not_found() {
+        let params = fixture_params("missing");
+        let err = Service::build_report(&params, &report_options(&["WRIG-99"]))
+            .await
+            .unwrap_err();
+        let _ = fs::remove_dir_all(&params.working_dir);
+        assert_eq!(exit_code(err.as_ref()), 5);
+    }
+
+    fn fake_client() -> FakeJiraClient {
+        FakeJiraClient::new(concat!(env!("CARGO_MANIFEST_DIR"), "/docs/fixtures").to_string())
+    }
+
+    fn keys(issues: &[JiraResponse]) -> Vec<&str> {
+        issues.iter().map(|jira| jira.key.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn search_pages_until_total() {
+        let client = fake_client().with_page_size(1);
+        let issues = search_all(&client, "project = WRIG", None).await.unwrap();
+        assert_eq!(keys(&issues), ["WRIG-1", "WRIG-4"]);
+        assert_eq!(client.calls(), ["search 0", "search 1"]);
+    }
+
+    #[tokio::test]
+    async fn search_stops_on_an_empty_page() {
+        // jira counts 5 issues but returns only 2
+        let client = fake_client().with_page_size(1).with_total(5);
+        let issues = search_all(&client, "project = WRIG", None).await.unwrap();
+        assert_eq!(keys(&issues), ["WRIG-1", "WRIG-4"]);
+        assert_eq!(client.calls(), ["search 0", "search 1", "search 2"]);
+    }
+
+    #[tokio::test]
+    async fn search_stops_at_the_limit() {
+        let client = fake_client();
+        let issues = search_all(&client, "project = WRIG", Some(1))
+            .await
+            .unwrap();
+        assert_eq!(keys(&issues), ["WRIG-1"]);
+        assert_eq!(client.calls(), ["search 0"]);
+
+        let client = fake_client().with_page_size(1);
+        let issues = search_all(&client, "project = WRIG", Some(5))
+            .await
+            .unwrap();
+        assert_eq!(keys(&issues), ["WRIG-1", "WRIG-4"]);
+    }
+
+    #[tokio::test]
+    async fn search_follows_page_tokens() {
+        let client = fake_client().with_page_size(1).with_page_tokens();
+        let issues = search_all(&client, "project = WRIG", None).await.unwrap();
+        assert_eq!(keys(&issues), ["WRIG-1", "WRIG-4"]);
+        // the v3 search has no total, the last page has no token
+        assert_eq!(client.calls(), ["search 0", "search 1"]);
+    }
+
+    /// fields of the WRIG-2 fixture changed by the closure
+    fn fields_with(change: impl FnOnce(&mut serde_json::Map<String, Value>)) -> Fields {
+        let data = fs::read_to_string(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/docs/fixtures/WRIG-2.json"
+        ))
+        .unwrap();
+        let mut jira: Value = serde_json::from_str(&data).unwrap();
+        change(jira["fields"].as_object_mut().unwrap());
+        serde_json::from_value::<JiraResponse>(jira).unwrap().fields
+    }
+
+    const OPTIONAL_FIELDS: [&str; 8] = [
+        "assignee",
+        "duedate",
+        "timetracking",
+        "fixVersions",
+        "versions",
+        "components",
+        "subtasks",
+        "attachment",
+    ];
+
+    #[test]
+    fn fields_read_null_values() {
+        let fields = fields_with(|fields| {
+            for name in OPTIONAL_FIELDS {
+                fields.insert(name.to_string(), Value::Null);
+            }
+        });
+        assert_eq!(fields.assignee, None);
+        assert_eq!(fields.duedate, None);
+        assert_eq!(fields.timetracking, Timetracking::default());
+        assert!(fields.fix_versions.is_empty() && fields.versions.is_empty());
+        assert!(fields.components.is_empty() && fields.subtasks.is_empty());
+        assert!(fields.attachment.is_empty());
+        assert!(
+            OPTIONAL_FIELDS
+                .iter()
+                .all(|name| !fields.custom_fields.contains_key(*name))
+        );
+    }
+
+    #[test]
+    fn fields_read_missing_values() {
+        let fields = fields_with(|fields| {
+            for name in OPTIONAL_FIELDS {
+                fields.remove(name);
+            }
+        });
+        assert_eq!(fields.assignee, None);
+        assert_eq!(fields.timetracking, Timetracking::default());
+        assert!(fields.fix_versions.is_empty() && fields.components.is_empty());
+        assert_eq!(fields.summary, fields_with(|_| {}).summary);
+    }
+
+    #[test]
+    fn custom_fields_are_kept_apart() {
+        let fields = fields_with(|fields| {
+            fields.insert("customfield_10016".to_string(), json!(5));
+            fields.insert("customfield_10020".to_string(), Value::Null);
+        });
+        assert_eq!(fields.custom_fields["customfield_10016"], json!(5));
+        assert_eq!(fields.custom_fields["customfield_10020"], Value::Null);
+        assert!(fields.custom_fields.contains_key("customfield_12310243"));
+        // typed fields are not copied into the custom fields
+        for name in [
+            "summary",
+            "status",
+            "assignee",
+            "fixVersions",
+            "timetracking",
+        ] {
+            assert!(!fields.custom_fields.contains_key(name), "{}", name);
+        }
+        assert_eq!(fields.assignee.unwrap().name, "asmith");
+        assert_eq!(fields.fix_versions[0].name, "1.2.0");
+        assert_eq!(fields.timetracking.time_spent_seconds, Some(28800));
+    }
+
+    /// blocks link with the other issue on the given side (outwardIssue, inwardIssue)
+    fn blocks(side: &str, key: &str) -> IssueLink {
+        let mut link = json!({
+            "id": "10",
+            "type": { "name": "Blocks", "inward": "is blocked by", "outward": "blocks" },
+        });
+        link[side] = json!({ "key": key });
+        serde_json::from_value(link).unwrap()
+    }
+
+    fn types(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn link_targets() {
+        assert_eq!(
+            blocks("outwardIssue", "WRIG-2").target(),
+            Some(("WRIG-2", "blocks"))
+        );
+        assert_eq!(
+            blocks("inwardIssue", "WRIG-3").target(),
+            Some(("WRIG-3", "is blocked by"))
+        );
+        // the type name stands in for a missing description
+        let parent: IssueLink = serde_json::from_value(json!({
+            "id": "11",
+            "type": { "name": "Parent" },
+            "outwardIssue": { "key": "WRIG-4" },
+        }))
+        .unwrap();
+        assert_eq!(parent.target(), Some(("WRIG-4", "Parent")));
+        let dangling: IssueLink = serde_json::from_value(json!({ "id": "12" })).unwrap();
+        assert_eq!(dangling.target(), None);
+        assert!(!dangling.is_story_link(&[]));
+        assert!(!dangling.is_story_link(&types(&["Blocks"])));
+    }
+
+    #[test]
+    fn story_links_by_type_or_description() {
+        let outward = blocks("outwardIssue", "WRIG-2");
+        let inward = blocks("inwardIssue", "WRIG-3");
+        // the type name matches both sides, ignoring case
+        assert!(outward.is_story_link(&types(&["blocks"])));
+        assert!(inward.is_story_link(&types(&["BLOCKS"])));
+        // a description only matches the side it is read from
+        assert!(inward.is_story_link(&types(&["Is Blocked By"])));
+        assert!(!outward.is_story_link(&types(&["is blocked by"])));
+        assert!(!inward.is_story_link(&types(&["relates", "relates to"])));
+    }
+
+    #[test]
+    fn story_links_without_link_types() {
+        assert!(blocks("outwardIssue", "WRIG-2").is_story_link(&[]));
+        assert!(!blocks("inwardIssue", "WRIG-3").is_story_link(&[]));
+    }
+
+    #[tokio::test]
+    async fn transitions_by_id_name_or_status() {
+        for (transition, id, name) in [
+            ("21", "21", "Start Progress"),
+            ("start progress", "21", "Start Progress"),
+            ("IN PROGRESS", "21", "Start Progress"),
+            ("done", "31", "Resolve"),
+            ("To Do", "11", "To Do"),
+        ] {
+            let client = fake_client();
+            let found = transition_by_name(&client, "WRIG-2", transition, Some("moving on"))
+                .await
+                .unwrap();
+            assert_eq!(found, name);
+            assert_eq!(
+                client.calls(),
+                [
+                    "transitions of WRIG-2".to_string(),
+                    format!("transition WRIG-2 with {}\nmoving on", id)
+                ]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn unknown_transitions_list_the_available_ones() {
+        let client = fake_client();
+        let err = transition_by_name(&client, "WRIG-2", "Reopen", None)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "transition Reopen is not available for WRIG-2 (available : To Do, Start Progress, Resolve)"
+        );
+        // nothing is sent
+        assert_eq!(client.calls(), ["transitions of WRIG-2"]);
+
+        let err = transition_by_name(&client, "WRIG-99", "Resolve", None)
+            .await
+            .unwrap_err();
+        assert_eq!(exit_code(err.as_ref()), 5);
+    }
+
+    #[tokio::test]
+    async fn comments_need_a_body() {
+        let params = fixture_params("comment");
+        let err = Service::comment(&params, "WRIG-1", " \n", false)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "comment body is empty");
+        let _ = fs::remove_dir_all(&params.working_dir);
+    }
+
+    #[tokio::test]
+    async fn comments_and_transitions_in_test_mode() {
+        let params = fixture_params("write");
+        let res = Service::comment(&params, "WRIG-1", "status update", true).await;
+        assert_eq!(res.unwrap(), "dry run, comment not sent to WRIG-1");
+        let res = Service::transition(&params, "WRIG-2", "done", None, false).await;
+        assert_eq!(res.unwrap(), "WRIG-2 transitioned with Resolve");
+        let err = Service::comment(&params, "WRIG-99", "status update", false)
+            .await
+            .unwrap_err();
+        let _ = fs::remove_dir_all(&params.working_dir);
+        assert_eq!(exit_code(err.as_ref()), 5);
     }
-    file.write_all(data.as_bytes())?;
-    Ok(())
 }
@@ -0,0 +1,304 @@
+// module spec
+//
+// story specs used by the create command, read from yaml (.yaml, .yml) or markdown (.md)
+//
+// markdown specs start with the epic key as the title, optional settings follow, every
+// second level heading is a story summary with optional settings and the description
+//
+// story settings are the lines right below the heading, the first blank line or a line that
+// is not a known setting starts the description, so a description line such as labels: foo
+// is kept as text once a blank line separates it from the heading
+//
+// # WRIG-1
+// project: WRIG
+//
+// ## Sign release artifacts
+// labels: security, release
+// components: build
+// assignee: jdoe
+//
+// Description in jira wiki markup
+
+use crate::error::jira::JiraServiceError;
+use serde_derive::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// link type used between the epic and its stories when the spec does not set one
+const DEFAULT_LINK_TYPE: &str = "Relates";
+
+/// issue type of the created stories when the spec does not set one
+const DEFAULT_ISSUE_TYPE: &str = "Story";
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct EpicSpec {
+    pub epic: String,
+    /// project of the new stories, defaults to the project of the epic
+    #[serde(default)]
+    pub project: Option<String>,
+    #[serde(default)]
+    pub issue_type: Option<String>,
+    #[serde(default)]
+    pub link_type: Option<String>,
+    #[serde(default)]
+    pub stories: Vec<StorySpec>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StorySpec {
+    pub summary: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub components: Vec<String>,
+    #[serde(default)]
+    pub assignee: Option<String>,
+}
+
+impl EpicSpec {
+    pub fn read(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let data = fs::read_to_string(path).map_err(|e| JiraServiceError::io(path, e))?;
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        let spec = match extension.as_str() {
+            "yaml" | "yml" => {
+                serde_yaml::from_str(&data).map_err(|e| JiraServiceError::config(path, e))?
+            }
+            "md" | "markdown" => {
+                parse_markdown(&data).map_err(|e| JiraServiceError::config(path, e))?
+            }
+            _ => {
+                return Err(Box::new(JiraServiceError::config(
+                    path,
+                    "unsupported extension (expected .yaml, .yml or .md)",
+                )));
+            }
+        };
+        spec.validate()
+            .map_err(|e| JiraServiceError::config(path, e))?;
+        Ok(spec)
+    }
+
+    pub fn project(&self) -> String {
+        match &self.project {
+            Some(project) => project.clone(),
+            None => self
+                .epic
+                .split_once('-')
+                .map(|(project, _)| project.to_string())
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn issue_type(&self) -> String {
+        self.issue_type
+            .clone()
+            .unwrap_or(DEFAULT_ISSUE_TYPE.to_string())
+    }
+
+    pub fn link_type(&self) -> String {
+        self.link_type
+            .clone()
+            .unwrap_or(DEFAULT_LINK_TYPE.to_string())
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.epic.trim().is_empty() {
+            return Err("epic key is missing".to_string());
+        }
+        if self.stories.is_empty() {
+            return Err("no stories".to_string());
+        }
+        for (index, story) in self.stories.iter().enumerate() {
+            if story.summary.trim().is_empty() {
+                return Err(format!("story {} has no summary", index + 1));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_markdown(data: &str) -> Result<EpicSpec, String> {
+    let mut spec = EpicSpec::default();
+    let mut current: Option<StorySpec> = None;
+    // settings are only read right below the heading, before a blank or a description line
+    let mut in_description = false;
+    let mut description: Vec<&str> = vec![];
+    for line in data.lines() {
+        let trimmed = line.trim();
+        if let Some(summary) = trimmed.strip_prefix("## ") {
+            if let Some(story) = current.take() {
+                spec.stories.push(finish_story(story, &description));
+            }
+            current = Some(StorySpec {
+                summary: summary.trim().to_string(),
+                ..Default::default()
+            });
+            in_description = false;
+            description.clear();
+            continue;
+        }
+        if let Some(epic) = trimmed.strip_prefix("# ") {
+            if current.is_some() {
+                return Err("the epic title must come before the stories".to_string());
+            }
+            spec.epic = epic.trim().to_string();
+            continue;
+        }
+        match current.as_mut() {
+            Some(story) => {
+                if !in_description {
+                    if trimmed.is_empty() {
+                        in_description = true;
+                        continue;
+                    }
+                    // anything that is not a known setting starts the description
+                    match setting(trimmed) {
+                        Some((name, value)) if name == "labels" => {
+                            story.labels = list(value);
+                            continue;
+                        }
+                        Some((name, value)) if name == "components" => {
+                            story.components = list(value);
+                            continue;
+                        }
+                        Some((name, value)) if name == "assignee" => {
+                            story.assignee = Some(value.to_string());
+                            continue;
+                        }
+                        _ => in_description = true,
+                    }
+                }
+                description.push(line);
+            }
+            None => {
+                if trimmed.is_empty() {
+                    continue;
+                }
+                match setting(trimmed) {
+                    Some((name, value)) => match name.as_str() {
+                        "project" => spec.project = Some(value.to_string()),
+                        "issue_type" => spec.issue_type = Some(value.to_string()),
+                        "link_type" => spec.link_type = Some(value.to_string()),
+                        _ => return Err(format!("unknown setting {}", name)),
+                    },
+                    None => {
+                        return Err(format!("unexpected line before the stories : {}", trimmed));
+                    }
+                }
+            }
+        }
+    }
+    if let Some(story) = current.take() {
+        spec.stories.push(finish_story(story, &description));
+    }
+    Ok(spec)
+}
+
+fn finish_story(mut story: StorySpec, description: &[&str]) -> StorySpec {
+    let text = description.join("\n").trim().to_string();
+    if !text.is_empty() {
+        story.description = Some(text);
+    }
+    story
+}
+
+/// name: value with a lowercase identifier as name
+fn setting(line: &str) -> Option<(String, &str)> {
+    let (name, value) = line.split_once(':')?;
+    let name = name.trim();
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_lowercase() || c == '_') {
+        return None;
+    }
+    Some((name.to_string(), value.trim()))
+}
+
+fn list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::jira::exit_code;
+
+    #[test]
+    fn markdown_settings_and_stories() {
+        let spec = parse_markdown(
+            "# WRIG-1\nproject: OPS\nissue_type: Task\nlink_type: Blocks\n\n## First\nlabels: security, release\ncomponents: build\nassignee: jdoe\n\nSign every artifact.\n\n## Second\n",
+        )
+        .unwrap();
+        assert_eq!(spec.epic, "WRIG-1");
+        assert_eq!(spec.project(), "OPS");
+        assert_eq!(spec.issue_type(), "Task");
+        assert_eq!(spec.link_type(), "Blocks");
+        assert_eq!(spec.stories.len(), 2);
+        let first = &spec.stories[0];
+        assert_eq!(first.summary, "First");
+        assert_eq!(first.labels, ["security", "release"]);
+        assert_eq!(first.components, ["build"]);
+        assert_eq!(first.assignee.as_deref(), Some("jdoe"));
+        assert_eq!(first.description.as_deref(), Some("Sign every artifact."));
+        let second = &spec.stories[1];
+        assert_eq!(second.summary, "Second");
+        assert!(second.labels.is_empty());
+        assert_eq!(second.description, None);
+    }
+
+    #[test]
+    fn markdown_description_starts_after_the_settings() {
+        let spec = parse_markdown(
+            "# WRIG-1\n## Unknown setting\npriority: High\nlabels: kept\n## After a blank line\nlabels: release\n\nlabels: foo\n* one\n\n{code}\nx: 1\n{code}\n",
+        )
+        .unwrap();
+        // a line that is not a known setting starts the description
+        let first = &spec.stories[0];
+        assert!(first.labels.is_empty());
+        assert_eq!(
+            first.description.as_deref(),
+            Some("priority: High\nlabels: kept")
+        );
+        // the blank line ends the settings, the description keeps its lines as they are
+        let second = &spec.stories[1];
+        assert_eq!(second.labels, ["release"]);
+        assert_eq!(
+            second.description.as_deref(),
+            Some("labels: foo\n* one\n\n{code}\nx: 1\n{code}")
+        );
+        assert_eq!(spec.project(), "WRIG");
+    }
+
+    #[test]
+    fn markdown_errors() {
+        assert_eq!(
+            parse_markdown("## Story\n# WRIG-1\n").unwrap_err(),
+            "the epic title must come before the stories"
+        );
+        assert_eq!(
+            parse_markdown("# WRIG-1\npriority: High\n## Story\n").unwrap_err(),
+            "unknown setting priority"
+        );
+        assert_eq!(
+            parse_markdown("# WRIG-1\nSome notes\n## Story\n").unwrap_err(),
+            "unexpected line before the stories : Some notes"
+        );
+    }
+
+    #[test]
+    fn read_errors_are_typed() {
+        let err = EpicSpec::read("docs/specs/missing.yaml").unwrap_err();
+        assert_eq!(exit_code(err.as_ref()), 11);
+        let err = EpicSpec::read("Cargo.toml").unwrap_err();
+        assert_eq!(exit_code(err.as_ref()), 3);
+        assert!(EpicSpec::read("docs/specs/stories.yaml").is_ok());
+    }
+}
@@ -0,0 +1,399 @@
+// module template
+//
+// a small mustache style template engine used to render the report view model
+//
+// {{name}}                     variable (dotted paths and {{.}} for the current item)
+// {{name|filter|filter:arg}}   variable with filters (trim, upper, lower, lines, indent, default)
+// {{#name}}...{{/name}}        section, repeated for lists, rendered once for truthy values
+// {{^name}}...{{/name}}        inverted section, rendered for empty lists and falsy values
+// {{! comment }}               ignored
+//
+// section and comment tags that sit alone on a line are removed together with that line
+
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Debug, Clone)]
+enum Node {
+    Text(String),
+    Var {
+        path: String,
+        filters: Vec<Filter>,
+    },
+    Section {
+        path: String,
+        inverted: bool,
+        children: Vec<Node>,
+    },
+}
+
+#[derive(Debug, Clone)]
+enum Filter {
+    Trim,
+    Upper,
+    Lower,
+    /// prefixes and terminates every line ("" stays "")
+    Lines(String),
+    /// prefixes every line
+    Indent(String),
+    Default(String),
+}
+
+#[derive(Debug)]
+enum Token {
+    Text(String),
+    Tag(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let tokens = tokenize(source)?;
+        let mut stack: Vec<(String, bool, Vec<Node>)> = vec![(String::new(), false, vec![])];
+        for token in tokens {
+            match token {
+                Token::Text(text) => {
+                    if !text.is_empty() {
+                        stack.last_mut().unwrap().2.push(Node::Text(text));
+                    }
+                }
+                Token::Tag(tag) => {
+                    if tag.starts_with('!') {
+                        continue;
+                    }
+                    if let Some(name) = tag.strip_prefix('#') {
+                        stack.push((name.trim().to_string(), false, vec![]));
+                    } else if let Some(name) = tag.strip_prefix('^') {
+                        stack.push((name.trim().to_string(), true, vec![]));
+                    } else if let Some(name) = tag.strip_prefix('/') {
+                        let name = name.trim();
+                        if stack.len() == 1 {
+                            return Err(Box::from(format!(
+                                "template : unexpected closing tag {{{{/{}}}}}",
+                                name
+                            )));
+                        }
+                        let (path, inverted, children) = stack.pop().unwrap();
+                        if path != name {
+                            return Err(Box::from(format!(
+                                "template : section {{{{#{}}}}} closed by {{{{/{}}}}}",
+                                path, name
+                            )));
+                        }
+                        stack.last_mut().unwrap().2.push(Node::Section {
+                            path,
+                            inverted,
+                            children,
+                        });
+                    } else {
+                        let mut parts = tag.split('|');
+                        let path = parts.next().unwrap_or("").trim().to_string();
+                        let filters = parts
+                            .map(parse_filter)
+                            .collect::<Result<Vec<Filter>, Box<dyn std::error::Error>>>()?;
+                        stack
+                            .last_mut()
+                            .unwrap()
+                            .2
+                            .push(Node::Var { path, filters });
+                    }
+                }
+            }
+        }
+        if stack.len() > 1 {
+            return Err(Box::from(format!(
+                "template : section {{{{#{}}}}} is not closed",
+                stack.last().unwrap().0
+            )));
+        }
+        Ok(Template {
+            nodes: stack.pop().unwrap().2,
+        })
+    }
+
+    pub fn render<T: Serialize>(&self, data: &T) -> Result<String, Box<dyn std::error::Error>> {
+        let value = serde_json::to_value(data)?;
+        let mut out = String::new();
+        render_nodes(&self.nodes, &mut vec![&value], &mut out);
+        Ok(out)
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, Box<dyn std::error::Error>> {
+    let mut tokens = vec![];
+    let mut pos = 0;
+    while let Some(open) = source[pos..].find("{{") {
+        let start = pos + open;
+        let close = match source[start + 2..].find("}}") {
+            Some(close) => start + 2 + close,
+            None => {
+                return Err(Box::from(format!(
+                    "template : unterminated tag at offset {}",
+                    start
+                )));
+            }
+        };
+        let tag = source[start + 2..close].trim().to_string();
+        let mut text_end = start;
+        let mut next = close + 2;
+        if tag.starts_with(['#', '^', '/', '!']) {
+            // standalone tags take their whole line with them
+            let line_start = source[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+            let line_end = source[next..]
+                .find('\n')
+                .map(|i| next + i + 1)
+                .unwrap_or(source.len());
+            if line_start >= pos
+                && source[line_start..start].trim().is_empty()
+                && source[next..line_end].trim().is_empty()
+            {
+                text_end = line_start;
+                next = line_end;
+            }
+        }
+        tokens.push(Token::Text(source[pos..text_end].to_string()));
+        tokens.push(Token::Tag(tag));
+        pos = next;
+    }
+    tokens.push(Token::Text(source[pos..].to_string()));
+    Ok(tokens)
+}
+
+fn parse_filter(filter: &str) -> Result<Filter, Box<dyn std::error::Error>> {
+    let filter = filter.trim_start();
+    let (name, arg) = match filter.split_once(':') {
+        Some((name, arg)) => (name.trim(), Some(unescape(arg))),
+        None => (filter.trim(), None),
+    };
+    match name {
+        "trim" => Ok(Filter::Trim),
+        "upper" => Ok(Filter::Upper),
+        "lower" => Ok(Filter::Lower),
+        "lines" => Ok(Filter::Lines(arg.unwrap_or_default())),
+        "indent" => Ok(Filter::Indent(arg.unwrap_or("  ".to_string()))),
+        "default" => Ok(Filter::Default(arg.unwrap_or_default())),
+        _ => Err(Box::from(format!("template : unknown filter {}", name))),
+    }
+}
+
+fn unescape(arg: &str) -> String {
+    arg.replace("\\t", "\t").replace("\\n", "\n")
+}
+
+fn render_nodes(nodes: &[Node], stack: &mut Vec<&Value>, out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Var { path, filters } => {
+                let value = lookup(stack, path);
+                let mut text = to_text(value);
+                for filter in filters {
+                    text = apply_filter(filter, text);
+                }
+                out.push_str(&text);
+            }
+            Node::Section {
+                path,
+                inverted,
+                children,
+            } => {
+                let value = lookup(stack, path);
+                match (inverted, value) {
+                    (true, value) if !is_truthy(value) => {
+                        render_nodes(children, stack, out);
+                    }
+                    (true, _) => {}
+                    (false, Some(Value::Array(items))) => {
+                        for item in items {
+                            stack.push(item);
+                            render_nodes(children, stack, out);
+                            stack.pop();
+                        }
+                    }
+                    (false, Some(value)) if is_truthy(Some(value)) => {
+                        stack.push(value);
+                        render_nodes(children, stack, out);
+                        stack.pop();
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// resolves a dotted path against the context stack (innermost first)
+fn lookup<'a>(stack: &[&'a Value], path: &str) -> Option<&'a Value> {
+    if path == "." {
+        return stack.last().copied();
+    }
+    let mut segments = path.split('.');
+    let first = segments.next()?;
+    let mut value = stack.iter().rev().find_map(|ctx| ctx.get(first))?;
+    for segment in segments {
+        value = match value {
+            Value::Array(items) => items.get(segment.parse::<usize>().ok()?)?,
+            _ => value.get(segment)?,
+        };
+    }
+    Some(value)
+}
+
+fn is_truthy(value: Option<&Value>) -> bool {
+    match value {
+        None | Some(Value::Null) => false,
+        Some(Value::Bool(b)) => *b,
+        Some(Value::String(s)) => !s.is_empty(),
+        Some(Value::Array(items)) => !items.is_empty(),
+        Some(Value::Number(n)) => n.as_f64() != Some(0.0),
+        Some(Value::Object(_)) => true,
+    }
+}
+
+fn to_text(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(value) => value.to_string(),
+    }
+}
+
+fn apply_filter(filter: &Filter, text: String) -> String {
+    match filter {
+        Filter::Trim => text.trim().to_string(),
+        Filter::Upper => text.to_uppercase(),
+        Filter::Lower => text.to_lowercase(),
+        Filter::Lines(prefix) => text
+            .lines()
+            .map(|line| format!("{}{}\n", prefix, line))
+            .collect(),
+        Filter::Indent(prefix) => text
+            .lines()
+            .map(|line| format!("{}{}", prefix, line))
+            .collect::<Vec<String>>()
+            .join("\n"),
+        Filter::Default(default) => match text.is_empty() {
+            true => default.clone(),
+            false => text,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn render(source: &str, data: Value) -> String {
+        Template::parse(source).unwrap().render(&data).unwrap()
+    }
+
+    fn parse_error(source: &str) -> String {
+        Template::parse(source).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn variables() {
+        let data = json!({ "title": "Report", "epic": { "key": "WRIG-1" }, "count": 3 });
+        assert_eq!(render("# {{title}}", data.clone()), "# Report");
+        assert_eq!(
+            render("{{ epic.key }} ({{count}})", data.clone()),
+            "WRIG-1 (3)"
+        );
+        assert_eq!(
+            render("{{title|upper}} {{title|lower}}", data.clone()),
+            "REPORT report"
+        );
+        assert_eq!(render("[{{missing|default:none}}]", data), "[none]");
+    }
+
+    #[test]
+    fn line_filters() {
+        let data = json!({ "text": "one\ntwo" });
+        assert_eq!(render("{{text|lines:\\t}}", data.clone()), "\tone\n\ttwo\n");
+        assert_eq!(render("{{text|lines:>}}", data.clone()), ">one\n>two\n");
+        assert_eq!(render("{{text|indent}}", data), "  one\n  two");
+    }
+
+    #[test]
+    fn sections_over_lists() {
+        let data = json!({ "stories": [{ "key": "WRIG-2" }, { "key": "WRIG-3" }] });
+        assert_eq!(
+            render("{{#stories}}- {{key}}\n{{/stories}}", data),
+            "- WRIG-2\n- WRIG-3\n"
+        );
+        let data = json!({ "keys": ["A", "B"] });
+        assert_eq!(render("{{#keys}}{{.}},{{/keys}}", data), "A,B,");
+    }
+
+    #[test]
+    fn sections_over_values() {
+        let data = json!({ "yes": true, "no": false, "zero": 0, "empty": "", "epic": { "key": "WRIG-1" } });
+        assert_eq!(render("{{#yes}}shown{{/yes}}", data.clone()), "shown");
+        assert_eq!(render("{{#no}}hidden{{/no}}", data.clone()), "");
+        assert_eq!(render("{{#zero}}hidden{{/zero}}", data.clone()), "");
+        assert_eq!(render("{{#empty}}hidden{{/empty}}", data.clone()), "");
+        // objects become the context of the section
+        assert_eq!(render("{{#epic}}{{key}}{{/epic}}", data), "WRIG-1");
+    }
+
+    #[test]
+    fn inverted_sections() {
+        let data = json!({ "stories": [], "done": true });
+        assert_eq!(
+            render("{{^stories}}No stories{{/stories}}", data.clone()),
+            "No stories"
+        );
+        assert_eq!(render("{{^done}}open{{/done}}", data.clone()), "");
+        assert_eq!(render("{{^missing}}default{{/missing}}", data), "default");
+    }
+
+    #[test]
+    fn nested_sections() {
+        let data = json!({
+            "title": "Report",
+            "epics": [
+                { "key": "WRIG-1", "stories": [{ "key": "WRIG-2" }] },
+                { "key": "WRIG-4", "stories": [] },
+            ],
+        });
+        let source = "{{#epics}}\n{{key}}:{{#stories}} {{key}} of {{title}}{{/stories}}{{^stories}} none{{/stories}}\n{{/epics}}\n";
+        // inner sections see the outer contexts, standalone tags take their line with them
+        assert_eq!(
+            render(source, data),
+            "WRIG-1: WRIG-2 of Report\nWRIG-4: none\n"
+        );
+    }
+
+    #[test]
+    fn missing_keys_render_empty() {
+        let data = json!({ "epic": {} });
+        assert_eq!(
+            render(
+                "[{{missing}}][{{epic.key}}][{{epic.key.deep}}]",
+                data.clone()
+            ),
+            "[][][]"
+        );
+        assert_eq!(render("{{#missing}}hidden{{/missing}}", data), "");
+    }
+
+    #[test]
+    fn comments_are_dropped() {
+        assert_eq!(render("a\n{{! note }}\nb", json!({})), "a\nb");
+    }
+
+    #[test]
+    fn malformed_templates_are_errors() {
+        assert!(parse_error("{{#epics}}open").contains("is not closed"));
+        assert!(parse_error("{{^epics}}open").contains("is not closed"));
+        assert!(parse_error("text {{/epics}}").contains("unexpected closing tag"));
+        assert!(parse_error("{{#epics}}{{/stories}}").contains("closed by"));
+        assert!(parse_error("{{title").contains("unterminated tag"));
+        assert!(parse_error("{{title|bogus}}").contains("unknown filter"));
+    }
+}
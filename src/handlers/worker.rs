@@ -0,0 +1,396 @@
+// module worker
+//
+// background worker mode, polls the formdata queue and treats every entry as a report job
+//
+// title      report title
+// prompt     comma separated issue keys (WRIG-1,WRIG-4) or a jql query
+// category   output format (markdown, html, json, csv, asciidoc), else taken from file
+// file       key of the generated document in the documents store
+// run_once   "off" keeps the job queued so it is regenerated on every poll, otherwise the
+//            job is moved to the archive once the document is saved
+//
+// a failed job stays queued for the next polls, after MAX_JOB_ATTEMPTS failed runs it is
+// moved to the failed store with the error of its last run
+
+use crate::config::load::Parameters;
+use crate::handlers::common::shutdown_signal;
+use crate::handlers::document::{Document, DocumentformInterface, FormData};
+use crate::handlers::render::{ReportFormat, get_renderer};
+use crate::handlers::report::ReportWindow;
+use crate::handlers::service::{
+    ReportOptions, ReportQuery, Service, ServiceInterface, default_format,
+};
+use clap::ValueEnum;
+use custom_logger as log;
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// seconds between queue polls when poll_interval_secs is not set
+const DEFAULT_POLL_INTERVAL_SECS: u64 = 30;
+
+/// failed runs before a job is moved from the queue to the failed store
+const MAX_JOB_ATTEMPTS: u32 = 3;
+
+/// polls the queue until ctrl-c or sigterm, a job in progress is finished before exiting
+pub async fn run_worker(params: Parameters) -> Result<(), Box<dyn std::error::Error>> {
+    let db_path = queue_db_path(&params);
+    let interval = Duration::from_secs(
+        params
+            .poll_interval_secs
+            .unwrap_or(DEFAULT_POLL_INTERVAL_SECS)
+            .max(1),
+    );
+    std::fs::create_dir_all(&db_path)?;
+    log::info!("polling {}/queue every {}s", db_path, interval.as_secs());
+    let (shutdown_tx, mut shutdown_rx) = watch::channel(false);
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        let _ = shutdown_tx.send(true);
+    });
+    loop {
+        let keys = Document::list_queue(db_path.clone()).await?;
+        for key in keys {
+            if *shutdown_rx.borrow() {
+                break;
+            }
+            run_job(&params, &db_path, &key).await;
+        }
+        if *shutdown_rx.borrow() {
+            break;
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = shutdown_rx.changed() => {}
+        }
+        if *shutdown_rx.borrow() {
+            break;
+        }
+    }
+    log::info!("shutting down");
+    Ok(())
+}
+
+/// queue_db_path, else {working_dir}/formdata
+pub fn queue_db_path(params: &Parameters) -> String {
+    params
+        .queue_db_path
+        .clone()
+        .unwrap_or(format!("{}/formdata", params.working_dir))
+}
+
+/// queue command, lists the queued jobs
+pub async fn list_jobs(params: &Parameters) -> Result<String, Box<dyn std::error::Error>> {
+    let db_path = queue_db_path(params);
+    let keys = Document::list_queue(db_path.clone()).await?;
+    let mut table = format!(
+        "{:<20} {:<12} {:<6} {:<30} {}\n",
+        "KEY", "FORMAT", "ONCE", "FILE", "PROMPT"
+    );
+    for key in keys.iter() {
+        let fd = Document::get_formdata(format!("{}/queue", db_path), key.clone()).await?;
+        table.push_str(&format!(
+            "{:<20} {:<12} {:<6} {:<30} {}\n",
+            key, fd.category, fd.run_once, fd.file, fd.prompt
+        ));
+    }
+    table.push_str(&format!("\n{} queued jobs", keys.len()));
+    Ok(table)
+}
+
+/// queue command, adds (or replaces) a report job, the prompt and format are checked the way
+/// the worker reads them
+pub async fn add_job(
+    params: &Parameters,
+    key: &str,
+    fd: FormData,
+) -> Result<String, Box<dyn std::error::Error>> {
+    job_query(&fd.prompt)?;
+    if fd.file.trim().is_empty() {
+        return Err(Box::from("file is empty (expected the document key)"));
+    }
+    let format = job_format(params, &fd);
+    let db_path = queue_db_path(params);
+    std::fs::create_dir_all(&db_path)?;
+    Document::queue_formdata(db_path, key.to_string(), fd).await?;
+    Ok(format!(
+        "job {} queued ({})",
+        key,
+        format!("{:?}", format).to_lowercase()
+    ))
+}
+
+/// queue command, drops a queued job
+pub async fn remove_job(
+    params: &Parameters,
+    key: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let db_path = queue_db_path(params);
+    let keys = Document::list_queue(db_path.clone()).await?;
+    if !keys.iter().any(|queued| queued == key) {
+        return Err(Box::from(format!("job {} is not queued", key)));
+    }
+    Document::remove_formdata(db_path, key.to_string()).await?;
+    Ok(format!("job {} removed", key))
+}
+
+/// runs a queued job, a failure is counted on the job so it is not retried forever
+async fn run_job(params: &Parameters, db_path: &str, key: &str) {
+    let err = match process_job(params, db_path, key).await {
+        Ok(()) => return,
+        Err(e) => e.to_string(),
+    };
+    log::error!("job {} : {}", key, err);
+    if let Err(e) = record_failure(db_path, key, err).await {
+        log::error!("job {} : failure not recorded : {}", key, e);
+    }
+}
+
+/// keeps the job queued with the error until MAX_JOB_ATTEMPTS, then moves it to failed
+async fn record_failure(
+    db_path: &str,
+    key: &str,
+    error: String,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut fd = Document::get_formdata(format!("{}/queue", db_path), key.to_string()).await?;
+    // removed by another consumer while it ran
+    if fd.file.is_empty() {
+        return Ok(());
+    }
+    fd.attempts += 1;
+    fd.error = Some(error);
+    if fd.attempts >= MAX_JOB_ATTEMPTS {
+        log::warn!(
+            "job {} failed {} times, moved to {}/failed",
+            key,
+            fd.attempts,
+            db_path
+        );
+        Document::fail_formdata(db_path.to_string(), key.to_string(), fd).await?;
+    } else {
+        Document::queue_formdata(db_path.to_string(), key.to_string(), fd).await?;
+    }
+    Ok(())
+}
+
+async fn process_job(
+    params: &Parameters,
+    db_path: &str,
+    key: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let fd = Document::get_formdata(format!("{}/queue", db_path), key.to_string()).await?;
+    // removed by another consumer since the queue was listed
+    if fd.file.is_empty() {
+        return Ok(());
+    }
+    log::info!("job {} : {} -> {}", key, fd.title, fd.file);
+    let format = job_format(params, &fd);
+    let mut params = params.clone();
+    if !fd.title.trim().is_empty() {
+        params.report_title = Some(fd.title.trim().to_string());
+    }
+    let renderer = get_renderer(format, &params)?;
+    let options = ReportOptions {
+        query: job_query(&fd.prompt)?,
+        refresh: false,
+        formats: vec![format],
+        window: ReportWindow::new(None, None),
+        comment_epics: false,
+        dry_run: false,
+    };
+    let report = Service::build_report(&params, &options).await?;
+    let document = renderer.render(&report)?;
+    let res = match fd.run_once.trim().eq_ignore_ascii_case("off") {
+        true => Document::save_document(db_path.to_string(), fd.file.clone(), document).await?,
+        false => Document::save_formdata(db_path.to_string(), key.to_string(), document).await?,
+    };
+    log::debug!("[process_job] {}", res);
+    Ok(())
+}
+
+/// category, then the extension of file, then the configured default format
+fn job_format(params: &Parameters, fd: &FormData) -> ReportFormat {
+    let extension = Path::new(&fd.file)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default();
+    [fd.category.trim(), extension]
+        .iter()
+        .find_map(|value| ReportFormat::from_str(value, true).ok())
+        .unwrap_or(default_format(params))
+}
+
+/// a prompt made only of issue keys selects those issues, anything else is used as jql
+fn job_query(prompt: &str) -> Result<ReportQuery, Box<dyn std::error::Error>> {
+    let prompt = prompt.trim();
+    if prompt.is_empty() {
+        return Err(Box::from("prompt is empty (expected issue keys or jql)"));
+    }
+    let keys = prompt
+        .split(',')
+        .map(|key| key.trim().to_string())
+        .filter(|key| !key.is_empty())
+        .collect::<Vec<String>>();
+    match keys.iter().all(|key| is_issue_key(key)) {
+        true => Ok(ReportQuery::Issues(keys)),
+        false => Ok(ReportQuery::Jql(prompt.to_string())),
+    }
+}
+
+/// PROJECT-123
+fn is_issue_key(value: &str) -> bool {
+    match value.split_once('-') {
+        Some((project, number)) => {
+            project.starts_with(|c: char| c.is_ascii_uppercase())
+                && project
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+                && !number.is_empty()
+                && number.chars().all(|c| c.is_ascii_digit())
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::document::db_get;
+    use serde_json::json;
+
+    fn job(file: &str, category: &str) -> FormData {
+        FormData {
+            key: None,
+            title: String::new(),
+            file: file.to_string(),
+            category: category.to_string(),
+            prompt: "WRIG-1".to_string(),
+            credentials: String::new(),
+            run_once: "on".to_string(),
+            db: "formdata".to_string(),
+            attempts: 0,
+            error: None,
+        }
+    }
+
+    /// test mode over docs/fixtures with the queue in a scratch directory
+    async fn queued(name: &str, fd: FormData) -> (Parameters, String) {
+        let working_dir =
+            std::env::temp_dir().join(format!("jira-service-{}-{}", name, std::process::id()));
+        let params: Parameters = serde_json::from_value(json!({
+            "test": true,
+            "fixtures_dir": concat!(env!("CARGO_MANIFEST_DIR"), "/docs/fixtures"),
+            "working_dir": working_dir.to_string_lossy(),
+            "base_url": "https://jira.example.com/rest/api/2/issue/",
+            "story_link_types": ["Relates"],
+        }))
+        .unwrap();
+        let db_path = queue_db_path(&params);
+        std::fs::create_dir_all(&db_path).unwrap();
+        Document::queue_formdata(db_path.clone(), "weekly".to_string(), fd)
+            .await
+            .unwrap();
+        (params, db_path)
+    }
+
+    #[tokio::test]
+    async fn run_once_jobs_move_to_the_archive() {
+        let (params, db_path) = queued("worker-once", job("weekly.md", "")).await;
+        run_job(&params, &db_path, "weekly").await;
+
+        assert!(
+            Document::list_queue(db_path.clone())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        let archived = Document::get_formdata(format!("{}/archive", db_path), "weekly".into())
+            .await
+            .unwrap();
+        assert_eq!(archived.key.as_deref(), Some("weekly"));
+        let document = db_get(format!("{}/documents", db_path), "weekly.md".into())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(document.contains("WRIG-1"));
+        let _ = std::fs::remove_dir_all(&params.working_dir);
+    }
+
+    #[tokio::test]
+    async fn repeated_jobs_stay_queued() {
+        let mut fd = job("weekly.html", "");
+        fd.run_once = "off".to_string();
+        let (params, db_path) = queued("worker-repeat", fd).await;
+        run_job(&params, &db_path, "weekly").await;
+
+        assert_eq!(
+            Document::list_queue(db_path.clone()).await.unwrap(),
+            ["weekly"]
+        );
+        let archived = Document::get_formdata(format!("{}/archive", db_path), "weekly".into())
+            .await
+            .unwrap();
+        assert!(archived.file.is_empty());
+        let document = db_get(format!("{}/documents", db_path), "weekly.html".into())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(document.starts_with("<!DOCTYPE html>"));
+        let _ = std::fs::remove_dir_all(&params.working_dir);
+    }
+
+    #[tokio::test]
+    async fn failing_jobs_move_to_failed() {
+        let mut fd = job("weekly.md", "");
+        fd.prompt = "WRIG-99".to_string();
+        let (params, db_path) = queued("worker-failed", fd).await;
+        let queue = format!("{}/queue", db_path);
+
+        run_job(&params, &db_path, "weekly").await;
+        let retried = Document::get_formdata(queue.clone(), "weekly".into())
+            .await
+            .unwrap();
+        assert_eq!(retried.attempts, 1);
+        assert!(retried.error.is_some_and(|error| error.contains("WRIG-99")));
+
+        for _ in 1..MAX_JOB_ATTEMPTS {
+            run_job(&params, &db_path, "weekly").await;
+        }
+        assert!(
+            Document::list_queue(db_path.clone())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        let failed = Document::get_formdata(format!("{}/failed", db_path), "weekly".into())
+            .await
+            .unwrap();
+        assert_eq!(failed.attempts, MAX_JOB_ATTEMPTS);
+        assert!(
+            db_get(format!("{}/documents", db_path), "weekly.md".into())
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let _ = std::fs::remove_dir_all(&params.working_dir);
+    }
+
+    #[test]
+    fn job_format_falls_back_to_the_config() {
+        let params: Parameters = serde_json::from_value(json!({ "formats": [] })).unwrap();
+        assert_eq!(
+            job_format(&params, &job("report", "")),
+            ReportFormat::Markdown
+        );
+        assert_eq!(
+            job_format(&params, &job("report.html", "")),
+            ReportFormat::Html
+        );
+        assert_eq!(
+            job_format(&params, &job("report.html", "csv")),
+            ReportFormat::Csv
+        );
+        let params: Parameters = serde_json::from_value(json!({ "formats": ["json"] })).unwrap();
+        assert_eq!(job_format(&params, &job("report", "")), ReportFormat::Json);
+    }
+}
@@ -1,6 +1,15 @@
-use crate::cli::schema::Cli;
-use crate::config::load::{ConfigInterface, ImplConfigInterface};
-use crate::handlers::service::{Service, ServiceInterface};
+use crate::cli::schema::{CacheCommand, Cli, Command, ConfigCommand, QueueCommand, ReportArgs};
+use crate::config::load::{ConfigInterface, ImplConfigInterface, Parameters};
+use crate::config::validate::validate;
+use crate::error::jira::{JiraServiceError, exit_code};
+use crate::handlers::browse::BrowseLinks;
+use crate::handlers::cache::{clear_cache, show_cache};
+use crate::handlers::document::FormData;
+use crate::handlers::output::{render_issue, search_table};
+use crate::handlers::report::ReportWindow;
+use crate::handlers::server::serve;
+use crate::handlers::service::{ReportOptions, ReportQuery, Service, ServiceInterface};
+use crate::handlers::worker::{add_job, list_jobs, remove_job, run_worker};
 use clap::Parser;
 use custom_logger as log;
 
@@ -12,7 +21,7 @@ mod handlers;
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Cli::parse();
-    let config = args.config;
+    let config = args.config.clone();
     let impl_config = ImplConfigInterface {};
 
     // setup logging
@@ -22,10 +31,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .expect("log should initialize");
 
     // read and parse config
-    let params = impl_config.read(config);
-    if params.is_err() {
-        log::error!("{}", params.err().unwrap());
-        std::process::exit(1);
+    let params = impl_config.read(config.clone(), &args.set);
+    if let Err(err) = params.as_ref() {
+        log::error!("{}", err);
+        std::process::exit(exit_code(err.as_ref()));
     }
 
     let level = match params.as_ref().unwrap().log_level.as_str() {
@@ -43,14 +52,129 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     log::info!("author      : {}", env!("CARGO_PKG_AUTHORS"));
     log::info!("version     : {}", env!("CARGO_PKG_VERSION"));
 
-    let res = Service::execute(params.unwrap(), args.issues).await;
+    let mut params = params.unwrap();
+    let res = match args.command {
+        Command::Report(report) => run_report(params, report).await,
+        Command::Get {
+            key,
+            format,
+            refresh,
+        } => match Service::get_issue(&params, &key, refresh).await {
+            Ok(jira) => {
+                let links = BrowseLinks::new(&params.base_url, params.browse_url.as_deref())
+                    .with_projects([jira.key.as_str()]);
+                render_issue(&jira, format, &links)
+            }
+            Err(err) => Err(err),
+        },
+        Command::Search { jql, limit } => Service::search(&params, &jql, Some(limit))
+            .await
+            .map(|issues| search_table(&issues)),
+        Command::Cache { action } => match action {
+            CacheCommand::Show { key } => show_cache(&params, key.as_deref()).await,
+            CacheCommand::Clear { key, .. } => clear_cache(&params, key.as_deref()).await,
+        },
+        Command::Queue { action } => match action {
+            QueueCommand::List => list_jobs(&params).await,
+            QueueCommand::Add {
+                key,
+                prompt,
+                file,
+                title,
+                format,
+                repeat,
+            } => {
+                let fd = FormData {
+                    key: None,
+                    title,
+                    file,
+                    category: format
+                        .map(|format| format!("{:?}", format).to_lowercase())
+                        .unwrap_or_default(),
+                    prompt,
+                    credentials: String::new(),
+                    run_once: if repeat { "off" } else { "on" }.to_string(),
+                    db: "formdata".to_string(),
+                    attempts: 0,
+                    error: None,
+                };
+                add_job(&params, &key, fd).await
+            }
+            QueueCommand::Remove { key } => remove_job(&params, &key).await,
+        },
+        Command::Serve { address } => {
+            if address.is_some() {
+                params.server_address = address;
+            }
+            serve(params).await.map(|_| "exit => 0".to_string())
+        }
+        Command::Worker => run_worker(params).await.map(|_| "exit => 0".to_string()),
+        Command::Comment {
+            key,
+            message,
+            dry_run,
+        } => Service::comment(&params, &key, &message, dry_run).await,
+        Command::Transitions { key } => Service::list_transitions(&params, &key).await,
+        Command::Transition {
+            key,
+            to,
+            message,
+            dry_run,
+        } => Service::transition(&params, &key, &to, message.as_deref(), dry_run).await,
+        Command::Create { spec, dry_run } => Service::create(&params, &spec, dry_run).await,
+        Command::Config {
+            action: ConfigCommand::Validate,
+        } => validate(&config.unwrap_or("defaults".to_string()), &params).map_err(|e| e.into()),
+    };
+    exit_with(res)
+}
+
+/// report command, today's single report run
+async fn run_report(
+    params: Parameters,
+    args: ReportArgs,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let query = match args.jql {
+        Some(jql) => ReportQuery::Jql(jql),
+        None => ReportQuery::Issues(
+            args.issues
+                .unwrap_or_default()
+                .split(",")
+                .map(|issue| issue.trim().to_string())
+                .filter(|issue| !issue.is_empty())
+                .collect(),
+        ),
+    };
+
+    let window = match ReportWindow::parse(args.since.as_deref(), args.until.as_deref()) {
+        Ok(window) => window,
+        Err(err) => return Err(Box::new(JiraServiceError::config("--since/--until", err))),
+    };
+
+    let options = ReportOptions {
+        query,
+        refresh: args.refresh,
+        formats: args.format,
+        window,
+        comment_epics: args.comment_epics,
+        dry_run: args.dry_run,
+    };
+
+    Service::execute(params, options).await
+}
+
+fn exit_with(
+    res: Result<String, Box<dyn std::error::Error>>,
+) -> Result<(), Box<dyn std::error::Error>> {
     match res {
         Ok(result) => {
             println!("{}", result);
         }
         Err(e) => {
-            println!("{}", e.to_string());
-            println!("exit => 2")
+            let code = exit_code(e.as_ref());
+            println!("{}", e);
+            println!("exit => {}", code);
+            std::process::exit(code);
         }
     }
     Ok(())